@@ -0,0 +1,238 @@
+use std::thread;
+use color_eyre::eyre::bail;
+use color_eyre::Result;
+use tokio::sync::{mpsc, oneshot};
+use crate::{exec_or_eyre, InputBatch, InputEmulator, KeyCode, Macro, MacroCancel, MacroStatus, OS_Input_Coord};
+
+// Commands waiting for the worker, senders wait for a free slot once it's full
+pub const DEFAULT_QUEUE_CAPACITY: usize = 64;
+
+type Job = Box<dyn FnOnce(&mut InputEmulator) + Send>;
+
+// Clone + Send + Sync handle to an emulator owned by a dedicated worker thread.
+// Commands run one at a time in the order they were sent, each call waits for its own result.
+// A future dropped after sending doesn't cancel its command. The worker stops and the backend
+// is dropped when the last handle is dropped.
+#[derive(Clone)]
+pub struct AsyncInputEmulator {
+    sender: mpsc::Sender<Job>,
+}
+
+impl AsyncInputEmulator {
+    pub async fn new() -> Result<Self> {
+        Self::spawn(InputEmulator::new, DEFAULT_QUEUE_CAPACITY).await
+    }
+
+    // The backend is created on the worker thread, so it doesn't have to be Send
+    pub async fn spawn<F>(create: F, capacity: usize) -> Result<Self>
+    where
+        F: FnOnce() -> Result<InputEmulator> + Send + 'static,
+    {
+        if capacity == 0 {
+            bail!("Queue capacity must be at least 1");
+        }
+        let (sender, mut receiver) = mpsc::channel::<Job>(capacity);
+        let (ready_sender, ready_receiver) = oneshot::channel();
+
+        exec_or_eyre!(thread::Builder::new()
+            .name("universal_input".to_string())
+            .spawn(move || {
+                let mut emulator = match create() {
+                    Ok(emulator) => emulator,
+                    Err(err) => {
+                        let _ = ready_sender.send(Err(err));
+                        return;
+                    }
+                };
+                let _ = ready_sender.send(Ok(()));
+                while let Some(job) = receiver.blocking_recv() {
+                    job(&mut emulator);
+                }
+            }))?;
+
+        match ready_receiver.await {
+            Ok(result) => result.map(|()| Self { sender }),
+            Err(_) => bail!("Input worker panicked while creating the backend"),
+        }
+    }
+
+    // Runs `f` on the worker thread after everything sent before it
+    pub async fn with<R, F>(&self, f: F) -> Result<R>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut InputEmulator) -> R + Send + 'static,
+    {
+        let (reply_sender, reply_receiver) = oneshot::channel();
+        let job: Job = Box::new(move |emulator| {
+            let _ = reply_sender.send(f(emulator));
+        });
+        if self.sender.send(job).await.is_err() {
+            bail!("Input worker has stopped");
+        }
+        match reply_receiver.await {
+            Ok(result) => Ok(result),
+            Err(_) => bail!("Input worker stopped before finishing the command"),
+        }
+    }
+
+    #[inline]
+    async fn run<F>(&self, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut InputEmulator) -> Result<()> + Send + 'static,
+    {
+        self.with(f).await?
+    }
+
+    pub async fn press(&self, key_code: KeyCode) -> Result<()> {
+        self.run(move |emulator| emulator.press(key_code)).await
+    }
+
+    pub async fn release(&self, key_code: KeyCode) -> Result<()> {
+        self.run(move |emulator| emulator.release(key_code)).await
+    }
+
+    pub async fn move_mouse(&self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        self.run(move |emulator| emulator.move_mouse(x, y)).await
+    }
+
+    pub async fn move_mouse_x(&self, x: OS_Input_Coord) -> Result<()> {
+        self.run(move |emulator| emulator.move_mouse_x(x)).await
+    }
+
+    pub async fn move_mouse_y(&self, y: OS_Input_Coord) -> Result<()> {
+        self.run(move |emulator| emulator.move_mouse_y(y)).await
+    }
+
+    pub async fn gradual_move_mouse(&self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        self.run(move |emulator| emulator.gradual_move_mouse(x, y)).await
+    }
+
+    pub async fn scroll_x(&self, value: OS_Input_Coord) -> Result<()> {
+        self.run(move |emulator| emulator.scroll_x(value)).await
+    }
+
+    pub async fn scroll_y(&self, value: OS_Input_Coord) -> Result<()> {
+        self.run(move |emulator| emulator.scroll_y(value)).await
+    }
+
+    pub async fn gradual_scroll(&self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        self.run(move |emulator| emulator.gradual_scroll(x, y)).await
+    }
+
+    pub async fn write_buffer(&self, batch: InputBatch) -> Result<()> {
+        self.run(move |emulator| emulator.write_buffer(&batch)).await
+    }
+
+    // Commands sent while the macro runs wait behind it, cancel it through `cancel`
+    pub async fn run_macro(&self, input_macro: Macro, cancel: MacroCancel) -> Result<MacroStatus> {
+        self.with(move |emulator| emulator.run_macro(&input_macro, &cancel)).await?
+    }
+}
+
+#[cfg(all(test, feature = "use_uinput"))]
+mod tests {
+    use std::io::{PipeReader, Read};
+    use std::time::Duration;
+    use color_eyre::eyre::eyre;
+    use crate::key_codes::{EV_KEY, EV_SYN, KEY_A, KEY_B, SYN_REPORT};
+    use crate::uinput::tests::decode_events;
+    use super::*;
+
+    async fn piped(capacity: usize) -> (AsyncInputEmulator, PipeReader) {
+        let (reader, writer) = std::io::pipe().unwrap();
+        let emulator = AsyncInputEmulator::spawn(move || Ok(InputEmulator::from_writer(writer)), capacity).await.unwrap();
+        (emulator, reader)
+    }
+
+    // Key events as (code, value), returns once the worker has dropped the backend
+    async fn keys_written(mut reader: PipeReader) -> Vec<(u16, i32)> {
+        let written = tokio::task::spawn_blocking(move || {
+            let mut written = vec![];
+            reader.read_to_end(&mut written).unwrap();
+            written
+        }).await.unwrap();
+        decode_events(&written).into_iter()
+            .filter(|(event_type, _, _)| *event_type == EV_KEY)
+            .map(|(_, code, value)| (code, value))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn clones_keep_send_order() {
+        let (first, reader) = piped(DEFAULT_QUEUE_CAPACITY).await;
+        let second = first.clone();
+
+        first.press(KeyCode::KEY_A).await.unwrap();
+        second.press(KeyCode::KEY_B).await.unwrap();
+        let (released_b, released_a) = tokio::join!(second.release(KeyCode::KEY_B), first.release(KeyCode::KEY_A));
+        released_b.unwrap();
+        released_a.unwrap();
+        drop((first, second));
+
+        assert_eq!(keys_written(reader).await, [(KEY_A, 1), (KEY_B, 1), (KEY_B, 0), (KEY_A, 0)]);
+    }
+
+    #[tokio::test]
+    async fn full_queue_blocks_senders() {
+        let (emulator, mut reader) = piped(1).await;
+
+        let mut input_macro = Macro::new();
+        input_macro.press(KeyCode::KEY_A).delay(10_000).release(KeyCode::KEY_A);
+        let cancel = MacroCancel::new();
+        let running = tokio::spawn({
+            let (emulator, cancel) = (emulator.clone(), cancel.clone());
+            async move { emulator.run_macro(input_macro, cancel).await }
+        });
+
+        // Once the press is written the worker sits in the macro's delay
+        let (reader, pressed) = tokio::task::spawn_blocking(move || {
+            let mut pressed = vec![0; 2 * size_of::<libc::input_event>()];
+            reader.read_exact(&mut pressed).unwrap();
+            (reader, pressed)
+        }).await.unwrap();
+        assert_eq!(decode_events(&pressed), [(EV_KEY, KEY_A, 1), (EV_SYN, SYN_REPORT, 0)]);
+
+        // The first sender takes the only slot
+        let queued = tokio::spawn({
+            let emulator = emulator.clone();
+            async move { emulator.press(KeyCode::KEY_B).await }
+        });
+        while emulator.sender.capacity() > 0 {
+            tokio::task::yield_now().await;
+        }
+        // The second one waits for it. Dropped before a slot frees up, its command is never sent.
+        let blocked = tokio::time::timeout(Duration::from_millis(50), emulator.press(KeyCode::KEY_C)).await;
+        assert!(blocked.is_err());
+
+        cancel.cancel();
+        assert_eq!(running.await.unwrap().unwrap(), MacroStatus::Cancelled);
+        queued.await.unwrap().unwrap();
+        drop(emulator);
+
+        // The cancelled macro released KEY_A, KEY_C never made it
+        assert_eq!(keys_written(reader).await, [(KEY_A, 0), (KEY_B, 1)]);
+    }
+
+    #[tokio::test]
+    async fn last_handle_stops_the_worker() {
+        let (emulator, reader) = piped(DEFAULT_QUEUE_CAPACITY).await;
+        let clone = emulator.clone();
+        emulator.press(KeyCode::KEY_A).await.unwrap();
+        drop(emulator);
+        // A clone still keeps it running
+        clone.release(KeyCode::KEY_A).await.unwrap();
+        drop(clone);
+
+        // The backend, and with it the write end of the pipe, is gone
+        let keys = tokio::time::timeout(Duration::from_secs(5), keys_written(reader)).await.unwrap();
+        assert_eq!(keys, [(KEY_A, 1), (KEY_A, 0)]);
+    }
+
+    #[tokio::test]
+    async fn create_error_is_returned() {
+        let result = AsyncInputEmulator::spawn(|| Err(eyre!("No such device")), 1).await;
+        assert_eq!(result.err().unwrap().to_string(), "No such device");
+        let result = AsyncInputEmulator::spawn(|| unreachable!(), 0).await;
+        assert_eq!(result.err().unwrap().to_string(), "Queue capacity must be at least 1");
+    }
+}
@@ -0,0 +1,264 @@
+use serde::{Deserialize, Serialize};
+use crate::{KeyCode, OS_Input_Coord};
+use crate::utils::GradualMove;
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum InputEvent {
+    Move(OS_Input_Coord, OS_Input_Coord),
+    Scroll(OS_Input_Coord, OS_Input_Coord),
+    Press(KeyCode),
+    Release(KeyCode),
+    // Ends a frame: everything queued before it is delivered together
+    Sync,
+}
+
+#[derive(PartialEq, Eq, Clone, Default, Debug, Serialize, Deserialize)]
+pub struct InputBatch {
+    events: Vec<InputEvent>,
+}
+
+impl InputBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            events: Vec::with_capacity(capacity),
+        }
+    }
+
+    #[inline]
+    pub fn events(&self) -> &[InputEvent] {
+        &self.events
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    #[inline]
+    pub fn iter(&self) -> std::slice::Iter<'_, InputEvent> {
+        self.events.iter()
+    }
+
+    // True when the last queued event is not followed by a Sync
+    #[inline]
+    pub fn is_pending_sync(&self) -> bool {
+        !matches!(self.events.last(), None | Some(InputEvent::Sync))
+    }
+
+    #[inline]
+    pub fn push(&mut self, event: InputEvent) -> &mut Self {
+        self.events.push(event);
+        self
+    }
+
+    #[inline]
+    pub fn append(&mut self, other: &mut InputBatch) -> &mut Self {
+        self.events.append(&mut other.events);
+        self
+    }
+
+    #[inline]
+    pub fn move_mouse_x(&mut self, x: OS_Input_Coord) -> &mut Self {
+        self.push(InputEvent::Move(x, 0))
+    }
+
+    #[inline]
+    pub fn move_mouse_y(&mut self, y: OS_Input_Coord) -> &mut Self {
+        self.push(InputEvent::Move(0, y))
+    }
+
+    #[inline]
+    pub fn move_mouse(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> &mut Self {
+        self.push(InputEvent::Move(x, y))
+    }
+
+    pub fn gradual_move_mouse(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> &mut Self {
+        let gradual_move = GradualMove::calculate(x, y);
+
+        for _ in 0..gradual_move.both_move {
+            self.move_mouse(gradual_move.x_direction, gradual_move.y_direction);
+        }
+        for _ in 0..gradual_move.move_only_x {
+            self.move_mouse_x(gradual_move.x_direction);
+        }
+        for _ in 0..gradual_move.move_only_y {
+            self.move_mouse_y(gradual_move.y_direction);
+        }
+        self
+    }
+
+    #[inline]
+    pub fn scroll_x(&mut self, value: OS_Input_Coord) -> &mut Self {
+        self.push(InputEvent::Scroll(value, 0))
+    }
+
+    #[inline]
+    pub fn scroll_y(&mut self, value: OS_Input_Coord) -> &mut Self {
+        self.push(InputEvent::Scroll(0, value))
+    }
+
+    pub fn gradual_scroll(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> &mut Self {
+        let gradual_scroll = GradualMove::calculate(x, y);
+
+        for _ in 0..gradual_scroll.both_move {
+            self.push(InputEvent::Scroll(gradual_scroll.x_direction, gradual_scroll.y_direction));
+        }
+        for _ in 0..gradual_scroll.move_only_x {
+            self.scroll_x(gradual_scroll.x_direction);
+        }
+        for _ in 0..gradual_scroll.move_only_y {
+            self.scroll_y(gradual_scroll.y_direction);
+        }
+        self
+    }
+
+    #[inline]
+    pub fn press(&mut self, key_code: KeyCode) -> &mut Self {
+        self.push(InputEvent::Press(key_code))
+    }
+
+    #[inline]
+    pub fn release(&mut self, key_code: KeyCode) -> &mut Self {
+        self.push(InputEvent::Release(key_code))
+    }
+
+    #[inline]
+    pub fn sync(&mut self) -> &mut Self {
+        self.push(InputEvent::Sync)
+    }
+}
+
+impl From<Vec<InputEvent>> for InputBatch {
+    fn from(events: Vec<InputEvent>) -> Self {
+        Self { events }
+    }
+}
+
+impl FromIterator<InputEvent> for InputBatch {
+    fn from_iter<I: IntoIterator<Item=InputEvent>>(iter: I) -> Self {
+        Self {
+            events: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl Extend<InputEvent> for InputBatch {
+    fn extend<I: IntoIterator<Item=InputEvent>>(&mut self, iter: I) {
+        self.events.extend(iter);
+    }
+}
+
+impl IntoIterator for InputBatch {
+    type Item = InputEvent;
+    type IntoIter = std::vec::IntoIter<InputEvent>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.events.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a InputBatch {
+    type Item = &'a InputEvent;
+    type IntoIter = std::slice::Iter<'a, InputEvent>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.events.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use InputEvent::*;
+
+    #[test]
+    fn keeps_call_order() {
+        let mut batch = InputBatch::new();
+        batch.move_mouse_x(1)
+            .press(KeyCode::MOUSE_LEFT)
+            .scroll_y(-2)
+            .sync()
+            .move_mouse(3, 4)
+            .release(KeyCode::MOUSE_LEFT)
+            .scroll_x(5)
+            .move_mouse_y(-6);
+        assert_eq!(batch.events(), [
+            Move(1, 0),
+            Press(KeyCode::MOUSE_LEFT),
+            Scroll(0, -2),
+            Sync,
+            Move(3, 4),
+            Release(KeyCode::MOUSE_LEFT),
+            Scroll(5, 0),
+            Move(0, -6),
+        ]);
+
+        let mut other: InputBatch = vec![Press(KeyCode::KEY_A)].into();
+        batch.append(&mut other);
+        assert!(other.is_empty());
+        assert_eq!(batch.iter().last(), Some(&Press(KeyCode::KEY_A)));
+        assert_eq!(batch.len(), 9);
+    }
+
+    #[test]
+    fn gradual_move_expands_to_unit_steps() {
+        let mut batch = InputBatch::new();
+        batch.gradual_move_mouse(3, -1);
+        assert_eq!(batch.events(), [Move(1, -1), Move(1, 0), Move(1, 0)]);
+
+        batch.clear();
+        batch.gradual_move_mouse(-1, 2);
+        assert_eq!(batch.events(), [Move(-1, 1), Move(0, 1)]);
+
+        batch.clear();
+        batch.gradual_move_mouse(0, 0);
+        assert!(batch.is_empty());
+    }
+
+    #[test]
+    fn gradual_scroll_expands_to_unit_steps() {
+        let mut batch = InputBatch::new();
+        batch.gradual_scroll(-2, 3);
+        assert_eq!(batch.events(), [Scroll(-1, 1), Scroll(-1, 1), Scroll(0, 1)]);
+
+        // Totals stay the same as one big step
+        let total = batch.iter().fold((0, 0), |(x, y), event| match event {
+            Scroll(dx, dy) => (x + dx, y + dy),
+            _ => (x, y),
+        });
+        assert_eq!(total, (-2, 3));
+    }
+
+    #[test]
+    fn pending_sync() {
+        let mut batch = InputBatch::new();
+        assert!(!batch.is_pending_sync());
+        batch.move_mouse(1, 1);
+        assert!(batch.is_pending_sync());
+        batch.sync();
+        assert!(!batch.is_pending_sync());
+        batch.press(KeyCode::KEY_A);
+        assert!(batch.is_pending_sync());
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let batch: InputBatch = [Move(1, -1), Sync, Release(KeyCode::KEY_A)].into_iter().collect();
+        let json = serde_json::to_string(&batch).unwrap();
+        assert_eq!(serde_json::from_str::<InputBatch>(&json).unwrap(), batch);
+    }
+}
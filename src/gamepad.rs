@@ -0,0 +1,639 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use color_eyre::eyre::bail;
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+use strum_macros::{AsRefStr, Display, EnumIter, EnumString};
+use crate::exec_or_eyre;
+use crate::key_codes::*;
+
+#[cfg(feature = "uinput")]
+use strum::IntoEnumIterator;
+#[cfg(feature = "uinput")]
+use crate::uinput::{AbsInfo, UinputBuilder, UinputDevice};
+
+#[derive(PartialOrd, EnumIter, EnumString, AsRefStr, Display, Eq, Hash, PartialEq, Copy, Clone, Debug, Serialize, Deserialize, )]
+pub enum GamepadButton {
+    BTN_A,
+    BTN_B,
+    BTN_X,
+    BTN_Y,
+    BTN_LEFT_SHOULDER,
+    BTN_RIGHT_SHOULDER,
+    BTN_SELECT,
+    BTN_START,
+    BTN_GUIDE,
+    BTN_LEFT_STICK,
+    BTN_RIGHT_STICK,
+    DPAD_UP,
+    DPAD_DOWN,
+    DPAD_LEFT,
+    DPAD_RIGHT,
+}
+
+#[derive(PartialOrd, EnumIter, EnumString, AsRefStr, Display, Eq, Hash, PartialEq, Copy, Clone, Debug, Serialize, Deserialize, )]
+pub enum GamepadAxis {
+    LEFT_STICK_X,
+    LEFT_STICK_Y,
+    RIGHT_STICK_X,
+    RIGHT_STICK_Y,
+    LEFT_TRIGGER,
+    RIGHT_TRIGGER,
+}
+
+pub const STICK_MIN: i32 = i16::MIN as i32;
+pub const STICK_MAX: i32 = i16::MAX as i32;
+pub const TRIGGER_MIN: i32 = 0;
+pub const TRIGGER_MAX: i32 = u8::MAX as i32;
+
+impl GamepadButton {
+    #[inline]
+    pub fn is_dpad(&self) -> bool {
+        matches!(self, GamepadButton::DPAD_UP | GamepadButton::DPAD_DOWN | GamepadButton::DPAD_LEFT | GamepadButton::DPAD_RIGHT)
+    }
+
+    // D-pad is reported through the hat axes, same as a real Xbox 360 pad
+    pub fn evdev_code(&self) -> Option<u16> {
+        let result = match self {
+            GamepadButton::BTN_A => BTN_SOUTH,
+            GamepadButton::BTN_B => BTN_EAST,
+            GamepadButton::BTN_X => BTN_NORTH,
+            GamepadButton::BTN_Y => BTN_WEST,
+            GamepadButton::BTN_LEFT_SHOULDER => BTN_TL,
+            GamepadButton::BTN_RIGHT_SHOULDER => BTN_TR,
+            GamepadButton::BTN_SELECT => BTN_SELECT,
+            GamepadButton::BTN_START => BTN_START,
+            GamepadButton::BTN_GUIDE => BTN_MODE,
+            GamepadButton::BTN_LEFT_STICK => BTN_THUMBL,
+            GamepadButton::BTN_RIGHT_STICK => BTN_THUMBR,
+            _ => return None,
+        };
+        Some(result)
+    }
+
+    // Bit index in the HID report, d-pad excluded
+    pub fn hid_index(&self) -> Option<u8> {
+        let result = match self {
+            GamepadButton::BTN_A => 0,
+            GamepadButton::BTN_B => 1,
+            GamepadButton::BTN_X => 2,
+            GamepadButton::BTN_Y => 3,
+            GamepadButton::BTN_LEFT_SHOULDER => 4,
+            GamepadButton::BTN_RIGHT_SHOULDER => 5,
+            GamepadButton::BTN_SELECT => 6,
+            GamepadButton::BTN_START => 7,
+            GamepadButton::BTN_GUIDE => 8,
+            GamepadButton::BTN_LEFT_STICK => 9,
+            GamepadButton::BTN_RIGHT_STICK => 10,
+            _ => return None,
+        };
+        Some(result)
+    }
+}
+
+impl GamepadAxis {
+    #[inline]
+    pub fn is_trigger(&self) -> bool {
+        matches!(self, GamepadAxis::LEFT_TRIGGER | GamepadAxis::RIGHT_TRIGGER)
+    }
+
+    #[inline]
+    pub fn range(&self) -> (i32, i32) {
+        match self.is_trigger() {
+            true => (TRIGGER_MIN, TRIGGER_MAX),
+            false => (STICK_MIN, STICK_MAX),
+        }
+    }
+
+    pub fn evdev_code(&self) -> u16 {
+        match self {
+            GamepadAxis::LEFT_STICK_X => ABS_X,
+            GamepadAxis::LEFT_STICK_Y => ABS_Y,
+            GamepadAxis::RIGHT_STICK_X => ABS_RX,
+            GamepadAxis::RIGHT_STICK_Y => ABS_RY,
+            GamepadAxis::LEFT_TRIGGER => ABS_Z,
+            GamepadAxis::RIGHT_TRIGGER => ABS_RZ,
+        }
+    }
+
+    // Stick Y is positive up like move_mouse, devices expect positive down
+    #[inline]
+    fn device_value(&self, value: i32) -> i32 {
+        let (min, max) = self.range();
+        match self {
+            GamepadAxis::LEFT_STICK_Y | GamepadAxis::RIGHT_STICK_Y => value.saturating_neg().clamp(min, max),
+            _ => value.clamp(min, max),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Default, Debug)]
+struct Dpad {
+    up: bool,
+    down: bool,
+    left: bool,
+    right: bool,
+}
+
+impl Dpad {
+    #[inline]
+    fn set(&mut self, button: GamepadButton, pressed: bool) {
+        match button {
+            GamepadButton::DPAD_UP => self.up = pressed,
+            GamepadButton::DPAD_DOWN => self.down = pressed,
+            GamepadButton::DPAD_LEFT => self.left = pressed,
+            GamepadButton::DPAD_RIGHT => self.right = pressed,
+            _ => {}
+        }
+    }
+
+    #[inline]
+    fn hat_x(&self) -> i32 {
+        self.right as i32 - self.left as i32
+    }
+
+    #[inline]
+    fn hat_y(&self) -> i32 {
+        self.down as i32 - self.up as i32
+    }
+
+    // HID hat switch: 0 is up, clockwise in 45 degree steps, 8 is released
+    fn hid_hat(&self) -> u8 {
+        match (self.hat_x(), self.hat_y()) {
+            (0, -1) => 0,
+            (1, -1) => 1,
+            (1, 0) => 2,
+            (1, 1) => 3,
+            (0, 1) => 4,
+            (-1, 1) => 5,
+            (-1, 0) => 6,
+            (-1, -1) => 7,
+            _ => 8,
+        }
+    }
+}
+
+#[cfg(feature = "uinput")]
+pub struct VirtualGamepad {
+    device: UinputDevice,
+    dpad: Dpad,
+}
+
+#[cfg(feature = "uinput")]
+impl VirtualGamepad {
+    // Identifies as a wired Xbox 360 controller so games pick a known mapping
+    pub fn uinput_builder() -> UinputBuilder {
+        let key_bits: Vec<u16> = GamepadButton::iter()
+            .filter_map(|button| button.evdev_code())
+            .collect();
+
+        let mut builder = UinputBuilder::new()
+            .name("Microsoft X-Box 360 pad")
+            .bus_type(BUS_USB)
+            .vendor(0x045e)
+            .product(0x028e)
+            .version(0x0110)
+            .key_bits(&key_bits)
+            .abs_axis(ABS_HAT0X, AbsInfo::new(-1, 1))
+            .abs_axis(ABS_HAT0Y, AbsInfo::new(-1, 1));
+
+        for axis in GamepadAxis::iter() {
+            let (min, max) = axis.range();
+            let abs_info = match axis.is_trigger() {
+                true => AbsInfo::new(min, max),
+                false => AbsInfo::new(min, max).fuzz(16).flat(128),
+            };
+            builder = builder.abs_axis(axis.evdev_code(), abs_info);
+        }
+        builder
+    }
+
+    pub fn new() -> Result<Self> {
+        Self::from_builder(&Self::uinput_builder())
+    }
+
+    pub fn from_builder(builder: &UinputBuilder) -> Result<Self> {
+        Ok(Self {
+            device: builder.create()?,
+            dpad: Dpad::default(),
+        })
+    }
+
+    pub fn from_writer<W: Write + Send + 'static>(writer: W) -> Self {
+        Self {
+            device: UinputDevice::from_writer(writer),
+            dpad: Dpad::default(),
+        }
+    }
+
+    #[inline]
+    pub fn synchronize(&mut self) -> Result<()> {
+        self.device.synchronize()
+    }
+
+    fn set_button(&mut self, button: GamepadButton, pressed: bool) -> Result<()> {
+        match button.evdev_code() {
+            Some(code) => {
+                self.device.write_event(EV_KEY, code, pressed as i32)?;
+            }
+            None => {
+                self.dpad.set(button, pressed);
+                self.device.write_batch(&[
+                    (EV_ABS, ABS_HAT0X, self.dpad.hat_x()),
+                    (EV_ABS, ABS_HAT0Y, self.dpad.hat_y()),
+                ])?;
+            }
+        }
+        self.synchronize()
+    }
+
+    #[inline]
+    pub fn press(&mut self, button: GamepadButton) -> Result<()> {
+        self.set_button(button, true)
+    }
+
+    #[inline]
+    pub fn release(&mut self, button: GamepadButton) -> Result<()> {
+        self.set_button(button, false)
+    }
+
+    #[inline]
+    pub fn set_axis_raw(&mut self, axis: GamepadAxis, value: i32) -> Result<()> {
+        self.device.write_event(EV_ABS, axis.evdev_code(), axis.device_value(value))
+    }
+
+    #[inline]
+    pub fn set_axis(&mut self, axis: GamepadAxis, value: i32) -> Result<()> {
+        self.set_axis_raw(axis, value)?;
+        self.synchronize()
+    }
+
+    #[inline]
+    pub fn move_left_stick(&mut self, x: i32, y: i32) -> Result<()> {
+        self.set_axis_raw(GamepadAxis::LEFT_STICK_X, x)?;
+        self.set_axis(GamepadAxis::LEFT_STICK_Y, y)
+    }
+
+    #[inline]
+    pub fn move_right_stick(&mut self, x: i32, y: i32) -> Result<()> {
+        self.set_axis_raw(GamepadAxis::RIGHT_STICK_X, x)?;
+        self.set_axis(GamepadAxis::RIGHT_STICK_Y, y)
+    }
+
+    #[inline]
+    pub fn set_left_trigger(&mut self, value: i32) -> Result<()> {
+        self.set_axis(GamepadAxis::LEFT_TRIGGER, value)
+    }
+
+    #[inline]
+    pub fn set_right_trigger(&mut self, value: i32) -> Result<()> {
+        self.set_axis(GamepadAxis::RIGHT_TRIGGER, value)
+    }
+}
+
+// Report descriptor for a USB HID gadget function (configfs "report_desc"),
+// matching the layout produced by GamepadReport::to_bytes
+pub const GAMEPAD_REPORT_DESCRIPTOR: [u8; 85] = [
+    0x05, 0x01, // Usage Page (Generic Desktop)
+    0x09, 0x05, // Usage (Game Pad)
+    0xa1, 0x01, // Collection (Application)
+    0x05, 0x09, //   Usage Page (Button)
+    0x19, 0x01, //   Usage Minimum (1)
+    0x29, 0x10, //   Usage Maximum (16)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x25, 0x01, //   Logical Maximum (1)
+    0x75, 0x01, //   Report Size (1)
+    0x95, 0x10, //   Report Count (16)
+    0x81, 0x02, //   Input (Data, Variable, Absolute)
+    0x05, 0x01, //   Usage Page (Generic Desktop)
+    0x09, 0x39, //   Usage (Hat switch)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x25, 0x07, //   Logical Maximum (7)
+    0x35, 0x00, //   Physical Minimum (0)
+    0x46, 0x3b, 0x01, //   Physical Maximum (315)
+    0x65, 0x14, //   Unit (Degrees)
+    0x75, 0x04, //   Report Size (4)
+    0x95, 0x01, //   Report Count (1)
+    0x81, 0x42, //   Input (Data, Variable, Absolute, Null State)
+    0x65, 0x00, //   Unit (None)
+    0x45, 0x00, //   Physical Maximum (0)
+    0x81, 0x03, //   Input (Constant) - 4 bit padding
+    0x09, 0x30, //   Usage (X)
+    0x09, 0x31, //   Usage (Y)
+    0x09, 0x33, //   Usage (Rx)
+    0x09, 0x34, //   Usage (Ry)
+    0x16, 0x00, 0x80, //   Logical Minimum (-32768)
+    0x26, 0xff, 0x7f, //   Logical Maximum (32767)
+    0x75, 0x10, //   Report Size (16)
+    0x95, 0x04, //   Report Count (4)
+    0x81, 0x02, //   Input (Data, Variable, Absolute)
+    0x09, 0x32, //   Usage (Z)
+    0x09, 0x35, //   Usage (Rz)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x26, 0xff, 0x00, //   Logical Maximum (255)
+    0x75, 0x08, //   Report Size (8)
+    0x95, 0x02, //   Report Count (2)
+    0x81, 0x02, //   Input (Data, Variable, Absolute)
+    0xc0, // End Collection
+];
+
+pub const GAMEPAD_REPORT_SIZE: usize = 13;
+
+#[derive(PartialEq, Eq, Copy, Clone, Default, Debug)]
+pub struct GamepadReport {
+    buttons: u16,
+    dpad: Dpad,
+    axes: [i32; 6],
+}
+
+impl GamepadReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn set_button(&mut self, button: GamepadButton, pressed: bool) {
+        match button.hid_index() {
+            Some(index) if pressed => self.buttons |= 1 << index,
+            Some(index) => self.buttons &= !(1 << index),
+            None => self.dpad.set(button, pressed),
+        }
+    }
+
+    #[inline]
+    pub fn set_axis(&mut self, axis: GamepadAxis, value: i32) {
+        self.axes[axis as usize] = axis.device_value(value);
+    }
+
+    pub fn to_bytes(&self) -> [u8; GAMEPAD_REPORT_SIZE] {
+        let mut report = [0u8; GAMEPAD_REPORT_SIZE];
+        report[0..2].copy_from_slice(&self.buttons.to_le_bytes());
+        report[2] = self.dpad.hid_hat();
+
+        let sticks = [
+            GamepadAxis::LEFT_STICK_X,
+            GamepadAxis::LEFT_STICK_Y,
+            GamepadAxis::RIGHT_STICK_X,
+            GamepadAxis::RIGHT_STICK_Y,
+        ];
+        for (index, axis) in sticks.iter().enumerate() {
+            let offset = 3 + index * 2;
+            let value = self.axes[*axis as usize] as i16;
+            report[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+        }
+
+        report[11] = self.axes[GamepadAxis::LEFT_TRIGGER as usize] as u8;
+        report[12] = self.axes[GamepadAxis::RIGHT_TRIGGER as usize] as u8;
+        report
+    }
+}
+
+pub struct HidGamepad {
+    writer: Box<dyn Write + Send>,
+    report: GamepadReport,
+}
+
+impl HidGamepad {
+    // Gadget function must be configured with GAMEPAD_REPORT_DESCRIPTOR
+    pub fn open(name: &str) -> Result<Self> {
+        if name.contains('/') {
+            bail!("Expected device name like hidg2, got: {name}");
+        }
+        let file = exec_or_eyre!(OpenOptions::new().write(true).open(format!("/dev/{name}")))?;
+        Ok(Self::from_writer(file))
+    }
+
+    pub fn from_writer<W: Write + Send + 'static>(writer: W) -> Self {
+        Self {
+            writer: Box::new(writer),
+            report: GamepadReport::new(),
+        }
+    }
+
+    #[inline]
+    pub fn report(&self) -> &GamepadReport {
+        &self.report
+    }
+
+    #[inline]
+    pub fn synchronize(&mut self) -> Result<()> {
+        exec_or_eyre!(self.writer.write_all(&self.report.to_bytes()))?;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn press(&mut self, button: GamepadButton) -> Result<()> {
+        self.report.set_button(button, true);
+        self.synchronize()
+    }
+
+    #[inline]
+    pub fn release(&mut self, button: GamepadButton) -> Result<()> {
+        self.report.set_button(button, false);
+        self.synchronize()
+    }
+
+    #[inline]
+    pub fn set_axis_raw(&mut self, axis: GamepadAxis, value: i32) {
+        self.report.set_axis(axis, value);
+    }
+
+    #[inline]
+    pub fn set_axis(&mut self, axis: GamepadAxis, value: i32) -> Result<()> {
+        self.set_axis_raw(axis, value);
+        self.synchronize()
+    }
+
+    #[inline]
+    pub fn move_left_stick(&mut self, x: i32, y: i32) -> Result<()> {
+        self.set_axis_raw(GamepadAxis::LEFT_STICK_X, x);
+        self.set_axis(GamepadAxis::LEFT_STICK_Y, y)
+    }
+
+    #[inline]
+    pub fn move_right_stick(&mut self, x: i32, y: i32) -> Result<()> {
+        self.set_axis_raw(GamepadAxis::RIGHT_STICK_X, x);
+        self.set_axis(GamepadAxis::RIGHT_STICK_Y, y)
+    }
+
+    #[inline]
+    pub fn set_left_trigger(&mut self, value: i32) -> Result<()> {
+        self.set_axis(GamepadAxis::LEFT_TRIGGER, value)
+    }
+
+    #[inline]
+    pub fn set_right_trigger(&mut self, value: i32) -> Result<()> {
+        self.set_axis(GamepadAxis::RIGHT_TRIGGER, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use strum::IntoEnumIterator;
+    use crate::hid_report::tests::report_bits;
+    use super::*;
+
+    const HAT_NULL: u8 = 8;
+
+    fn report_with(buttons: &[GamepadButton]) -> [u8; GAMEPAD_REPORT_SIZE] {
+        let mut report = GamepadReport::new();
+        for button in buttons {
+            report.set_button(*button, true);
+        }
+        report.to_bytes()
+    }
+
+    #[test]
+    fn descriptor_matches_report_size() {
+        assert_eq!(report_bits(&GAMEPAD_REPORT_DESCRIPTOR), (GAMEPAD_REPORT_SIZE * 8, 0));
+    }
+
+    #[test]
+    fn button_bits() {
+        assert_eq!(GamepadReport::new().to_bytes(), [0, 0, HAT_NULL, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let buttons: Vec<_> = GamepadButton::iter().filter(|button| !button.is_dpad()).collect();
+        assert_eq!(buttons.len(), 11);
+        for (index, button) in buttons.iter().enumerate() {
+            let bytes = report_with(&[*button]);
+            assert_eq!(u16::from_le_bytes([bytes[0], bytes[1]]), 1 << index, "{button}");
+            assert_eq!(bytes[2], HAT_NULL);
+        }
+
+        let mut report = GamepadReport::new();
+        report.set_button(GamepadButton::BTN_A, true);
+        report.set_button(GamepadButton::BTN_RIGHT_STICK, true);
+        assert_eq!(report.to_bytes()[..2], [0x01, 0x04]);
+        report.set_button(GamepadButton::BTN_A, false);
+        assert_eq!(report.to_bytes()[..2], [0x00, 0x04]);
+    }
+
+    #[test]
+    fn dpad_hat() {
+        use GamepadButton::*;
+        let directions: [(&[GamepadButton], u8); 10] = [
+            (&[], HAT_NULL),
+            (&[DPAD_UP], 0),
+            (&[DPAD_UP, DPAD_RIGHT], 1),
+            (&[DPAD_RIGHT], 2),
+            (&[DPAD_DOWN, DPAD_RIGHT], 3),
+            (&[DPAD_DOWN], 4),
+            (&[DPAD_DOWN, DPAD_LEFT], 5),
+            (&[DPAD_LEFT], 6),
+            (&[DPAD_UP, DPAD_LEFT], 7),
+            // Opposite directions cancel out
+            (&[DPAD_UP, DPAD_DOWN], HAT_NULL),
+        ];
+        for (buttons, hat) in directions {
+            let bytes = report_with(buttons);
+            assert_eq!(bytes[2], hat, "{buttons:?}");
+            assert_eq!(bytes[..2], [0, 0]);
+        }
+    }
+
+    #[test]
+    fn axes_clamp_and_negate_y() {
+        let mut report = GamepadReport::new();
+        report.set_axis(GamepadAxis::LEFT_STICK_X, 40000);
+        report.set_axis(GamepadAxis::LEFT_STICK_Y, 100);
+        report.set_axis(GamepadAxis::RIGHT_STICK_X, -40000);
+        report.set_axis(GamepadAxis::RIGHT_STICK_Y, STICK_MIN);
+        report.set_axis(GamepadAxis::LEFT_TRIGGER, 300);
+        report.set_axis(GamepadAxis::RIGHT_TRIGGER, -5);
+        assert_eq!(report.to_bytes(), [
+            0, 0, HAT_NULL,
+            0xff, 0x7f, // 32767
+            0x9c, 0xff, // -100, up is negative on the device
+            0x00, 0x80, // -32768
+            0xff, 0x7f, // -(-32768) saturates to 32767
+            0xff, 0x00,
+        ]);
+    }
+
+    #[test]
+    fn hid_gamepad_writes_reports() {
+        let (mut reader, writer) = std::io::pipe().unwrap();
+        let mut gamepad = HidGamepad::from_writer(writer);
+        gamepad.press(GamepadButton::BTN_B).unwrap();
+        gamepad.move_left_stick(1, 1).unwrap();
+        gamepad.release(GamepadButton::BTN_B).unwrap();
+        drop(gamepad);
+
+        let mut written = vec![];
+        std::io::Read::read_to_end(&mut reader, &mut written).unwrap();
+        assert_eq!(written, [
+            [0x02, 0, HAT_NULL, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            [0x02, 0, HAT_NULL, 1, 0, 0xff, 0xff, 0, 0, 0, 0, 0, 0],
+            [0x00, 0, HAT_NULL, 1, 0, 0xff, 0xff, 0, 0, 0, 0, 0, 0],
+        ].concat());
+    }
+}
+
+#[cfg(all(test, feature = "uinput"))]
+mod uinput_tests {
+    use std::io::Read;
+    use crate::uinput::tests::decode_events;
+    use crate::uinput::EventParams;
+    use super::*;
+
+    const SYN: EventParams = (EV_SYN, SYN_REPORT, 0);
+
+    fn capture(f: impl FnOnce(&mut VirtualGamepad) -> Result<()>) -> Vec<EventParams> {
+        let (mut reader, writer) = std::io::pipe().unwrap();
+        let mut gamepad = VirtualGamepad::from_writer(writer);
+        f(&mut gamepad).unwrap();
+        drop(gamepad);
+
+        let mut written = vec![];
+        reader.read_to_end(&mut written).unwrap();
+        decode_events(&written)
+    }
+
+    #[test]
+    fn buttons() {
+        let events = capture(|gamepad| {
+            for button in GamepadButton::iter().filter(|button| !button.is_dpad()) {
+                gamepad.press(button)?;
+            }
+            gamepad.release(GamepadButton::BTN_A)
+        });
+        let codes = [
+            BTN_SOUTH, BTN_EAST, BTN_NORTH, BTN_WEST, BTN_TL, BTN_TR,
+            BTN_SELECT, BTN_START, BTN_MODE, BTN_THUMBL, BTN_THUMBR,
+        ];
+        let mut expected: Vec<_> = codes.iter().flat_map(|code| [(EV_KEY, *code, 1), SYN]).collect();
+        expected.extend([(EV_KEY, BTN_SOUTH, 0), SYN]);
+        assert_eq!(events, expected);
+    }
+
+    #[test]
+    fn dpad_goes_through_the_hat() {
+        let events = capture(|gamepad| {
+            gamepad.press(GamepadButton::DPAD_UP)?;
+            gamepad.press(GamepadButton::DPAD_RIGHT)?;
+            gamepad.release(GamepadButton::DPAD_UP)?;
+            gamepad.release(GamepadButton::DPAD_RIGHT)
+        });
+        assert_eq!(events, [
+            (EV_ABS, ABS_HAT0X, 0), (EV_ABS, ABS_HAT0Y, -1), SYN,
+            (EV_ABS, ABS_HAT0X, 1), (EV_ABS, ABS_HAT0Y, -1), SYN,
+            (EV_ABS, ABS_HAT0X, 1), (EV_ABS, ABS_HAT0Y, 0), SYN,
+            (EV_ABS, ABS_HAT0X, 0), (EV_ABS, ABS_HAT0Y, 0), SYN,
+        ]);
+    }
+
+    #[test]
+    fn sticks_and_triggers() {
+        let events = capture(|gamepad| {
+            gamepad.move_left_stick(100, 200)?;
+            gamepad.move_right_stick(-40000, STICK_MIN)?;
+            gamepad.set_left_trigger(-1)?;
+            gamepad.set_right_trigger(300)
+        });
+        assert_eq!(events, [
+            (EV_ABS, ABS_X, 100), (EV_ABS, ABS_Y, -200), SYN,
+            (EV_ABS, ABS_RX, STICK_MIN), (EV_ABS, ABS_RY, STICK_MAX), SYN,
+            (EV_ABS, ABS_Z, 0), SYN,
+            (EV_ABS, ABS_RZ, TRIGGER_MAX), SYN,
+        ]);
+    }
+}
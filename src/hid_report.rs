@@ -0,0 +1,367 @@
+use color_eyre::eyre::bail;
+use color_eyre::Result;
+use crate::key_codes::BTN_LEFT;
+use crate::{KeyCode, OS_Input_Coord};
+
+pub const KEYBOARD_REPORT_SIZE: usize = 8;
+pub const MOUSE_REPORT_SIZE: usize = 5;
+pub const BOOT_MOUSE_REPORT_SIZE: usize = 3;
+
+// Reported in every key slot when more than 6 keys are held
+pub const HID_ERROR_ROLL_OVER: u8 = 0x01;
+const MAX_KEYS: usize = 6;
+// One move or scroll is split over at most this many frames, anything further saturates
+pub const MAX_MOUSE_FRAMES: usize = 256;
+
+const MODIFIER_USAGE_MIN: u16 = 0xe0;
+const MODIFIER_USAGE_MAX: u16 = 0xe7;
+
+// Boot keyboard layout from HID 1.11 appendix B.1, key usages widened to 0..0xff
+pub const KEYBOARD_REPORT_DESCRIPTOR: [u8; 65] = [
+    0x05, 0x01, // Usage Page (Generic Desktop)
+    0x09, 0x06, // Usage (Keyboard)
+    0xa1, 0x01, // Collection (Application)
+    0x05, 0x07, //   Usage Page (Keyboard/Keypad)
+    0x19, 0xe0, //   Usage Minimum (Left Control)
+    0x29, 0xe7, //   Usage Maximum (Right GUI)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x25, 0x01, //   Logical Maximum (1)
+    0x75, 0x01, //   Report Size (1)
+    0x95, 0x08, //   Report Count (8)
+    0x81, 0x02, //   Input (Data, Variable, Absolute), modifiers
+    0x95, 0x01, //   Report Count (1)
+    0x75, 0x08, //   Report Size (8)
+    0x81, 0x01, //   Input (Constant), reserved
+    0x95, 0x05, //   Report Count (5)
+    0x75, 0x01, //   Report Size (1)
+    0x05, 0x08, //   Usage Page (LEDs)
+    0x19, 0x01, //   Usage Minimum (Num Lock)
+    0x29, 0x05, //   Usage Maximum (Kana)
+    0x91, 0x02, //   Output (Data, Variable, Absolute), LEDs
+    0x95, 0x01, //   Report Count (1)
+    0x75, 0x03, //   Report Size (3)
+    0x91, 0x01, //   Output (Constant), padding
+    0x95, 0x06, //   Report Count (6)
+    0x75, 0x08, //   Report Size (8)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x26, 0xff, 0x00, //   Logical Maximum (255)
+    0x05, 0x07, //   Usage Page (Keyboard/Keypad)
+    0x19, 0x00, //   Usage Minimum (0)
+    0x2a, 0xff, 0x00, //   Usage Maximum (255)
+    0x81, 0x00, //   Input (Data, Array), keys
+    0xc0, // End Collection
+];
+
+// 8 buttons, X, Y, wheel and horizontal pan. The first 3 bytes match the boot mouse report.
+pub const MOUSE_REPORT_DESCRIPTOR: [u8; 61] = [
+    0x05, 0x01, // Usage Page (Generic Desktop)
+    0x09, 0x02, // Usage (Mouse)
+    0xa1, 0x01, // Collection (Application)
+    0x09, 0x01, //   Usage (Pointer)
+    0xa1, 0x00, //   Collection (Physical)
+    0x05, 0x09, //     Usage Page (Button)
+    0x19, 0x01, //     Usage Minimum (1)
+    0x29, 0x08, //     Usage Maximum (8)
+    0x15, 0x00, //     Logical Minimum (0)
+    0x25, 0x01, //     Logical Maximum (1)
+    0x95, 0x08, //     Report Count (8)
+    0x75, 0x01, //     Report Size (1)
+    0x81, 0x02, //     Input (Data, Variable, Absolute)
+    0x05, 0x01, //     Usage Page (Generic Desktop)
+    0x09, 0x30, //     Usage (X)
+    0x09, 0x31, //     Usage (Y)
+    0x09, 0x38, //     Usage (Wheel)
+    0x15, 0x81, //     Logical Minimum (-127)
+    0x25, 0x7f, //     Logical Maximum (127)
+    0x75, 0x08, //     Report Size (8)
+    0x95, 0x03, //     Report Count (3)
+    0x81, 0x06, //     Input (Data, Variable, Relative)
+    0x05, 0x0c, //     Usage Page (Consumer)
+    0x0a, 0x38, 0x02, //     Usage (AC Pan)
+    0x15, 0x81, //     Logical Minimum (-127)
+    0x25, 0x7f, //     Logical Maximum (127)
+    0x75, 0x08, //     Report Size (8)
+    0x95, 0x01, //     Report Count (1)
+    0x81, 0x06, //     Input (Data, Variable, Relative)
+    0xc0, //   End Collection
+    0xc0, // End Collection
+];
+
+#[derive(PartialEq, Eq, Clone, Default, Debug)]
+pub struct KeyboardReport {
+    modifiers: u8,
+    // Usages in the order they were pressed
+    keys: Vec<u8>,
+}
+
+impl KeyboardReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn usage(key_code: KeyCode) -> Result<u16> {
+        match key_code.hid_usage() {
+            Some(usage) => Ok(usage),
+            None => bail!("No HID usage for key code: {key_code}"),
+        }
+    }
+
+    pub fn press(&mut self, key_code: KeyCode) -> Result<()> {
+        let usage = Self::usage(key_code)?;
+        if (MODIFIER_USAGE_MIN..=MODIFIER_USAGE_MAX).contains(&usage) {
+            self.modifiers |= 1 << (usage - MODIFIER_USAGE_MIN);
+        } else if !self.keys.contains(&(usage as u8)) {
+            self.keys.push(usage as u8);
+        }
+        Ok(())
+    }
+
+    pub fn release(&mut self, key_code: KeyCode) -> Result<()> {
+        let usage = Self::usage(key_code)?;
+        if (MODIFIER_USAGE_MIN..=MODIFIER_USAGE_MAX).contains(&usage) {
+            self.modifiers &= !(1 << (usage - MODIFIER_USAGE_MIN));
+        } else {
+            self.keys.retain(|key| *key != usage as u8);
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn release_all(&mut self) {
+        self.modifiers = 0;
+        self.keys.clear();
+    }
+
+    #[inline]
+    pub fn modifiers(&self) -> u8 {
+        self.modifiers
+    }
+
+    pub fn to_bytes(&self) -> [u8; KEYBOARD_REPORT_SIZE] {
+        let mut report = [0u8; KEYBOARD_REPORT_SIZE];
+        report[0] = self.modifiers;
+        match self.keys.len() > MAX_KEYS {
+            true => report[2..].fill(HID_ERROR_ROLL_OVER),
+            false => report[2..2 + self.keys.len()].copy_from_slice(&self.keys),
+        }
+        report
+    }
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Default, Debug)]
+pub struct MouseFrame {
+    pub buttons: u8,
+    pub x: i8,
+    pub y: i8,
+    pub wheel: i8,
+    pub pan: i8,
+}
+
+impl MouseFrame {
+    pub fn to_bytes(&self) -> [u8; MOUSE_REPORT_SIZE] {
+        [
+            self.buttons,
+            self.x as u8,
+            self.y as u8,
+            self.wheel as u8,
+            self.pan as u8,
+        ]
+    }
+
+    // Boot protocol only knows 3 buttons, X and Y
+    pub fn to_boot_bytes(&self) -> [u8; BOOT_MOUSE_REPORT_SIZE] {
+        [self.buttons & 0b111, self.x as u8, self.y as u8]
+    }
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Default, Debug)]
+pub struct MouseReport {
+    buttons: u8,
+}
+
+impl MouseReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Button n of the HID button page is evdev BTN_LEFT + n - 1, the same way Linux maps it back
+    pub(crate) fn button_bit(key_code: KeyCode) -> Result<u8> {
+        match key_code.evdev_code() {
+            Some(code) if key_code.is_mouse_button() => Ok(1 << (code - BTN_LEFT)),
+            _ => bail!("Not a mouse button: {key_code}"),
+        }
+    }
+
+    pub fn press(&mut self, key_code: KeyCode) -> Result<MouseFrame> {
+        self.buttons |= Self::button_bit(key_code)?;
+        Ok(self.frame())
+    }
+
+    pub fn release(&mut self, key_code: KeyCode) -> Result<MouseFrame> {
+        self.buttons &= !Self::button_bit(key_code)?;
+        Ok(self.frame())
+    }
+
+    #[inline]
+    pub fn release_all(&mut self) -> MouseFrame {
+        self.buttons = 0;
+        self.frame()
+    }
+
+    #[inline]
+    pub fn buttons(&self) -> u8 {
+        self.buttons
+    }
+
+    // Current buttons without movement
+    #[inline]
+    pub fn frame(&self) -> MouseFrame {
+        MouseFrame {
+            buttons: self.buttons,
+            ..Default::default()
+        }
+    }
+
+    // Values beyond one byte are split over several frames, up to MAX_MOUSE_FRAMES.
+    // Positive y goes up like everywhere in the crate.
+    pub fn move_mouse(&self, x: OS_Input_Coord, y: OS_Input_Coord) -> Vec<MouseFrame> {
+        self.split(x, y.saturating_neg(), |frame, x, y| {
+            frame.x = x;
+            frame.y = y;
+        })
+    }
+
+    pub fn scroll(&self, x: OS_Input_Coord, y: OS_Input_Coord) -> Vec<MouseFrame> {
+        self.split(x, y, |frame, x, y| {
+            frame.pan = x;
+            frame.wheel = y;
+        })
+    }
+
+    fn split(
+        &self,
+        mut x: OS_Input_Coord,
+        mut y: OS_Input_Coord,
+        set: impl Fn(&mut MouseFrame, i8, i8),
+    ) -> Vec<MouseFrame> {
+        let limit = i8::MAX as OS_Input_Coord;
+        let total = limit * MAX_MOUSE_FRAMES as OS_Input_Coord;
+        x = x.clamp(-total, total);
+        y = y.clamp(-total, total);
+
+        let mut frames = vec![];
+        while x != 0 || y != 0 {
+            let step_x = x.clamp(-limit, limit);
+            let step_y = y.clamp(-limit, limit);
+            x -= step_x;
+            y -= step_y;
+
+            let mut frame = self.frame();
+            set(&mut frame, step_x as i8, step_y as i8);
+            frames.push(frame);
+        }
+        frames
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // Walks the short items, returns the input and output report sizes in bits
+    pub(crate) fn report_bits(descriptor: &[u8]) -> (usize, usize) {
+        let (mut size, mut count, mut input, mut output) = (0, 0, 0, 0);
+        let mut i = 0;
+        while i < descriptor.len() {
+            let prefix = descriptor[i];
+            let len = match prefix & 0b11 {
+                3 => 4,
+                n => n as usize,
+            };
+            let data = descriptor[i + 1..i + 1 + len].iter().rev().fold(0usize, |acc, b| acc << 8 | *b as usize);
+            match prefix & 0xfc {
+                0x74 => size = data,
+                0x94 => count = data,
+                0x80 => input += size * count,
+                0x90 => output += size * count,
+                _ => {}
+            }
+            i += 1 + len;
+        }
+        assert_eq!(i, descriptor.len());
+        (input, output)
+    }
+
+    #[test]
+    fn descriptors_match_report_sizes() {
+        assert_eq!(report_bits(&KEYBOARD_REPORT_DESCRIPTOR), (KEYBOARD_REPORT_SIZE * 8, 8));
+        assert_eq!(report_bits(&MOUSE_REPORT_DESCRIPTOR), (MOUSE_REPORT_SIZE * 8, 0));
+    }
+
+    #[test]
+    fn keyboard_report_bytes() {
+        let mut report = KeyboardReport::new();
+        report.press(KeyCode::KEY_LEFTSHIFT).unwrap();
+        report.press(KeyCode::KEY_A).unwrap();
+        report.press(KeyCode::KEY_B).unwrap();
+        report.press(KeyCode::KEY_RIGHTALT).unwrap();
+        assert_eq!(report.to_bytes(), [0x42, 0, 0x04, 0x05, 0, 0, 0, 0]);
+
+        report.release(KeyCode::KEY_A).unwrap();
+        report.release(KeyCode::KEY_LEFTSHIFT).unwrap();
+        assert_eq!(report.to_bytes(), [0x40, 0, 0x05, 0, 0, 0, 0, 0]);
+        assert!(report.press(KeyCode::MOUSE_LEFT).is_err());
+    }
+
+    #[test]
+    fn keyboard_roll_over() {
+        let mut report = KeyboardReport::new();
+        report.press(KeyCode::KEY_LEFTCTRL).unwrap();
+        for key_code in [KeyCode::KEY_A, KeyCode::KEY_B, KeyCode::KEY_C, KeyCode::KEY_D, KeyCode::KEY_E, KeyCode::KEY_F] {
+            report.press(key_code).unwrap();
+        }
+        assert_eq!(report.to_bytes(), [0x01, 0, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09]);
+
+        report.press(KeyCode::KEY_G).unwrap();
+        assert_eq!(report.to_bytes(), [0x01, 0, 1, 1, 1, 1, 1, 1]);
+
+        report.release(KeyCode::KEY_A).unwrap();
+        assert_eq!(report.to_bytes(), [0x01, 0, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a]);
+    }
+
+    #[test]
+    fn mouse_report_bytes() {
+        let mut report = MouseReport::new();
+        report.press(KeyCode::MOUSE_LEFT).unwrap();
+        let frame = report.press(KeyCode::MOUSE_SIDE).unwrap();
+        assert_eq!(frame.to_bytes(), [0b1001, 0, 0, 0, 0]);
+        assert_eq!(frame.to_boot_bytes(), [0b001, 0, 0]);
+
+        let frames = report.move_mouse(-3, 5);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].to_bytes(), [0b1001, 0xfd, 0xfb, 0, 0]);
+        assert_eq!(frames[0].to_boot_bytes(), [0b001, 0xfd, 0xfb]);
+
+        let frames = report.scroll(2, -1);
+        assert_eq!(frames[0].to_bytes(), [0b1001, 0, 0, 0xff, 0x02]);
+        assert_eq!(report.release_all().to_bytes(), [0; MOUSE_REPORT_SIZE]);
+        assert!(report.press(KeyCode::KEY_A).is_err());
+    }
+
+    #[test]
+    fn split_frames() {
+        let report = MouseReport::new();
+        let frames: Vec<_> = report.move_mouse(300, -130).iter().map(|frame| frame.to_bytes()).collect();
+        assert_eq!(frames, vec![[0, 127, 127, 0, 0], [0, 127, 3, 0, 0], [0, 46, 0, 0, 0]]);
+        assert!(report.move_mouse(0, 0).is_empty());
+    }
+
+    #[test]
+    fn split_saturates() {
+        let report = MouseReport::new();
+        let frames = report.move_mouse(OS_Input_Coord::MAX, OS_Input_Coord::MIN);
+        assert_eq!(frames.len(), MAX_MOUSE_FRAMES);
+        assert!(frames.iter().all(|frame| frame.x == 127 && frame.y == 127));
+        assert_eq!(report.scroll(OS_Input_Coord::MIN, 0).len(), MAX_MOUSE_FRAMES);
+    }
+}
@@ -0,0 +1,400 @@
+use std::collections::VecDeque;
+use std::io::Read;
+use color_eyre::Result;
+use crate::record::{EvdevReader, FrameDecoder};
+use crate::{InputBatch, InputEmulator, InputEvent, KeyCode, KeyCodes};
+
+#[cfg(feature = "capture")]
+use std::fs::{File, OpenOptions};
+#[cfg(feature = "capture")]
+use std::os::unix::io::AsRawFd;
+#[cfg(feature = "capture")]
+use std::path::{Path, PathBuf};
+#[cfg(feature = "capture")]
+use crate::{err_eyre, exec_or_eyre};
+
+pub const INPUT_DEVICES_DIR: &str = "/dev/input";
+
+// ioctl request codes, see linux/input.h
+#[cfg(feature = "capture")]
+const EVIOCGRAB: u64 = 0x40044590;
+
+#[cfg(feature = "capture")]
+const fn eviocgname(len: usize) -> u64 {
+    2 << 30 | (len as u64) << 16 | (b'E' as u64) << 8 | 0x06
+}
+
+// Turns raw input_event structs from any reader into InputEvents, one SYN_REPORT frame at a time
+pub struct InputEventStream<R: Read> {
+    reader: EvdevReader<R>,
+    decoder: FrameDecoder,
+    pending: VecDeque<InputEvent>,
+}
+
+impl<R: Read> InputEventStream<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader: EvdevReader::new(reader),
+            decoder: FrameDecoder::default(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    // None at the end of the stream
+    pub fn next_event(&mut self) -> Result<Option<InputEvent>> {
+        while self.pending.is_empty() {
+            let Some(raw_event) = self.reader.read_event()? else {
+                return Ok(None);
+            };
+            if let Some(events) = self.decoder.feed(&raw_event) {
+                self.pending.extend(events);
+            }
+        }
+        Ok(self.pending.pop_front())
+    }
+}
+
+impl<R: Read> Iterator for InputEventStream<R> {
+    type Item = Result<InputEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_event().transpose()
+    }
+}
+
+#[cfg(feature = "capture")]
+pub struct InputCapture {
+    file: File,
+    grabbed: bool,
+}
+
+#[cfg(feature = "capture")]
+impl InputCapture {
+    // e.g. /dev/input/event3, reading it usually requires root or the input group
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let file = exec_or_eyre!(OpenOptions::new().read(true).open(path))?;
+        Ok(Self {
+            file,
+            grabbed: false,
+        })
+    }
+
+    // event* nodes sorted by number
+    pub fn list_devices() -> Result<Vec<PathBuf>> {
+        let mut devices = vec![];
+        for entry in exec_or_eyre!(std::fs::read_dir(INPUT_DEVICES_DIR))? {
+            let path = exec_or_eyre!(entry)?.path();
+            let number = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix("event"))
+                .and_then(|number| number.parse::<u32>().ok());
+            if let Some(number) = number {
+                devices.push((number, path));
+            }
+        }
+        devices.sort();
+        Ok(devices.into_iter().map(|(_, path)| path).collect())
+    }
+
+    pub fn name(&self) -> Result<String> {
+        let mut name = [0u8; 256];
+        let result = unsafe { libc::ioctl(self.file.as_raw_fd(), eviocgname(name.len()) as _, name.as_mut_ptr()) };
+        if result < 0 {
+            return Err(err_eyre!(std::io::Error::last_os_error()));
+        }
+        let len = name.iter().position(|byte| *byte == 0).unwrap_or(name.len());
+        Ok(String::from_utf8_lossy(&name[..len]).into_owned())
+    }
+
+    // Exclusive access: other readers, including the desktop, stop receiving events from the device
+    pub fn grab(&mut self) -> Result<()> {
+        self.set_grab(true)
+    }
+
+    pub fn ungrab(&mut self) -> Result<()> {
+        self.set_grab(false)
+    }
+
+    #[inline]
+    pub fn is_grabbed(&self) -> bool {
+        self.grabbed
+    }
+
+    fn set_grab(&mut self, grab: bool) -> Result<()> {
+        let result = unsafe { libc::ioctl(self.file.as_raw_fd(), EVIOCGRAB as _, grab as libc::c_int) };
+        if result < 0 {
+            return Err(err_eyre!(std::io::Error::last_os_error()));
+        }
+        self.grabbed = grab;
+        Ok(())
+    }
+
+    pub fn events(&self) -> InputEventStream<&File> {
+        InputEventStream::new(&self.file)
+    }
+}
+
+#[cfg(feature = "capture")]
+impl Drop for InputCapture {
+    fn drop(&mut self) {
+        if self.grabbed {
+            let _ = self.set_grab(false);
+        }
+    }
+}
+
+type RemapStage = Box<dyn FnMut(InputEvent, &mut InputBatch) + Send>;
+
+// Stages run in the order they were added, each one sees the output of the previous one
+#[derive(Default)]
+pub struct RemapPipeline {
+    stages: Vec<RemapStage>,
+    held: KeyCodes,
+}
+
+impl RemapPipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Custom stage, push nothing to drop the event or several events to expand it
+    pub fn stage(mut self, stage: impl FnMut(InputEvent, &mut InputBatch) + Send + 'static) -> Self {
+        self.stages.push(Box::new(stage));
+        self
+    }
+
+    pub fn map_key(self, from: KeyCode, to: KeyCode) -> Self {
+        self.stage(move |event, output| {
+            output.push(match event {
+                InputEvent::Press(key_code) if key_code == from => InputEvent::Press(to),
+                InputEvent::Release(key_code) if key_code == from => InputEvent::Release(to),
+                event => event,
+            });
+        })
+    }
+
+    pub fn swap_keys(self, first: KeyCode, second: KeyCode) -> Self {
+        let swap = move |key_code: KeyCode| match key_code {
+            key_code if key_code == first => second,
+            key_code if key_code == second => first,
+            key_code => key_code,
+        };
+
+        self.stage(move |event, output| {
+            output.push(match event {
+                InputEvent::Press(key_code) => InputEvent::Press(swap(key_code)),
+                InputEvent::Release(key_code) => InputEvent::Release(swap(key_code)),
+                event => event,
+            });
+        })
+    }
+
+    pub fn disable_key(self, disabled: KeyCode) -> Self {
+        self.stage(move |event, output| match event {
+            InputEvent::Press(key_code) | InputEvent::Release(key_code) if key_code == disabled => {}
+            event => {
+                output.push(event);
+            }
+        })
+    }
+
+    pub fn invert_scroll(self) -> Self {
+        self.stage(|event, output| {
+            output.push(match event {
+                InputEvent::Scroll(x, y) => InputEvent::Scroll(-x, -y),
+                event => event,
+            });
+        })
+    }
+
+    pub fn transform(&mut self, event: InputEvent) -> InputBatch {
+        let mut events = InputBatch::from(vec![event]);
+        for stage in &mut self.stages {
+            let mut output = InputBatch::new();
+            for event in events {
+                stage(event, &mut output);
+            }
+            events = output;
+        }
+
+        for event in &events {
+            match *event {
+                InputEvent::Press(key_code) if !self.held.contains(&key_code) => self.held.push(key_code),
+                InputEvent::Release(key_code) => self.held.retain(|held| *held != key_code),
+                _ => {}
+            }
+        }
+        events
+    }
+
+    // Keys pressed through the pipeline that weren't released yet
+    #[inline]
+    pub fn held_keys(&self) -> &[KeyCode] {
+        &self.held
+    }
+
+    pub fn release_held(&mut self, emulator: &mut InputEmulator) -> Result<()> {
+        while let Some(key_code) = self.held.pop() {
+            emulator.release(key_code)?;
+        }
+        Ok(())
+    }
+
+    // Runs until the stream ends or fails, keys still held are released either way.
+    // Works with InputCapture::events() or any synthetic stream.
+    pub fn run<I>(&mut self, events: I, emulator: &mut InputEmulator) -> Result<()>
+    where
+        I: IntoIterator<Item = Result<InputEvent>>,
+    {
+        let result = self.forward(events, emulator);
+        let released = self.release_held(emulator);
+        result.and(released)
+    }
+
+    fn forward<I>(&mut self, events: I, emulator: &mut InputEmulator) -> Result<()>
+    where
+        I: IntoIterator<Item = Result<InputEvent>>,
+    {
+        for event in events {
+            let batch = self.transform(event?);
+            if !batch.is_empty() {
+                emulator.write_buffer(&batch)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::c_long;
+    use crate::key_codes::*;
+    use super::*;
+
+    fn raw_events(events: &[(u16, u16, i32)]) -> Vec<u8> {
+        let mut bytes = vec![];
+        for (index, (event_type, code, value)) in events.iter().enumerate() {
+            bytes.extend_from_slice(&(1 as c_long).to_ne_bytes());
+            bytes.extend_from_slice(&(index as c_long).to_ne_bytes());
+            bytes.extend_from_slice(&event_type.to_ne_bytes());
+            bytes.extend_from_slice(&code.to_ne_bytes());
+            bytes.extend_from_slice(&value.to_ne_bytes());
+        }
+        bytes
+    }
+
+    fn collect(bytes: &[u8]) -> Vec<InputEvent> {
+        InputEventStream::new(bytes).collect::<Result<_>>().unwrap()
+    }
+
+    #[test]
+    fn stream_decodes_frames() {
+        let bytes = raw_events(&[
+            (EV_REL, REL_X, 2),
+            (EV_REL, REL_X, 3),
+            (EV_REL, REL_Y, 4),
+            (EV_KEY, BTN_LEFT, 1),
+            (EV_SYN, SYN_REPORT, 0),
+            // Autorepeat and unknown codes are skipped
+            (EV_KEY, KEY_A, 2),
+            (EV_KEY, 0x2ff, 1),
+            (EV_SYN, SYN_REPORT, 0),
+            (EV_REL, REL_WHEEL, -1),
+            (EV_REL, REL_HWHEEL, 1),
+            (EV_KEY, BTN_LEFT, 0),
+            (EV_SYN, SYN_REPORT, 0),
+        ]);
+        assert_eq!(collect(&bytes), [
+            InputEvent::Move(5, -4),
+            InputEvent::Press(KeyCode::MOUSE_LEFT),
+            InputEvent::Scroll(1, -1),
+            InputEvent::Release(KeyCode::MOUSE_LEFT),
+        ]);
+    }
+
+    #[test]
+    fn stream_drops_incomplete_frame() {
+        let bytes = raw_events(&[
+            (EV_SYN, SYN_DROPPED, 0),
+            (EV_KEY, KEY_A, 1),
+            (EV_SYN, SYN_REPORT, 0),
+            (EV_KEY, KEY_B, 1),
+            (EV_SYN, SYN_REPORT, 0),
+            // Frame without SYN_REPORT at the end of the stream
+            (EV_KEY, KEY_C, 1),
+        ]);
+        assert_eq!(collect(&bytes), [InputEvent::Press(KeyCode::KEY_B)]);
+    }
+
+    #[test]
+    fn stream_ends_on_truncated_event() {
+        let bytes = raw_events(&[(EV_KEY, KEY_A, 1), (EV_SYN, SYN_REPORT, 0)]);
+        let mut stream = InputEventStream::new(&bytes[..bytes.len() - 1]);
+        // read_exact hits the end mid-event, which counts as the end of the stream
+        assert_eq!(stream.next_event().unwrap(), None);
+    }
+
+    #[test]
+    fn pipeline_runs_stages_in_order() {
+        let mut pipeline = RemapPipeline::new()
+            .swap_keys(KeyCode::KEY_A, KeyCode::KEY_B)
+            .map_key(KeyCode::KEY_B, KeyCode::KEY_C)
+            .disable_key(KeyCode::KEY_CAPSLOCK)
+            .invert_scroll()
+            .stage(|event, output| {
+                output.push(event);
+                if event == InputEvent::Press(KeyCode::KEY_ENTER) {
+                    output.push(InputEvent::Sync);
+                }
+            });
+
+        // A -> B -> C, B -> A
+        assert_eq!(pipeline.transform(InputEvent::Press(KeyCode::KEY_A)).events(), [InputEvent::Press(KeyCode::KEY_C)]);
+        assert_eq!(pipeline.transform(InputEvent::Press(KeyCode::KEY_B)).events(), [InputEvent::Press(KeyCode::KEY_A)]);
+        assert!(pipeline.transform(InputEvent::Press(KeyCode::KEY_CAPSLOCK)).is_empty());
+        assert_eq!(pipeline.transform(InputEvent::Scroll(1, -2)).events(), [InputEvent::Scroll(-1, 2)]);
+        assert_eq!(
+            pipeline.transform(InputEvent::Press(KeyCode::KEY_ENTER)).events(),
+            [InputEvent::Press(KeyCode::KEY_ENTER), InputEvent::Sync],
+        );
+
+        assert_eq!(pipeline.held_keys(), [KeyCode::KEY_C, KeyCode::KEY_A, KeyCode::KEY_ENTER]);
+        pipeline.transform(InputEvent::Release(KeyCode::KEY_B));
+        assert_eq!(pipeline.held_keys(), [KeyCode::KEY_C, KeyCode::KEY_ENTER]);
+    }
+
+    #[cfg(feature = "use_uinput")]
+    #[test]
+    fn pipeline_releases_keys_when_stream_fails() {
+        use std::io::Read;
+        use color_eyre::eyre::eyre;
+        use crate::uinput::tests::decode_events;
+
+        let (mut reader, writer) = std::io::pipe().unwrap();
+        let mut emulator = InputEmulator::from_writer(writer);
+        let mut pipeline = RemapPipeline::new().map_key(KeyCode::KEY_CAPSLOCK, KeyCode::KEY_LEFTCTRL);
+
+        let events = vec![
+            Ok(InputEvent::Press(KeyCode::KEY_CAPSLOCK)),
+            Ok(InputEvent::Move(1, 1)),
+            Err(eyre!("device unplugged")),
+            Ok(InputEvent::Release(KeyCode::KEY_CAPSLOCK)),
+        ];
+        assert!(pipeline.run(events, &mut emulator).is_err());
+        assert!(pipeline.held_keys().is_empty());
+        drop(emulator);
+
+        let mut written = vec![];
+        reader.read_to_end(&mut written).unwrap();
+        assert_eq!(decode_events(&written), [
+            (EV_KEY, KEY_LEFTCTRL, 1),
+            (EV_SYN, SYN_REPORT, 0),
+            (EV_REL, REL_X, 1),
+            (EV_REL, REL_Y, -1),
+            (EV_SYN, SYN_REPORT, 0),
+            (EV_KEY, KEY_LEFTCTRL, 0),
+            (EV_SYN, SYN_REPORT, 0),
+        ]);
+    }
+}
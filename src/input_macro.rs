@@ -0,0 +1,415 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+use color_eyre::eyre::bail;
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+use crate::{exec_or_eyre, InputEmulator, KeyCode, KeyCodes, OS_Input_Coord};
+
+// Delays are slept in slices so cancellation doesn't wait for a long delay to end
+const CANCEL_CHECK_INTERVAL: Duration = Duration::from_millis(5);
+
+#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+pub enum MacroStep {
+    Press(KeyCode),
+    Release(KeyCode),
+    Tap(KeyCode),
+    Move(OS_Input_Coord, OS_Input_Coord),
+    Scroll(OS_Input_Coord, OS_Input_Coord),
+    // Milliseconds
+    Delay(u64),
+    TypeText(String),
+}
+
+#[derive(PartialEq, Eq, Clone, Default, Debug, Serialize, Deserialize)]
+pub struct Macro {
+    pub steps: Vec<MacroStep>,
+}
+
+impl Macro {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        exec_or_eyre!(serde_json::from_str(json))
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        exec_or_eyre!(serde_json::to_string(self))
+    }
+
+    #[inline]
+    pub fn push(&mut self, step: MacroStep) -> &mut Self {
+        self.steps.push(step);
+        self
+    }
+
+    #[inline]
+    pub fn press(&mut self, key_code: KeyCode) -> &mut Self {
+        self.push(MacroStep::Press(key_code))
+    }
+
+    #[inline]
+    pub fn release(&mut self, key_code: KeyCode) -> &mut Self {
+        self.push(MacroStep::Release(key_code))
+    }
+
+    #[inline]
+    pub fn tap(&mut self, key_code: KeyCode) -> &mut Self {
+        self.push(MacroStep::Tap(key_code))
+    }
+
+    // Presses modifiers in order, taps the last key, releases in reverse order
+    pub fn chord(&mut self, key_codes: &[KeyCode]) -> &mut Self {
+        if let Some((key_code, modifiers)) = key_codes.split_last() {
+            for modifier in modifiers {
+                self.press(*modifier);
+            }
+            self.tap(*key_code);
+            for modifier in modifiers.iter().rev() {
+                self.release(*modifier);
+            }
+        }
+        self
+    }
+
+    #[inline]
+    pub fn move_mouse(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> &mut Self {
+        self.push(MacroStep::Move(x, y))
+    }
+
+    #[inline]
+    pub fn scroll(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> &mut Self {
+        self.push(MacroStep::Scroll(x, y))
+    }
+
+    #[inline]
+    pub fn delay(&mut self, ms: u64) -> &mut Self {
+        self.push(MacroStep::Delay(ms))
+    }
+
+    #[inline]
+    pub fn type_text(&mut self, text: &str) -> &mut Self {
+        self.push(MacroStep::TypeText(text.to_string()))
+    }
+
+    // Fails on the first character that has no key on the US layout
+    pub fn validate(&self) -> Result<()> {
+        for step in &self.steps {
+            if let MacroStep::TypeText(text) = step {
+                for ch in text.chars() {
+                    if KeyCode::from_char(ch).is_none() {
+                        bail!("Can't type character: {ch:?}");
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<Vec<MacroStep>> for Macro {
+    fn from(steps: Vec<MacroStep>) -> Self {
+        Self { steps }
+    }
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct MacroCancel {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl MacroCancel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+    }
+
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub fn reset(&self) {
+        self.cancelled.store(false, Ordering::Release);
+    }
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum MacroStatus {
+    Completed,
+    Cancelled,
+}
+
+// What a macro drives, the local emulator or a remote one
+pub(crate) trait MacroTarget {
+    fn press(&mut self, key_code: KeyCode) -> Result<()>;
+    fn release(&mut self, key_code: KeyCode) -> Result<()>;
+    fn move_mouse(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()>;
+    fn scroll_x(&mut self, value: OS_Input_Coord) -> Result<()>;
+    fn scroll_y(&mut self, value: OS_Input_Coord) -> Result<()>;
+}
+
+impl MacroTarget for InputEmulator {
+    #[inline]
+    fn press(&mut self, key_code: KeyCode) -> Result<()> {
+        InputEmulator::press(self, key_code)
+    }
+
+    #[inline]
+    fn release(&mut self, key_code: KeyCode) -> Result<()> {
+        InputEmulator::release(self, key_code)
+    }
+
+    #[inline]
+    fn move_mouse(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        InputEmulator::move_mouse(self, x, y)
+    }
+
+    #[inline]
+    fn scroll_x(&mut self, value: OS_Input_Coord) -> Result<()> {
+        InputEmulator::scroll_x(self, value)
+    }
+
+    #[inline]
+    fn scroll_y(&mut self, value: OS_Input_Coord) -> Result<()> {
+        InputEmulator::scroll_y(self, value)
+    }
+}
+
+// Keeps track of keys the macro holds down and releases them when dropped,
+// so an error, cancellation or panic never leaves a key stuck
+pub(crate) struct HeldKeys<'a, T: MacroTarget = InputEmulator> {
+    pub(crate) emulator: &'a mut T,
+    held: KeyCodes,
+}
+
+impl<'a, T: MacroTarget> HeldKeys<'a, T> {
+    pub(crate) fn new(emulator: &'a mut T) -> Self {
+        Self {
+            emulator,
+            held: vec![],
+        }
+    }
+
+    pub(crate) fn press(&mut self, key_code: KeyCode) -> Result<()> {
+        if !self.held.contains(&key_code) {
+            self.held.push(key_code);
+        }
+        self.emulator.press(key_code)
+    }
+
+    pub(crate) fn release(&mut self, key_code: KeyCode) -> Result<()> {
+        self.held.retain(|held| *held != key_code);
+        self.emulator.release(key_code)
+    }
+
+    pub(crate) fn tap(&mut self, key_code: KeyCode) -> Result<()> {
+        self.press(key_code)?;
+        self.release(key_code)
+    }
+
+    pub(crate) fn type_char(&mut self, ch: char) -> Result<()> {
+        let Some((key_code, shift)) = KeyCode::from_char(ch) else {
+            bail!("Can't type character: {ch:?}");
+        };
+
+        // A Shift the macro already holds stays held, tapping it here would lift it early
+        let add_shift = shift
+            && !self.held.contains(&KeyCode::KEY_LEFTSHIFT)
+            && !self.held.contains(&KeyCode::KEY_RIGHTSHIFT);
+
+        if add_shift {
+            self.press(KeyCode::KEY_LEFTSHIFT)?;
+        }
+        self.tap(key_code)?;
+        if add_shift {
+            self.release(KeyCode::KEY_LEFTSHIFT)?;
+        }
+        Ok(())
+    }
+
+    // Keys the macro intentionally leaves pressed stay pressed on completion
+    pub(crate) fn keep(mut self) {
+        self.held.clear();
+    }
+}
+
+impl<T: MacroTarget> Drop for HeldKeys<'_, T> {
+    fn drop(&mut self) {
+        while let Some(key_code) = self.held.pop() {
+            let _ = self.emulator.release(key_code);
+        }
+    }
+}
+
+pub(crate) fn cancellable_sleep(duration: Duration, cancel: &MacroCancel) -> bool {
+    let deadline = Instant::now() + duration;
+    loop {
+        if cancel.is_cancelled() {
+            return false;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        sleep(CANCEL_CHECK_INTERVAL.min(deadline - now));
+    }
+}
+
+pub(crate) fn run_macro_on<T: MacroTarget>(target: &mut T, input_macro: &Macro, cancel: &MacroCancel) -> Result<MacroStatus> {
+    input_macro.validate()?;
+
+    let mut keys = HeldKeys::new(target);
+
+    for step in &input_macro.steps {
+        if cancel.is_cancelled() {
+            return Ok(MacroStatus::Cancelled);
+        }
+
+        match step {
+            MacroStep::Press(key_code) => keys.press(*key_code)?,
+            MacroStep::Release(key_code) => keys.release(*key_code)?,
+            MacroStep::Tap(key_code) => keys.tap(*key_code)?,
+            MacroStep::Move(x, y) => keys.emulator.move_mouse(*x, *y)?,
+            MacroStep::Scroll(x, y) => {
+                if *x != 0 {
+                    keys.emulator.scroll_x(*x)?;
+                }
+                if *y != 0 {
+                    keys.emulator.scroll_y(*y)?;
+                }
+            }
+            MacroStep::Delay(ms) => {
+                if !cancellable_sleep(Duration::from_millis(*ms), cancel) {
+                    return Ok(MacroStatus::Cancelled);
+                }
+            }
+            MacroStep::TypeText(text) => {
+                for ch in text.chars() {
+                    if cancel.is_cancelled() {
+                        return Ok(MacroStatus::Cancelled);
+                    }
+                    keys.type_char(ch)?;
+                }
+            }
+        }
+    }
+
+    keys.keep();
+    Ok(MacroStatus::Completed)
+}
+
+impl InputEmulator {
+    #[inline]
+    pub fn run_macro(&mut self, input_macro: &Macro, cancel: &MacroCancel) -> Result<MacroStatus> {
+        run_macro_on(self, input_macro, cancel)
+    }
+}
+
+#[cfg(all(test, feature = "use_uinput"))]
+mod tests {
+    use std::io::{PipeReader, Read};
+    use std::thread;
+    use crate::key_codes::{EV_KEY, KEY_A, KEY_B, KEY_LEFTCTRL, KEY_LEFTSHIFT};
+    use crate::uinput::tests::decode_events;
+    use super::*;
+
+    // Key events as (code, value)
+    fn keys_written(mut reader: PipeReader) -> Vec<(u16, i32)> {
+        let mut written = vec![];
+        reader.read_to_end(&mut written).unwrap();
+        decode_events(&written).into_iter()
+            .filter(|(event_type, _, _)| *event_type == EV_KEY)
+            .map(|(_, code, value)| (code, value))
+            .collect()
+    }
+
+    #[test]
+    fn cancel_during_delay_releases_held_keys() {
+        let (reader, writer) = std::io::pipe().unwrap();
+        let mut emulator = InputEmulator::from_writer(writer);
+
+        let mut input_macro = Macro::new();
+        input_macro
+            .press(KeyCode::KEY_LEFTCTRL)
+            .delay(10_000)
+            .tap(KeyCode::KEY_A);
+        let cancel = MacroCancel::new();
+        let canceller = {
+            let cancel = cancel.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(20));
+                cancel.cancel();
+            })
+        };
+
+        let started = Instant::now();
+        let status = emulator.run_macro(&input_macro, &cancel).unwrap();
+        assert_eq!(status, MacroStatus::Cancelled);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        canceller.join().unwrap();
+        drop(emulator);
+
+        assert_eq!(keys_written(reader), [(KEY_LEFTCTRL, 1), (KEY_LEFTCTRL, 0)]);
+    }
+
+    #[test]
+    fn error_mid_macro_releases_held_keys() {
+        let (reader, writer) = std::io::pipe().unwrap();
+        let mut emulator = InputEmulator::from_writer(writer);
+
+        let mut input_macro = Macro::new();
+        input_macro
+            .press(KeyCode::KEY_LEFTCTRL)
+            .press(KeyCode::KEY_LEFTSHIFT)
+            .tap(KeyCode::KEY_A)
+            // No evdev code
+            .press(KeyCode::None)
+            .tap(KeyCode::KEY_B);
+        assert!(emulator.run_macro(&input_macro, &MacroCancel::new()).is_err());
+        drop(emulator);
+
+        // Released in reverse order of pressing
+        assert_eq!(keys_written(reader), [
+            (KEY_LEFTCTRL, 1),
+            (KEY_LEFTSHIFT, 1),
+            (KEY_A, 1),
+            (KEY_A, 0),
+            (KEY_LEFTSHIFT, 0),
+            (KEY_LEFTCTRL, 0),
+        ]);
+    }
+
+    #[test]
+    fn held_shift_survives_shifted_text() {
+        let (reader, writer) = std::io::pipe().unwrap();
+        let mut emulator = InputEmulator::from_writer(writer);
+
+        let mut input_macro = Macro::new();
+        input_macro
+            .press(KeyCode::KEY_LEFTSHIFT)
+            .type_text("A")
+            .tap(KeyCode::KEY_B)
+            .release(KeyCode::KEY_LEFTSHIFT);
+        let status = emulator.run_macro(&input_macro, &MacroCancel::new()).unwrap();
+        assert_eq!(status, MacroStatus::Completed);
+        drop(emulator);
+
+        assert_eq!(keys_written(reader), [
+            (KEY_LEFTSHIFT, 1),
+            (KEY_A, 1),
+            (KEY_A, 0),
+            (KEY_B, 1),
+            (KEY_B, 0),
+            (KEY_LEFTSHIFT, 0),
+        ]);
+    }
+}
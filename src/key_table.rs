@@ -0,0 +1,639 @@
+use color_eyre::eyre::bail;
+use color_eyre::{Report, Result};
+use strum::IntoEnumIterator;
+use crate::key_codes::*;
+use crate::{KeyCode, KeyCodes};
+
+#[cfg(feature = "use_tfc")]
+use tfc::Key as TfcKey;
+#[cfg(feature = "use_hidg")]
+use hidg::Key as HidgKey;
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct KeyMapping {
+    pub key_code: KeyCode,
+    // Also used by mki, uinput, wayland and libei
+    pub evdev: Option<u16>,
+    // Keyboard/keypad page (0x07), also used by uhid
+    pub hid_usage: Option<u16>,
+    // X11 keysym, also used by enigo on Linux, rfb and xtest
+    pub keysym: Option<u32>,
+    // Windows virtual-key code, also used by enigo on Windows
+    pub windows_vk: Option<u16>,
+    // Variant names of the backend key enums
+    pub tfc: Option<&'static str>,
+    pub hidg: Option<&'static str>,
+    // QEMU QKeyCode name, used by qmp
+    pub qcode: Option<&'static str>,
+}
+
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
+pub struct EvdevCode(pub u16);
+
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
+pub struct HidUsage(pub u16);
+
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
+pub struct Keysym(pub u32);
+
+// Keys that share their keysym with another row, from_keysym never returns them.
+// Again and Redo are both XK_Redo, the CD keys are all XF86Eject.
+pub const KEYSYM_ALIASES: &[KeyCode] = &[KeyCode::KEY_AGAIN, KeyCode::KEY_CLOSECD, KeyCode::KEY_EJECTCLOSECD];
+
+// Keys that share their Windows VK with another row, from_windows_vk never returns them.
+// Keypad Enter is VK_RETURN with the extended key flag.
+pub const WINDOWS_VK_ALIASES: &[KeyCode] = &[KeyCode::KEY_KPENTER];
+
+macro_rules! column {
+    (_) => {
+        None
+    };
+    ($value:expr) => {
+        Some($value)
+    };
+}
+
+macro_rules! column_name {
+    (_) => {
+        None
+    };
+    ($variant:ident) => {
+        Some(stringify!($variant))
+    };
+}
+
+#[cfg(any(feature = "use_tfc", feature = "use_hidg"))]
+macro_rules! variant {
+    ($enum:ident, _) => {
+        None
+    };
+    ($enum:ident, $variant:ident) => {
+        Some($enum::$variant)
+    };
+}
+
+// One row per key: KeyCode variant, evdev code, HID usage, X11 keysym, Windows VK, tfc Key, hidg Key, QEMU qcode.
+// `_` marks a missing mapping. Every backend conversion is generated from here.
+macro_rules! key_table {
+    ($($key_code:ident: $evdev:tt, $hid_usage:tt, $keysym:tt, $windows_vk:tt, $tfc:tt, $hidg:tt, $qcode:tt;)*) => {
+        pub const KEY_TABLE: &[KeyMapping] = &[
+            $(KeyMapping {
+                key_code: KeyCode::$key_code,
+                evdev: column!($evdev),
+                hid_usage: column!($hid_usage),
+                keysym: column!($keysym),
+                windows_vk: column!($windows_vk),
+                tfc: column_name!($tfc),
+                hidg: column_name!($hidg),
+                qcode: column!($qcode),
+            },)*
+        ];
+
+        impl KeyCode {
+            pub fn evdev_code(&self) -> Option<u16> {
+                match self {
+                    $(KeyCode::$key_code => column!($evdev),)*
+                    _ => None,
+                }
+            }
+
+            pub fn hid_usage(&self) -> Option<u16> {
+                match self {
+                    $(KeyCode::$key_code => column!($hid_usage),)*
+                    _ => None,
+                }
+            }
+
+            pub fn keysym(&self) -> Option<u32> {
+                match self {
+                    $(KeyCode::$key_code => column!($keysym),)*
+                    _ => None,
+                }
+            }
+
+            pub fn windows_vk(&self) -> Option<u16> {
+                match self {
+                    $(KeyCode::$key_code => column!($windows_vk),)*
+                    _ => None,
+                }
+            }
+
+            pub fn qcode(&self) -> Option<&'static str> {
+                match self {
+                    $(KeyCode::$key_code => column!($qcode),)*
+                    _ => None,
+                }
+            }
+
+            #[cfg(feature = "use_tfc")]
+            pub(crate) fn tfc_key(&self) -> Option<TfcKey> {
+                match self {
+                    $(KeyCode::$key_code => variant!(TfcKey, $tfc),)*
+                    _ => None,
+                }
+            }
+
+            #[cfg(feature = "use_hidg")]
+            pub(crate) fn hidg_key(&self) -> Option<HidgKey> {
+                match self {
+                    $(KeyCode::$key_code => variant!(HidgKey, $hidg),)*
+                    _ => None,
+                }
+            }
+
+            // The tfc and hidg columns have no duplicates, see the tests
+            #[cfg(feature = "use_tfc")]
+            pub(crate) fn from_tfc_key(key: &TfcKey) -> Option<KeyCode> {
+                [$((variant!(TfcKey, $tfc), KeyCode::$key_code),)*]
+                    .into_iter()
+                    .find(|(row, _)| row.as_ref() == Some(key))
+                    .map(|(_, key_code)| key_code)
+            }
+
+            #[cfg(feature = "use_hidg")]
+            pub(crate) fn from_hidg_key(key: &HidgKey) -> Option<KeyCode> {
+                [$((variant!(HidgKey, $hidg), KeyCode::$key_code),)*]
+                    .into_iter()
+                    .find(|(row, _)| row.as_ref() == Some(key))
+                    .map(|(_, key_code)| key_code)
+            }
+        }
+    };
+}
+
+// Usages 0xe8..0xfb are reserved by the HID spec, only Linux maps them to media keys
+key_table! {
+    KEY_ESC: KEY_ESC, 0x29, 0xff1b, 0x1b, Escape, Esc, "esc";
+    KEY_1: KEY_1, 0x1e, 0x31, 0x31, N1, Num1, "1";
+    KEY_2: KEY_2, 0x1f, 0x32, 0x32, N2, Num2, "2";
+    KEY_3: KEY_3, 0x20, 0x33, 0x33, N3, Num3, "3";
+    KEY_4: KEY_4, 0x21, 0x34, 0x34, N4, Num4, "4";
+    KEY_5: KEY_5, 0x22, 0x35, 0x35, N5, Num5, "5";
+    KEY_6: KEY_6, 0x23, 0x36, 0x36, N6, Num6, "6";
+    KEY_7: KEY_7, 0x24, 0x37, 0x37, N7, Num7, "7";
+    KEY_8: KEY_8, 0x25, 0x38, 0x38, N8, Num8, "8";
+    KEY_9: KEY_9, 0x26, 0x39, 0x39, N9, Num9, "9";
+    KEY_10: KEY_10, 0x27, 0x30, 0x30, N0, Num0, "0";
+    KEY_MINUS: KEY_MINUS, 0x2d, 0x2d, 0xbd, Minus, Minus, "minus";
+    KEY_EQUAL: KEY_EQUAL, 0x2e, 0x3d, 0xbb, Equal, Equal, "equal";
+    KEY_BACKSPACE: KEY_BACKSPACE, 0x2a, 0xff08, 0x08, DeleteOrBackspace, BackSpace, "backspace";
+    KEY_TAB: KEY_TAB, 0x2b, 0xff09, 0x09, Tab, Tab, "tab";
+    KEY_Q: KEY_Q, 0x14, 0x71, 0x51, Q, Q, "q";
+    KEY_W: KEY_W, 0x1a, 0x77, 0x57, W, W, "w";
+    KEY_E: KEY_E, 0x08, 0x65, 0x45, E, E, "e";
+    KEY_R: KEY_R, 0x15, 0x72, 0x52, R, R, "r";
+    KEY_T: KEY_T, 0x17, 0x74, 0x54, T, T, "t";
+    KEY_Y: KEY_Y, 0x1c, 0x79, 0x59, Y, Y, "y";
+    KEY_U: KEY_U, 0x18, 0x75, 0x55, U, U, "u";
+    KEY_I: KEY_I, 0x0c, 0x69, 0x49, I, I, "i";
+    KEY_O: KEY_O, 0x12, 0x6f, 0x4f, O, O, "o";
+    KEY_P: KEY_P, 0x13, 0x70, 0x50, P, P, "p";
+    KEY_LEFTBRACE: KEY_LEFTBRACE, 0x2f, 0x5b, 0xdb, LeftBracket, LeftBrace, "bracket_left";
+    KEY_RIGHTBRACE: KEY_RIGHTBRACE, 0x30, 0x5d, 0xdd, RightBracket, RightBrace, "bracket_right";
+    KEY_ENTER: KEY_ENTER, 0x28, 0xff0d, 0x0d, ReturnOrEnter, Enter, "ret";
+    KEY_LEFTCTRL: KEY_LEFTCTRL, 0xe0, 0xffe3, 0xa2, Control, LeftCtrl, "ctrl";
+    KEY_A: KEY_A, 0x04, 0x61, 0x41, A, A, "a";
+    KEY_S: KEY_S, 0x16, 0x73, 0x53, S, S, "s";
+    KEY_D: KEY_D, 0x07, 0x64, 0x44, D, D, "d";
+    KEY_F: KEY_F, 0x09, 0x66, 0x46, F, F, "f";
+    KEY_G: KEY_G, 0x0a, 0x67, 0x47, G, G, "g";
+    KEY_H: KEY_H, 0x0b, 0x68, 0x48, H, H, "h";
+    KEY_J: KEY_J, 0x0d, 0x6a, 0x4a, J, J, "j";
+    KEY_K: KEY_K, 0x0e, 0x6b, 0x4b, K, K, "k";
+    KEY_L: KEY_L, 0x0f, 0x6c, 0x4c, L, L, "l";
+    KEY_SEMICOLON: KEY_SEMICOLON, 0x33, 0x3b, 0xba, Semicolon, Semicolon, "semicolon";
+    KEY_APOSTROPHE: KEY_APOSTROPHE, 0x34, 0x27, 0xde, Quote, Apostrophe, "apostrophe";
+    KEY_GRAVE: KEY_GRAVE, 0x35, 0x60, 0xc0, Grave, Grave, "grave_accent";
+    KEY_LEFTSHIFT: KEY_LEFTSHIFT, 0xe1, 0xffe1, 0xa0, Shift, LeftShift, "shift";
+    KEY_BACKSLASH: KEY_BACKSLASH, 0x31, 0x5c, 0xdc, Backslash, BackSlash, "backslash";
+    KEY_Z: KEY_Z, 0x1d, 0x7a, 0x5a, Z, Z, "z";
+    KEY_X: KEY_X, 0x1b, 0x78, 0x58, X, X, "x";
+    KEY_C: KEY_C, 0x06, 0x63, 0x43, C, C, "c";
+    KEY_V: KEY_V, 0x19, 0x76, 0x56, V, V, "v";
+    KEY_B: KEY_B, 0x05, 0x62, 0x42, B, B, "b";
+    KEY_N: KEY_N, 0x11, 0x6e, 0x4e, N, N, "n";
+    KEY_M: KEY_M, 0x10, 0x6d, 0x4d, M, M, "m";
+    KEY_COMMA: KEY_COMMA, 0x36, 0x2c, 0xbc, Comma, Comma, "comma";
+    KEY_DOT: KEY_DOT, 0x37, 0x2e, 0xbe, Period, Dot, "dot";
+    KEY_SLASH: KEY_SLASH, 0x38, 0x2f, 0xbf, Slash, Slash, "slash";
+    KEY_RIGHTSHIFT: KEY_RIGHTSHIFT, 0xe5, 0xffe2, 0xa1, RightShift, RightShift, "shift_r";
+    KEY_KPASTERISK: KEY_KPASTERISK, 0x55, 0xffaa, 0x6a, _, _, "kp_multiply";
+    KEY_LEFTALT: KEY_LEFTALT, 0xe2, 0xffe9, 0xa4, Alt, LeftAlt, "alt";
+    KEY_SPACE: KEY_SPACE, 0x2c, 0x20, 0x20, Space, Space, "spc";
+    KEY_CAPSLOCK: KEY_CAPSLOCK, 0x39, 0xffe5, 0x14, CapsLock, CapsLock, "caps_lock";
+    KEY_F1: KEY_F1, 0x3a, 0xffbe, 0x70, F1, F1, "f1";
+    KEY_F2: KEY_F2, 0x3b, 0xffbf, 0x71, F2, F2, "f2";
+    KEY_F3: KEY_F3, 0x3c, 0xffc0, 0x72, F3, F3, "f3";
+    KEY_F4: KEY_F4, 0x3d, 0xffc1, 0x73, F4, F4, "f4";
+    KEY_F5: KEY_F5, 0x3e, 0xffc2, 0x74, F5, F5, "f5";
+    KEY_F6: KEY_F6, 0x3f, 0xffc3, 0x75, F6, F6, "f6";
+    KEY_F7: KEY_F7, 0x40, 0xffc4, 0x76, F7, F7, "f7";
+    KEY_F8: KEY_F8, 0x41, 0xffc5, 0x77, F8, F8, "f8";
+    KEY_F9: KEY_F9, 0x42, 0xffc6, 0x78, F9, F9, "f9";
+    KEY_F10: KEY_F10, 0x43, 0xffc7, 0x79, F10, F10, "f10";
+    KEY_NUMLOCK: KEY_NUMLOCK, 0x53, 0xff7f, 0x90, _, _, "num_lock";
+    KEY_SCROLLLOCK: KEY_SCROLLLOCK, 0x47, 0xff14, 0x91, _, _, "scroll_lock";
+    KEY_KP7: KEY_KP7, 0x5f, 0xffb7, 0x67, Numpad7, KeyPad7, "kp_7";
+    KEY_KP8: KEY_KP8, 0x60, 0xffb8, 0x68, Numpad8, KeyPad8, "kp_8";
+    KEY_KP9: KEY_KP9, 0x61, 0xffb9, 0x69, Numpad9, KeyPad9, "kp_9";
+    KEY_KPMINUS: KEY_KPMINUS, 0x56, 0xffad, 0x6d, NumpadMinus, KeyPadMinus, "kp_subtract";
+    KEY_KP4: KEY_KP4, 0x5c, 0xffb4, 0x64, Numpad4, KeyPad4, "kp_4";
+    KEY_KP5: KEY_KP5, 0x5d, 0xffb5, 0x65, Numpad5, KeyPad5, "kp_5";
+    KEY_KP6: KEY_KP6, 0x5e, 0xffb6, 0x66, Numpad6, KeyPad6, "kp_6";
+    KEY_KPPLUS: KEY_KPPLUS, 0x57, 0xffab, 0x6b, NumpadPlus, KeyPadPlus, "kp_add";
+    KEY_KP1: KEY_KP1, 0x59, 0xffb1, 0x61, Numpad1, KeyPad1, "kp_1";
+    KEY_KP2: KEY_KP2, 0x5a, 0xffb2, 0x62, Numpad2, KeyPad2, "kp_2";
+    KEY_KP3: KEY_KP3, 0x5b, 0xffb3, 0x63, Numpad3, KeyPad3, "kp_3";
+    KEY_KP0: KEY_KP0, 0x62, 0xffb0, 0x60, Numpad0, KeyPad0, "kp_0";
+    KEY_KPDOT: KEY_KPDOT, 0x63, 0xffae, 0x6e, NumpadDecimal, KeyPadDot, "kp_decimal";
+    KEY_ZENKAKUHANKAKU: KEY_ZENKAKUHANKAKU, 0x94, 0xff2a, _, _, _, _;
+    KEY_102ND: KEY_102ND, 0x64, 0x3c, 0xe2, _, _, "less";
+    KEY_F11: KEY_F11, 0x44, 0xffc8, 0x7a, F11, F11, "f11";
+    KEY_F12: KEY_F12, 0x45, 0xffc9, 0x7b, F12, F12, "f12";
+    KEY_RO: KEY_RO, 0x87, _, _, _, _, "ro";
+    KEY_KATAKANA: KEY_KATAKANA, 0x92, 0xff26, _, _, _, _;
+    KEY_HIRAGANA: KEY_HIRAGANA, 0x93, 0xff25, _, _, _, "hiragana";
+    KEY_HENKAN: KEY_HENKAN, 0x8a, 0xff23, 0x1c, _, _, "henkan";
+    KEY_KATAKANAHIRAGANA: KEY_KATAKANAHIRAGANA, 0x88, 0xff27, _, _, _, "katakanahiragana";
+    KEY_MUHENKAN: KEY_MUHENKAN, 0x8b, 0xff22, 0x1d, _, _, "muhenkan";
+    KEY_KPJPCOMMA: KEY_KPJPCOMMA, 0x8c, _, _, _, _, _;
+    KEY_KPENTER: KEY_KPENTER, 0x58, 0xff8d, 0x0d, NumpadEnter, KeyPadEnter, "kp_enter";
+    KEY_RIGHTCTRL: KEY_RIGHTCTRL, 0xe4, 0xffe4, 0xa3, RightControl, RightCtrl, "ctrl_r";
+    KEY_KPSLASH: KEY_KPSLASH, 0x54, 0xffaf, 0x6f, NumpadDivide, KeyPadSlash, "kp_divide";
+    KEY_SYSRQ: KEY_SYSRQ, 0x46, 0xff61, 0x2c, _, _, "sysrq";
+    KEY_RIGHTALT: KEY_RIGHTALT, 0xe6, 0xffea, 0xa5, RightAlt, RightAlt, "alt_r";
+    KEY_LINEFEED: KEY_LINEFEED, _, 0xff0a, _, _, _, "lf";
+    KEY_HOME: KEY_HOME, 0x4a, 0xff50, 0x24, Home, Home, "home";
+    KEY_UP: KEY_UP, 0x52, 0xff52, 0x26, UpArrow, Up, "up";
+    KEY_PAGEUP: KEY_PAGEUP, 0x4b, 0xff55, 0x21, PageUp, PageUp, "pgup";
+    KEY_LEFT: KEY_LEFT, 0x50, 0xff51, 0x25, LeftArrow, Left, "left";
+    KEY_RIGHT: KEY_RIGHT, 0x4f, 0xff53, 0x27, RightArrow, Right, "right";
+    KEY_END: KEY_END, 0x4d, 0xff57, 0x23, End, End, "end";
+    KEY_DOWN: KEY_DOWN, 0x51, 0xff54, 0x28, DownArrow, Down, "down";
+    KEY_PAGEDOWN: KEY_PAGEDOWN, 0x4e, 0xff56, 0x22, PageDown, PageDown, "pgdn";
+    KEY_INSERT: KEY_INSERT, 0x49, 0xff63, 0x2d, Insert, Insert, "insert";
+    KEY_DELETE: KEY_DELETE, 0x4c, 0xffff, 0x2e, ForwardDelete, Delete, "delete";
+    KEY_MACRO: KEY_MACRO, _, _, _, _, _, _;
+    KEY_MUTE: KEY_MUTE, 0x7f, 0x1008ff12, 0xad, Mute, Mute, "audiomute";
+    KEY_VOLUMEDOWN: KEY_VOLUMEDOWN, 0x81, 0x1008ff11, 0xae, VolumeDown, VolumeDown, "volumedown";
+    KEY_VOLUMEUP: KEY_VOLUMEUP, 0x80, 0x1008ff13, 0xaf, VolumeUp, VolumeUp, "volumeup";
+    KEY_POWER: KEY_POWER, 0x66, 0x1008ff2a, _, _, _, "power";
+    KEY_KPEQUAL: KEY_KPEQUAL, 0x67, 0xffbd, 0x92, NumpadEquals, KeyPadEqual, "kp_equals";
+    KEY_KPPLUSMINUS: KEY_KPPLUSMINUS, 0xd7, 0xb1, _, _, _, _;
+    KEY_PAUSE: KEY_PAUSE, 0x48, 0xff13, 0x13, _, _, "pause";
+    KEY_SCALE: KEY_SCALE, _, 0x1008ff4a, _, _, _, _;
+    KEY_KPCOMMA: KEY_KPCOMMA, 0x85, 0xffac, 0x6c, _, _, "kp_comma";
+    KEY_HANGEUL: KEY_HANGEUL, 0x90, 0xff31, 0x15, _, _, "lang1";
+    KEY_HANJA: KEY_HANJA, 0x91, 0xff34, 0x19, _, _, "lang2";
+    KEY_YEN: KEY_YEN, 0x89, 0xa5, _, _, _, "yen";
+    KEY_LEFTMETA: KEY_LEFTMETA, 0xe3, 0xffeb, 0x5b, Meta, LeftMeta, "meta_l";
+    KEY_RIGHTMETA: KEY_RIGHTMETA, 0xe7, 0xffec, 0x5c, RightMeta, RightMeta, "meta_r";
+    KEY_COMPOSE: KEY_COMPOSE, 0x65, 0xff67, 0x5d, _, _, "compose";
+    KEY_STOP: KEY_STOP, 0x78, 0xff69, 0xa9, _, _, "stop";
+    KEY_AGAIN: KEY_AGAIN, 0x79, 0xff66, _, _, _, "again";
+    KEY_PROPS: KEY_PROPS, 0x76, 0x1005ff70, _, _, _, "props";
+    KEY_UNDO: KEY_UNDO, 0x7a, 0xff65, _, _, _, "undo";
+    KEY_FRONT: KEY_FRONT, 0x77, 0x1005ff71, _, _, _, "front";
+    KEY_COPY: KEY_COPY, 0x7c, 0x1008ff57, _, _, _, "copy";
+    KEY_OPEN: KEY_OPEN, 0x74, 0x1008ff6b, _, _, _, "open";
+    KEY_PASTE: KEY_PASTE, 0x7d, 0x1008ff6d, _, _, _, "paste";
+    KEY_FIND: KEY_FIND, 0x7e, 0xff68, 0xaa, _, _, "find";
+    KEY_CUT: KEY_CUT, 0x7b, 0x1008ff58, _, _, _, "cut";
+    KEY_HELP: KEY_HELP, 0x75, 0xff6a, 0x2f, _, _, "help";
+    KEY_MENU: KEY_MENU, _, 0x1008ff65, _, _, _, "menu";
+    KEY_CALC: KEY_CALC, 0xfb, 0x1008ff1d, 0xb7, _, _, "calculator";
+    KEY_SETUP: KEY_SETUP, _, _, _, _, _, _;
+    KEY_SLEEP: KEY_SLEEP, 0xf8, 0x1008ff2f, 0x5f, _, _, "sleep";
+    KEY_WAKEUP: KEY_WAKEUP, _, 0x1008ff2b, _, _, _, "wake";
+    KEY_FILE: KEY_FILE, _, 0x1008ff5d, _, _, _, _;
+    KEY_SENDFILE: KEY_SENDFILE, _, _, _, _, _, _;
+    KEY_DELETEFILE: KEY_DELETEFILE, _, _, _, _, _, _;
+    KEY_XFER: KEY_XFER, _, 0x1008ff8a, _, _, _, _;
+    KEY_PROG1: KEY_PROG1, _, 0x1008ff41, _, _, _, _;
+    KEY_PROG2: KEY_PROG2, _, 0x1008ff42, _, _, _, _;
+    KEY_WWW: KEY_WWW, 0xf0, 0x1008ff2e, _, _, _, _;
+    KEY_MSDOS: KEY_MSDOS, _, 0x1008ff5a, _, _, _, _;
+    KEY_SCREENLOCK: KEY_SCREENLOCK, _, 0x1008ff2d, _, _, _, _;
+    KEY_ROTATE_DISPLAY: KEY_ROTATE_DISPLAY, _, _, _, _, _, _;
+    KEY_CYCLEWINDOWS: KEY_CYCLEWINDOWS, _, 0x1008ff74, _, _, _, _;
+    KEY_MAIL: KEY_MAIL, _, 0x1008ff19, 0xb4, _, _, "mail";
+    KEY_BOOKMARKS: KEY_BOOKMARKS, _, 0x1008ff30, 0xab, _, _, "ac_bookmarks";
+    KEY_COMPUTER: KEY_COMPUTER, _, 0x1008ff33, 0xb6, _, _, "computer";
+    KEY_BACK: KEY_BACK, 0xf1, 0x1008ff26, 0xa6, _, _, "ac_back";
+    KEY_FORWARD: KEY_FORWARD, 0xf2, 0x1008ff27, 0xa7, _, _, "ac_forward";
+    KEY_CLOSECD: KEY_CLOSECD, _, 0x1008ff2c, _, _, _, _;
+    KEY_EJECTCD: KEY_EJECTCD, 0xec, 0x1008ff2c, _, _, _, _;
+    KEY_EJECTCLOSECD: KEY_EJECTCLOSECD, _, 0x1008ff2c, _, _, _, _;
+    KEY_NEXTSONG: KEY_NEXTSONG, 0xeb, 0x1008ff17, 0xb0, _, _, "audionext";
+    KEY_PLAYPAUSE: KEY_PLAYPAUSE, 0xe8, 0x1008ff14, 0xb3, _, _, "audioplay";
+    KEY_PREVIOUSSONG: KEY_PREVIOUSSONG, 0xea, 0x1008ff16, 0xb1, _, _, "audioprev";
+    KEY_STOPCD: KEY_STOPCD, 0xe9, 0x1008ff15, 0xb2, _, _, "audiostop";
+    KEY_RECORD: KEY_RECORD, _, 0x1008ff1c, _, _, _, _;
+    KEY_REWIND: KEY_REWIND, _, 0x1008ff3e, _, _, _, _;
+    KEY_PHONE: KEY_PHONE, _, 0x1008ff6e, _, _, _, _;
+    KEY_ISO: KEY_ISO, _, _, _, _, _, _;
+    KEY_CONFIG: KEY_CONFIG, _, 0x1008ff81, _, _, _, _;
+    KEY_HOMEPAGE: KEY_HOMEPAGE, _, 0x1008ff18, 0xac, _, _, "ac_home";
+    KEY_REFRESH: KEY_REFRESH, 0xfa, 0x1008ff29, 0xa8, _, _, "ac_refresh";
+    KEY_EXIT: KEY_EXIT, _, _, _, _, _, _;
+    KEY_MOVE: KEY_MOVE, _, _, _, _, _, _;
+    KEY_EDIT: KEY_EDIT, 0xf7, _, _, _, _, _;
+    KEY_SCROLLUP: KEY_SCROLLUP, 0xf5, 0x1008ff78, _, _, _, _;
+    KEY_SCROLLDOWN: KEY_SCROLLDOWN, 0xf6, 0x1008ff79, _, _, _, _;
+    KEY_KPLEFTPAREN: KEY_KPLEFTPAREN, 0xb6, 0x28, _, _, _, _;
+    KEY_KPRIGHTPAREN: KEY_KPRIGHTPAREN, 0xb7, 0x29, _, _, _, _;
+    KEY_NEW: KEY_NEW, _, 0x1008ff68, _, _, _, _;
+    KEY_REDO: KEY_REDO, _, 0xff66, _, _, _, _;
+    MOUSE_LEFT: BTN_LEFT, _, _, _, _, _, _;
+    MOUSE_RIGHT: BTN_RIGHT, _, _, _, _, _, _;
+    MOUSE_MIDDLE: BTN_MIDDLE, _, _, _, _, _, _;
+    MOUSE_SIDE: BTN_SIDE, _, _, _, _, _, _;
+    MOUSE_EXTRA: BTN_EXTRA, _, _, _, _, _, _;
+    MOUSE_FORWARD: BTN_FORWARD, _, _, _, _, _, _;
+    MOUSE_BACK: BTN_BACK, _, _, _, _, _, _;
+    MOUSE_TASK: BTN_TASK, _, _, _, _, _, _;
+}
+
+impl KeyCode {
+    pub fn from_evdev(code: u16) -> Option<KeyCode> {
+        KEY_TABLE
+            .iter()
+            .find(|mapping| mapping.evdev == Some(code))
+            .map(|mapping| mapping.key_code)
+    }
+
+    pub fn from_hid_usage(usage: u16) -> Option<KeyCode> {
+        KEY_TABLE
+            .iter()
+            .find(|mapping| mapping.hid_usage == Some(usage))
+            .map(|mapping| mapping.key_code)
+    }
+
+    pub fn from_keysym(keysym: u32) -> Option<KeyCode> {
+        KEY_TABLE
+            .iter()
+            .find(|mapping| mapping.keysym == Some(keysym) && !KEYSYM_ALIASES.contains(&mapping.key_code))
+            .map(|mapping| mapping.key_code)
+    }
+
+    pub fn from_windows_vk(vk: u16) -> Option<KeyCode> {
+        KEY_TABLE
+            .iter()
+            .find(|mapping| mapping.windows_vk == Some(vk) && !WINDOWS_VK_ALIASES.contains(&mapping.key_code))
+            .map(|mapping| mapping.key_code)
+    }
+}
+
+// Plain u16 is an evdev code, same as the constants in key_codes
+impl TryFrom<u16> for KeyCode {
+    type Error = Report;
+
+    fn try_from(code: u16) -> Result<Self> {
+        KeyCode::try_from(EvdevCode(code))
+    }
+}
+
+impl TryFrom<EvdevCode> for KeyCode {
+    type Error = Report;
+
+    fn try_from(code: EvdevCode) -> Result<Self> {
+        match KeyCode::from_evdev(code.0) {
+            Some(key_code) => Ok(key_code),
+            None => bail!("No such evdev code: {}", code.0),
+        }
+    }
+}
+
+impl TryFrom<HidUsage> for KeyCode {
+    type Error = Report;
+
+    fn try_from(usage: HidUsage) -> Result<Self> {
+        match KeyCode::from_hid_usage(usage.0) {
+            Some(key_code) => Ok(key_code),
+            None => bail!("No such HID usage: {:#04x}", usage.0),
+        }
+    }
+}
+
+impl TryFrom<Keysym> for KeyCode {
+    type Error = Report;
+
+    fn try_from(keysym: Keysym) -> Result<Self> {
+        match KeyCode::from_keysym(keysym.0) {
+            Some(key_code) => Ok(key_code),
+            None => bail!("No such keysym: {:#x}", keysym.0),
+        }
+    }
+}
+
+impl TryFrom<KeyCode> for EvdevCode {
+    type Error = Report;
+
+    fn try_from(key_code: KeyCode) -> Result<Self> {
+        match key_code.evdev_code() {
+            Some(code) => Ok(EvdevCode(code)),
+            None => bail!("No evdev code for key code: {key_code}"),
+        }
+    }
+}
+
+impl TryFrom<KeyCode> for HidUsage {
+    type Error = Report;
+
+    fn try_from(key_code: KeyCode) -> Result<Self> {
+        match key_code.hid_usage() {
+            Some(usage) => Ok(HidUsage(usage)),
+            None => bail!("No HID usage for key code: {key_code}"),
+        }
+    }
+}
+
+impl TryFrom<KeyCode> for Keysym {
+    type Error = Report;
+
+    fn try_from(key_code: KeyCode) -> Result<Self> {
+        match key_code.keysym() {
+            Some(keysym) => Ok(Keysym(keysym)),
+            None => bail!("No keysym for key code: {key_code}"),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct BackendCoverage {
+    pub backend: &'static str,
+    pub unmapped: KeyCodes,
+}
+
+impl KeyCode {
+    // Buttons go through the mouse API of keyboard-only backends
+    pub fn is_mouse_button(&self) -> bool {
+        matches!(
+            self,
+            KeyCode::MOUSE_LEFT
+                | KeyCode::MOUSE_RIGHT
+                | KeyCode::MOUSE_MIDDLE
+                | KeyCode::MOUSE_SIDE
+                | KeyCode::MOUSE_EXTRA
+                | KeyCode::MOUSE_FORWARD
+                | KeyCode::MOUSE_BACK
+                | KeyCode::MOUSE_TASK
+        )
+    }
+
+    // Control codes for the application, never sent to a device
+    pub fn is_virtual(&self) -> bool {
+        matches!(
+            self,
+            KeyCode::None | KeyCode::RESET_BTN | KeyCode::SWITCH_MODE_BTN | KeyCode::RELEASE_ALL
+        )
+    }
+}
+
+// Keys each backend can't send
+pub fn key_coverage() -> Vec<BackendCoverage> {
+    let unmapped = |backend: &'static str, keyboard_only: bool, is_mapped: fn(&KeyMapping) -> bool| {
+        let unmapped = KeyCode::iter()
+            .filter(|key_code| !key_code.is_virtual())
+            .filter(|key_code| !(keyboard_only && key_code.is_mouse_button()))
+            .filter(|key_code| {
+                !KEY_TABLE
+                    .iter()
+                    .any(|mapping| mapping.key_code == *key_code && is_mapped(mapping))
+            })
+            .collect();
+        BackendCoverage { backend, unmapped }
+    };
+
+    vec![
+        unmapped("mki, uinput, wayland, libei (evdev)", false, |mapping| mapping.evdev.is_some()),
+        unmapped("uhid (hid usage)", true, |mapping| mapping.hid_usage.is_some()),
+        unmapped("enigo linux, rfb, xtest (keysym)", true, |mapping| mapping.keysym.is_some()),
+        unmapped("enigo windows (vk)", true, |mapping| mapping.windows_vk.is_some()),
+        unmapped("tfc", true, |mapping| mapping.tfc.is_some()),
+        unmapped("hidg", true, |mapping| mapping.hidg.is_some()),
+        unmapped("qmp (qcode)", true, |mapping| mapping.qcode.is_some()),
+    ]
+}
+
+pub fn coverage_report() -> String {
+    let total = KeyCode::iter().filter(|key_code| !key_code.is_virtual()).count();
+    let mut report = String::new();
+    for coverage in key_coverage() {
+        let names: Vec<&str> = coverage.unmapped.iter().map(|key_code| key_code.as_ref()).collect();
+        report += &format!(
+            "{}: {} of {total} unmapped: {}\n",
+            coverage.backend,
+            coverage.unmapped.len(),
+            names.join(", ")
+        );
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use super::*;
+
+    // Values used by more than one row
+    fn duplicates<T: Eq + std::hash::Hash + Copy>(column: impl Fn(&KeyMapping) -> Option<T>) -> Vec<Vec<KeyCode>> {
+        let mut rows: HashMap<T, Vec<KeyCode>> = HashMap::new();
+        for mapping in KEY_TABLE.iter() {
+            if let Some(value) = column(mapping) {
+                rows.entry(value).or_default().push(mapping.key_code);
+            }
+        }
+        let mut duplicates: Vec<Vec<KeyCode>> = rows.into_values().filter(|keys| keys.len() > 1).collect();
+        duplicates.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        duplicates
+    }
+
+    #[test]
+    fn every_key_has_one_row() {
+        for key_code in KeyCode::iter() {
+            let rows = KEY_TABLE.iter().filter(|mapping| mapping.key_code == key_code).count();
+            assert!(rows <= 1, "{key_code} has {rows} rows");
+        }
+    }
+
+    #[test]
+    fn evdev_round_trip() {
+        assert!(duplicates(|mapping| mapping.evdev).is_empty());
+        for key_code in KeyCode::iter() {
+            if let Ok(code) = EvdevCode::try_from(key_code) {
+                assert_eq!(KeyCode::try_from(code).unwrap(), key_code);
+                assert_eq!(KeyCode::try_from(code.0).unwrap(), key_code);
+            }
+        }
+    }
+
+    #[test]
+    fn hid_round_trip() {
+        assert!(duplicates(|mapping| mapping.hid_usage).is_empty());
+        for key_code in KeyCode::iter() {
+            if let Ok(usage) = HidUsage::try_from(key_code) {
+                assert_eq!(KeyCode::try_from(usage).unwrap(), key_code);
+            }
+        }
+    }
+
+    #[test]
+    fn keysym_round_trip() {
+        // Only the listed aliases may share a keysym
+        assert_eq!(
+            duplicates(|mapping| mapping.keysym),
+            vec![
+                vec![KeyCode::KEY_AGAIN, KeyCode::KEY_REDO],
+                vec![KeyCode::KEY_CLOSECD, KeyCode::KEY_EJECTCD, KeyCode::KEY_EJECTCLOSECD],
+            ]
+        );
+        for key_code in KeyCode::iter() {
+            if let Ok(keysym) = Keysym::try_from(key_code) {
+                let found = KeyCode::try_from(keysym).unwrap();
+                if KEYSYM_ALIASES.contains(&key_code) {
+                    assert_ne!(found, key_code);
+                    assert_eq!(found.keysym(), Some(keysym.0));
+                } else {
+                    assert_eq!(found, key_code);
+                }
+            }
+        }
+        assert_eq!(KeyCode::from_keysym(0xff66), Some(KeyCode::KEY_REDO));
+        assert_eq!(KeyCode::from_keysym(0x1008ff2c), Some(KeyCode::KEY_EJECTCD));
+    }
+
+    #[test]
+    fn windows_vk_round_trip() {
+        // Only the listed aliases may share a VK
+        assert_eq!(duplicates(|mapping| mapping.windows_vk), vec![vec![KeyCode::KEY_ENTER, KeyCode::KEY_KPENTER]]);
+        for key_code in KeyCode::iter() {
+            if let Some(vk) = key_code.windows_vk() {
+                let found = KeyCode::from_windows_vk(vk).unwrap();
+                if WINDOWS_VK_ALIASES.contains(&key_code) {
+                    assert_ne!(found, key_code);
+                    assert_eq!(found.windows_vk(), Some(vk));
+                } else {
+                    assert_eq!(found, key_code);
+                }
+            }
+        }
+        assert_eq!(KeyCode::from_windows_vk(0x0d), Some(KeyCode::KEY_ENTER));
+        assert_eq!(KeyCode::from_windows_vk(0xff), None);
+    }
+
+    // Reverse lookups of the enum columns don't have an alias list
+    #[test]
+    fn backend_names_are_unique() {
+        assert!(duplicates(|mapping| mapping.tfc).is_empty());
+        assert!(duplicates(|mapping| mapping.hidg).is_empty());
+        assert!(duplicates(|mapping| mapping.qcode).is_empty());
+    }
+
+    #[test]
+    fn missing_codes_are_errors() {
+        assert!(KeyCode::try_from(EvdevCode(0x2ff)).is_err());
+        assert!(KeyCode::try_from(HidUsage(0xffff)).is_err());
+        assert!(KeyCode::try_from(Keysym(0)).is_err());
+    }
+}
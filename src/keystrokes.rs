@@ -0,0 +1,404 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use crate::{KeyCode, KeyCodes, Macro};
+
+// Syntax, items are separated by commas:
+//   ctrl+shift+t            chord: modifiers held, last key tapped
+//   100ms, 2s               delay
+//   "some text"             typed as is
+//   {ctrl down}c{ctrl up}   text with keys in braces, {key}, {key down}, {key up}, {ctrl+c}, {50ms}
+// Inside braced text "{{" and "}}" type literal braces
+
+const KEY_ALIASES: &[(&str, KeyCode)] = &[
+    ("ctrl", KeyCode::KEY_LEFTCTRL),
+    ("control", KeyCode::KEY_LEFTCTRL),
+    ("lctrl", KeyCode::KEY_LEFTCTRL),
+    ("rctrl", KeyCode::KEY_RIGHTCTRL),
+    ("shift", KeyCode::KEY_LEFTSHIFT),
+    ("lshift", KeyCode::KEY_LEFTSHIFT),
+    ("rshift", KeyCode::KEY_RIGHTSHIFT),
+    ("alt", KeyCode::KEY_LEFTALT),
+    ("lalt", KeyCode::KEY_LEFTALT),
+    ("ralt", KeyCode::KEY_RIGHTALT),
+    ("altgr", KeyCode::KEY_RIGHTALT),
+    ("super", KeyCode::KEY_LEFTMETA),
+    ("win", KeyCode::KEY_LEFTMETA),
+    ("meta", KeyCode::KEY_LEFTMETA),
+    ("cmd", KeyCode::KEY_LEFTMETA),
+    ("esc", KeyCode::KEY_ESC),
+    ("escape", KeyCode::KEY_ESC),
+    ("return", KeyCode::KEY_ENTER),
+    ("bs", KeyCode::KEY_BACKSPACE),
+    ("del", KeyCode::KEY_DELETE),
+    ("ins", KeyCode::KEY_INSERT),
+    ("pgup", KeyCode::KEY_PAGEUP),
+    ("pgdn", KeyCode::KEY_PAGEDOWN),
+    ("pgdown", KeyCode::KEY_PAGEDOWN),
+    ("caps", KeyCode::KEY_CAPSLOCK),
+    ("prtsc", KeyCode::KEY_SYSRQ),
+    ("printscreen", KeyCode::KEY_SYSRQ),
+    ("0", KeyCode::KEY_10),
+    ("comma", KeyCode::KEY_COMMA),
+    ("plus", KeyCode::KEY_EQUAL),
+    ("period", KeyCode::KEY_DOT),
+    ("lmb", KeyCode::MOUSE_LEFT),
+    ("rmb", KeyCode::MOUSE_RIGHT),
+    ("mmb", KeyCode::MOUSE_MIDDLE),
+];
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct ParseError {
+    // Byte offset into the parsed string
+    pub position: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    fn new(position: usize, message: impl Into<String>) -> Self {
+        Self {
+            position,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl KeyCode {
+    // Case-insensitive: aliases, full names (KEY_A), names without prefix (a, f5, pageup, left)
+    // or a single character that is typed without shift
+    pub fn from_name(name: &str) -> Option<KeyCode> {
+        let lower = name.to_ascii_lowercase();
+        if let Some((_, key_code)) = KEY_ALIASES.iter().find(|(alias, _)| *alias == lower) {
+            return Some(*key_code);
+        }
+
+        let upper = name.to_ascii_uppercase();
+        for candidate in [upper.clone(), format!("KEY_{upper}"), format!("MOUSE_{upper}")] {
+            if let Ok(key_code) = KeyCode::from_str(&candidate) {
+                return Some(key_code);
+            }
+        }
+
+        let mut chars = name.chars();
+        match (chars.next(), chars.next()) {
+            (Some(ch), None) => match KeyCode::from_char(ch) {
+                Some((key_code, false)) => Some(key_code),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+fn trimmed_start(text: &str, offset: usize) -> usize {
+    offset + text.len() - text.trim_start().len()
+}
+
+fn parse_delay(item: &str) -> Option<u64> {
+    let is_number = |number: &str| !number.is_empty() && number.bytes().all(|byte| byte.is_ascii_digit());
+
+    if let Some(number) = item.strip_suffix("ms") {
+        let number = number.trim_end();
+        return is_number(number).then(|| number.parse().ok()).flatten();
+    }
+    if let Some(number) = item.strip_suffix('s') {
+        let number = number.trim_end();
+        return is_number(number)
+            .then(|| number.parse::<u64>().ok())
+            .flatten()
+            .and_then(|seconds| seconds.checked_mul(1000));
+    }
+    None
+}
+
+// Digits with a unit that parse_delay rejected, e.g. "1.5s" or a number that doesn't fit
+fn check_delay(item: &str, position: usize) -> Result<(), ParseError> {
+    if item.starts_with(|ch: char| ch.is_ascii_digit()) && item.ends_with('s') {
+        return Err(ParseError::new(position, format!("Invalid delay '{item}'")));
+    }
+    Ok(())
+}
+
+fn parse_key_at(name: &str, offset: usize) -> Result<KeyCode, ParseError> {
+    let position = trimmed_start(name, offset);
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(ParseError::new(position, "Expected key name"));
+    }
+    KeyCode::from_name(name).ok_or_else(|| ParseError::new(position, format!("Unknown key name '{name}'")))
+}
+
+fn parse_chord_at(text: &str, offset: usize) -> Result<KeyCodes, ParseError> {
+    let mut key_codes = vec![];
+    let mut part_offset = offset;
+    for part in text.split('+') {
+        key_codes.push(parse_key_at(part, part_offset)?);
+        part_offset += part.len() + 1;
+    }
+    Ok(key_codes)
+}
+
+fn check_typeable(text: &str, offset: usize) -> Result<(), ParseError> {
+    match text.char_indices().find(|(_, ch)| KeyCode::from_char(*ch).is_none()) {
+        Some((index, ch)) => Err(ParseError::new(offset + index, format!("Can't type character {ch:?}"))),
+        None => Ok(()),
+    }
+}
+
+// Splits on top-level commas, returns items with their offsets
+fn split_items(input: &str) -> Result<Vec<(usize, &str)>, ParseError> {
+    let bytes = input.as_bytes();
+    let mut items = vec![];
+    let mut item_start = 0;
+    let mut open_brace = None;
+    let mut open_quote = None;
+
+    let mut index = 0;
+    while index < bytes.len() {
+        match (bytes[index], open_brace, open_quote) {
+            (b'"', None, None) => open_quote = Some(index),
+            (b'"', None, Some(_)) => open_quote = None,
+            (_, None, Some(_)) => {}
+            (b'{', None, None) if bytes.get(index + 1) == Some(&b'{') => index += 1,
+            (b'{', None, None) => open_brace = Some(index),
+            (b'{', Some(_), _) => return Err(ParseError::new(index, "Unexpected '{' inside braces")),
+            (b'}', Some(_), _) => open_brace = None,
+            (b'}', None, None) if bytes.get(index + 1) == Some(&b'}') => index += 1,
+            (b'}', None, None) => return Err(ParseError::new(index, "Unmatched '}'")),
+            (b',', None, None) => {
+                items.push((item_start, &input[item_start..index]));
+                item_start = index + 1;
+            }
+            _ => {}
+        }
+        index += 1;
+    }
+
+    if let Some(position) = open_brace {
+        return Err(ParseError::new(position, "Unclosed '{'"));
+    }
+    if let Some(position) = open_quote {
+        return Err(ParseError::new(position, "Unclosed quote"));
+    }
+
+    items.push((item_start, &input[item_start..]));
+    Ok(items)
+}
+
+fn parse_group(content: &str, offset: usize, input_macro: &mut Macro) -> Result<(), ParseError> {
+    let trimmed = content.trim();
+    if let Some(ms) = parse_delay(trimmed) {
+        input_macro.delay(ms);
+        return Ok(());
+    }
+
+    match trimmed.rsplit_once(char::is_whitespace) {
+        Some((name, action @ ("down" | "up"))) => {
+            let key_code = parse_key_at(name, trimmed_start(content, offset))?;
+            match action {
+                "down" => input_macro.press(key_code),
+                _ => input_macro.release(key_code),
+            };
+        }
+        _ => {
+            check_delay(trimmed, trimmed_start(content, offset))?;
+            let key_codes = parse_chord_at(content, offset)?;
+            input_macro.chord(&key_codes);
+        }
+    }
+    Ok(())
+}
+
+fn parse_braced_text(item: &str, offset: usize, input_macro: &mut Macro) -> Result<(), ParseError> {
+    let mut text = String::new();
+    let mut chars = item.char_indices().peekable();
+
+    while let Some((index, ch)) = chars.next() {
+        match ch {
+            '{' if chars.peek().is_some_and(|(_, next)| *next == '{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek().is_some_and(|(_, next)| *next == '}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                // Closing brace is guaranteed by split_items
+                let end = index + item[index..].find('}').unwrap_or(item.len() - index);
+                if !text.is_empty() {
+                    input_macro.type_text(&text);
+                    text.clear();
+                }
+                parse_group(&item[index + 1..end], offset + index + 1, input_macro)?;
+                while chars.next_if(|(next_index, _)| *next_index <= end).is_some() {}
+            }
+            _ => {
+                check_typeable(&item[index..index + ch.len_utf8()], offset + index)?;
+                text.push(ch);
+            }
+        }
+    }
+
+    if !text.is_empty() {
+        input_macro.type_text(&text);
+    }
+    Ok(())
+}
+
+// "ctrl+shift+t" into [KEY_LEFTCTRL, KEY_LEFTSHIFT, KEY_T]
+pub fn parse_chord(input: &str) -> Result<KeyCodes, ParseError> {
+    parse_chord_at(input, 0)
+}
+
+pub fn parse_macro(input: &str) -> Result<Macro, ParseError> {
+    let mut input_macro = Macro::new();
+    if input.trim().is_empty() {
+        return Ok(input_macro);
+    }
+
+    for (offset, item) in split_items(input)? {
+        let position = trimmed_start(item, offset);
+        let item = item.trim();
+
+        if item.is_empty() {
+            return Err(ParseError::new(position, "Expected key, delay or text"));
+        }
+
+        if let Some(ms) = parse_delay(item) {
+            input_macro.delay(ms);
+        } else if let Some(text) = item.strip_prefix('"') {
+            let close = text.find('"').unwrap_or(text.len());
+            if close + 1 != text.len() {
+                return Err(ParseError::new(position + close + 2, "Expected ',' after quoted text"));
+            }
+            let text = &text[..close];
+            check_typeable(text, position + 1)?;
+            input_macro.type_text(text);
+        } else if item.contains(['{', '}']) {
+            parse_braced_text(item, position, &mut input_macro)?;
+        } else {
+            check_delay(item, position)?;
+            let key_codes = parse_chord_at(item, position)?;
+            input_macro.chord(&key_codes);
+        }
+    }
+
+    Ok(input_macro)
+}
+
+impl FromStr for Macro {
+    type Err = ParseError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        parse_macro(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::MacroStep::*;
+    use super::*;
+
+    fn steps(input: &str) -> Vec<crate::MacroStep> {
+        parse_macro(input).unwrap().steps
+    }
+
+    fn error_position(input: &str) -> usize {
+        parse_macro(input).unwrap_err().position
+    }
+
+    #[test]
+    fn chords() {
+        assert_eq!(parse_chord("ctrl+shift+t").unwrap(), [KeyCode::KEY_LEFTCTRL, KeyCode::KEY_LEFTSHIFT, KeyCode::KEY_T]);
+        assert_eq!(steps("ctrl+shift+t"), [
+            Press(KeyCode::KEY_LEFTCTRL),
+            Press(KeyCode::KEY_LEFTSHIFT),
+            Tap(KeyCode::KEY_T),
+            Release(KeyCode::KEY_LEFTSHIFT),
+            Release(KeyCode::KEY_LEFTCTRL),
+        ]);
+    }
+
+    #[test]
+    fn held_keys_in_braces() {
+        assert_eq!(steps("{ctrl down}c{ctrl up}"), [
+            Press(KeyCode::KEY_LEFTCTRL),
+            TypeText("c".to_string()),
+            Release(KeyCode::KEY_LEFTCTRL),
+        ]);
+        assert_eq!(steps("a{50ms}{{b}}{ctrl+v}"), [
+            TypeText("a".to_string()),
+            Delay(50),
+            TypeText("{b}".to_string()),
+            Press(KeyCode::KEY_LEFTCTRL),
+            Tap(KeyCode::KEY_V),
+            Release(KeyCode::KEY_LEFTCTRL),
+        ]);
+    }
+
+    #[test]
+    fn items_and_delays() {
+        assert_eq!(steps("alt+tab, 100ms, enter"), [
+            Press(KeyCode::KEY_LEFTALT),
+            Tap(KeyCode::KEY_TAB),
+            Release(KeyCode::KEY_LEFTALT),
+            Delay(100),
+            Tap(KeyCode::KEY_ENTER),
+        ]);
+        assert_eq!(steps("2s,5 ms"), [Delay(2000), Delay(5)]);
+        assert!(steps("  ").is_empty());
+    }
+
+    #[test]
+    fn aliases_and_names() {
+        assert_eq!(KeyCode::from_name("esc"), Some(KeyCode::KEY_ESC));
+        assert_eq!(KeyCode::from_name("PgUp"), Some(KeyCode::KEY_PAGEUP));
+        assert_eq!(KeyCode::from_name("lmb"), Some(KeyCode::MOUSE_LEFT));
+        assert_eq!(KeyCode::from_name("F5"), Some(KeyCode::KEY_F5));
+        assert_eq!(KeyCode::from_name("KEY_A"), Some(KeyCode::KEY_A));
+        assert_eq!(KeyCode::from_name("/"), Some(KeyCode::KEY_SLASH));
+        // Shifted characters aren't key names
+        assert_eq!(KeyCode::from_name("?"), None);
+        assert_eq!(steps("esc, pgup, lmb"), [Tap(KeyCode::KEY_ESC), Tap(KeyCode::KEY_PAGEUP), Tap(KeyCode::MOUSE_LEFT)]);
+    }
+
+    #[test]
+    fn quoted_text() {
+        assert_eq!(steps(r#""Hello, {world}!", enter"#), [
+            TypeText("Hello, {world}!".to_string()),
+            Tap(KeyCode::KEY_ENTER),
+        ]);
+        assert_eq!(steps(r#""""#), [TypeText(String::new())]);
+    }
+
+    #[test]
+    fn error_positions() {
+        // Unknown key
+        assert_eq!(error_position("ctrl+foo"), 5);
+        assert_eq!(error_position("alt+tab, 100ms,  bogus"), 17);
+        assert_eq!(error_position("x{ctrl+nope}"), 7);
+        assert_eq!(parse_macro("ctrl+foo").unwrap_err().to_string(), "Unknown key name 'foo' at position 5");
+        // Unclosed brace, unclosed quote
+        assert_eq!(error_position("a, {ctrl down"), 3);
+        assert_eq!(error_position(r#"a, "text"#), 3);
+        // Bad delay
+        assert_eq!(error_position("a, 1.5s"), 3);
+        assert_eq!(error_position("99999999999999999999ms"), 0);
+        assert_eq!(error_position("x{ 10xs}"), 3);
+        // Trailing '+'
+        assert_eq!(error_position("a, ctrl+shift+"), 14);
+        assert_eq!(parse_chord("ctrl+").unwrap_err(), ParseError::new(5, "Expected key name"));
+        // Other
+        assert_eq!(error_position("a,,b"), 2);
+        assert_eq!(error_position(r#""ab"c"#), 4);
+        assert_eq!(error_position("\"é\""), 1);
+    }
+}
@@ -1,14 +1,62 @@
 pub mod key_codes;
+mod key_table;
 mod utils;
+mod batch;
 mod spec_mki;
 mod spec_tfc;
 mod stubs;
 mod spec_enigo;
 mod spec_hidg;
+mod spec_uinput;
+pub mod uinput;
+mod spec_uhid;
+pub mod uhid;
+mod spec_serial_hid;
+pub mod serial_hid;
+mod spec_rfb;
+pub mod rfb;
+mod spec_qmp;
+pub mod qmp;
+mod spec_wayland;
+pub mod wayland;
+mod spec_libei;
+pub mod libei;
+mod spec_xtest;
+pub mod xtest;
+mod gamepad;
+mod touch;
+mod pen;
+mod input_macro;
+mod keystrokes;
+mod record;
+mod input_capture;
+mod hid_report;
+mod shared;
+mod scheduler;
+#[cfg(feature = "remote")]
+mod remote;
+#[cfg(feature = "async")]
+mod async_emulator;
 
 pub type OS_Input_Coord = i32;
 
 pub use key_codes::{KeyCode, KeyCodes};
+pub use key_table::{coverage_report, key_coverage, BackendCoverage, EvdevCode, HidUsage, KeyMapping, Keysym, KEYSYM_ALIASES, KEY_TABLE, WINDOWS_VK_ALIASES};
+pub use batch::{InputBatch, InputEvent};
+pub use gamepad::*;
+pub use touch::*;
+pub use pen::*;
+pub use input_macro::{Macro, MacroCancel, MacroStatus, MacroStep};
+pub use keystrokes::{parse_chord, parse_macro, ParseError};
+pub use record::{EvdevReader, RawInputEvent, RecordedEvent, Recording, INPUT_EVENT_SIZE};
+pub use input_capture::*;
+pub use hid_report::*;
+pub use shared::SharedInputEmulator;
+pub use scheduler::{OutputScheduler, DEFAULT_OUTPUT_RATE};
+#[cfg(feature = "remote")]
+pub use remote::*;
+#[cfg(feature = "async")]
+pub use async_emulator::*;
 pub use crate::stubs::*;
 
 #[cfg(feature = "use_mki")]
@@ -23,6 +71,30 @@ pub use crate::spec_enigo::*;
 #[cfg(feature = "use_hidg")]
 pub use crate::spec_hidg::*;
 
+#[cfg(feature = "use_uinput")]
+pub use crate::spec_uinput::*;
+
+#[cfg(feature = "use_uhid")]
+pub use crate::spec_uhid::*;
+
+#[cfg(feature = "use_serial_hid")]
+pub use crate::spec_serial_hid::*;
+
+#[cfg(feature = "use_rfb")]
+pub use crate::spec_rfb::*;
+
+#[cfg(feature = "use_qmp")]
+pub use crate::spec_qmp::*;
+
+#[cfg(feature = "use_wayland")]
+pub use crate::spec_wayland::*;
+
+#[cfg(feature = "use_libei")]
+pub use crate::spec_libei::*;
+
+#[cfg(feature = "use_xtest")]
+pub use crate::spec_xtest::*;
+
 
 // pub fn add(left: usize, right: usize) -> usize {
 //     left + right
@@ -0,0 +1,759 @@
+use color_eyre::eyre::bail;
+use color_eyre::Result;
+
+#[cfg(feature = "libei")]
+use std::collections::HashMap;
+#[cfg(feature = "libei")]
+use std::io::{ErrorKind, Read, Write};
+#[cfg(feature = "libei")]
+use std::os::unix::net::UnixStream;
+#[cfg(feature = "libei")]
+use std::path::PathBuf;
+#[cfg(feature = "libei")]
+use std::time::Duration;
+#[cfg(feature = "libei")]
+use color_eyre::eyre::eyre;
+#[cfg(feature = "libei")]
+use crate::exec_or_eyre;
+
+// Every message starts with the object id (u64), the total length including this header (u32)
+// and the opcode (u32), in native byte order. Arguments are padded to 4 bytes.
+pub const EI_HEADER_SIZE: usize = 16;
+// Messages that claim to be longer than this are a broken stream
+const MAX_MESSAGE_SIZE: usize = 1 << 16;
+
+pub const EI_HANDSHAKE_ID: u64 = 0;
+// Objects created by the server have ids from here on
+pub const EI_SERVER_ID_START: u64 = 0xff00_0000_0000_0000;
+
+// ei_handshake.context_type
+pub const EI_CONTEXT_SENDER: u32 = 2;
+
+// Logical wheel click in ei_scroll.scroll_discrete units
+pub const EI_SCROLL_DISCRETE_STEP: i32 = 120;
+
+// Interfaces the sender uses, all at version 1
+pub const EI_INTERFACES: &[&str] = &[
+    "ei_handshake",
+    "ei_connection",
+    "ei_callback",
+    "ei_pingpong",
+    "ei_seat",
+    "ei_device",
+    "ei_pointer",
+    "ei_button",
+    "ei_scroll",
+    "ei_keyboard",
+];
+// Device interfaces requested from the seat
+pub const EI_DEVICE_INTERFACES: &[&str] = &["ei_pointer", "ei_button", "ei_scroll", "ei_keyboard"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EiMessage {
+    pub object_id: u64,
+    pub opcode: u32,
+    pub arguments: Vec<u8>,
+}
+
+impl EiMessage {
+    pub fn new(object_id: u64, opcode: u32) -> Self {
+        Self {
+            object_id,
+            opcode,
+            arguments: vec![],
+        }
+    }
+
+    pub fn uint32(mut self, value: u32) -> Self {
+        self.arguments.extend_from_slice(&value.to_ne_bytes());
+        self
+    }
+
+    pub fn int32(mut self, value: i32) -> Self {
+        self.arguments.extend_from_slice(&value.to_ne_bytes());
+        self
+    }
+
+    // Also new_id and object arguments
+    pub fn uint64(mut self, value: u64) -> Self {
+        self.arguments.extend_from_slice(&value.to_ne_bytes());
+        self
+    }
+
+    pub fn float(mut self, value: f32) -> Self {
+        self.arguments.extend_from_slice(&value.to_ne_bytes());
+        self
+    }
+
+    // Length includes the terminating NUL, the bytes are padded to 4
+    pub fn string(mut self, value: &str) -> Self {
+        let length = value.len() + 1;
+        self.arguments.extend_from_slice(&(length as u32).to_ne_bytes());
+        self.arguments.extend_from_slice(value.as_bytes());
+        self.arguments.resize(self.arguments.len() + length.next_multiple_of(4) - value.len(), 0);
+        self
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let length = EI_HEADER_SIZE + self.arguments.len();
+        let mut bytes = Vec::with_capacity(length);
+        bytes.extend_from_slice(&self.object_id.to_ne_bytes());
+        bytes.extend_from_slice(&(length as u32).to_ne_bytes());
+        bytes.extend_from_slice(&self.opcode.to_ne_bytes());
+        bytes.extend_from_slice(&self.arguments);
+        bytes
+    }
+
+    // None until the whole message is buffered, otherwise the message and its length
+    pub fn decode(bytes: &[u8]) -> Result<Option<(Self, usize)>> {
+        if bytes.len() < EI_HEADER_SIZE {
+            return Ok(None);
+        }
+        let object_id = u64::from_ne_bytes(bytes[0..8].try_into().unwrap());
+        let length = u32::from_ne_bytes(bytes[8..12].try_into().unwrap()) as usize;
+        let opcode = u32::from_ne_bytes(bytes[12..16].try_into().unwrap());
+        if !(EI_HEADER_SIZE..=MAX_MESSAGE_SIZE).contains(&length) || !length.is_multiple_of(4) {
+            bail!("Invalid EI message length {length}");
+        }
+        if bytes.len() < length {
+            return Ok(None);
+        }
+        let message = Self {
+            object_id,
+            opcode,
+            arguments: bytes[EI_HEADER_SIZE..length].to_vec(),
+        };
+        Ok(Some((message, length)))
+    }
+
+    pub fn reader(&self) -> EiArguments<'_> {
+        EiArguments {
+            bytes: &self.arguments,
+            offset: 0,
+        }
+    }
+}
+
+pub struct EiArguments<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl EiArguments<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let Some(bytes) = self.bytes.get(self.offset..self.offset + N) else {
+            bail!("EI message is missing arguments");
+        };
+        self.offset += N;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    pub fn uint32(&mut self) -> Result<u32> {
+        Ok(u32::from_ne_bytes(self.take()?))
+    }
+
+    pub fn int32(&mut self) -> Result<i32> {
+        Ok(i32::from_ne_bytes(self.take()?))
+    }
+
+    pub fn uint64(&mut self) -> Result<u64> {
+        Ok(u64::from_ne_bytes(self.take()?))
+    }
+
+    pub fn float(&mut self) -> Result<f32> {
+        Ok(f32::from_ne_bytes(self.take()?))
+    }
+
+    // Null strings are read as empty
+    pub fn string(&mut self) -> Result<String> {
+        let length = self.uint32()? as usize;
+        if length == 0 {
+            return Ok(String::new());
+        }
+        let padded = length.next_multiple_of(4);
+        let Some(bytes) = self.bytes.get(self.offset..self.offset + padded) else {
+            bail!("EI string runs past the end of the message");
+        };
+        self.offset += padded;
+        Ok(String::from_utf8_lossy(&bytes[..length - 1]).into_owned())
+    }
+}
+
+#[cfg(feature = "libei")]
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[cfg(feature = "libei")]
+#[derive(Clone, Debug)]
+enum EiObject {
+    Connection,
+    Callback,
+    Seat,
+    Device,
+    DeviceInterface { device: u64, name: String },
+}
+
+#[cfg(feature = "libei")]
+#[derive(Clone, Debug, Default)]
+struct EiDevice {
+    interfaces: Vec<(String, u64)>,
+    done: bool,
+    resumed: bool,
+    emulating: bool,
+    pending_frame: bool,
+}
+
+// Sender context: the client emulates input on devices the EIS implementation hands out.
+// The socket comes from $LIBEI_SOCKET or from the RemoteDesktop portal's ConnectToEIS.
+#[cfg(feature = "libei")]
+pub struct EiSender {
+    stream: UnixStream,
+    buffer: Vec<u8>,
+    objects: HashMap<u64, EiObject>,
+    devices: HashMap<u64, EiDevice>,
+    // Capabilities of the seats: interface name to mask bit
+    capabilities: Vec<(String, u64)>,
+    connection_id: Option<u64>,
+    next_id: u64,
+    last_serial: u32,
+    sequence: u32,
+}
+
+#[cfg(feature = "libei")]
+impl EiSender {
+    // Relative paths are in $XDG_RUNTIME_DIR, like libei does
+    pub fn connect() -> Result<Self> {
+        let Some(socket) = std::env::var_os("LIBEI_SOCKET") else {
+            bail!("LIBEI_SOCKET is not set");
+        };
+        let mut path = PathBuf::from(socket);
+        if path.is_relative() {
+            let Some(runtime_dir) = std::env::var_os("XDG_RUNTIME_DIR") else {
+                bail!("XDG_RUNTIME_DIR is not set");
+            };
+            path = PathBuf::from(runtime_dir).join(path);
+        }
+        Self::from_stream(exec_or_eyre!(UnixStream::connect(path))?)
+    }
+
+    // Runs the handshake and waits until the offered device interfaces are resumed
+    pub fn from_stream(stream: UnixStream) -> Result<Self> {
+        exec_or_eyre!(stream.set_read_timeout(Some(CONNECT_TIMEOUT)))?;
+        let mut sender = Self {
+            stream,
+            buffer: vec![],
+            objects: HashMap::new(),
+            devices: HashMap::new(),
+            capabilities: vec![],
+            connection_id: None,
+            next_id: 1,
+            last_serial: 0,
+            sequence: 0,
+        };
+
+        let version = sender.read_message()?;
+        if version.object_id != EI_HANDSHAKE_ID || version.opcode != 0 {
+            bail!("Expected ei_handshake.handshake_version, got {version:?}");
+        }
+        sender.send(EiMessage::new(EI_HANDSHAKE_ID, 0).uint32(1))?;
+        sender.send(EiMessage::new(EI_HANDSHAKE_ID, 2).uint32(EI_CONTEXT_SENDER))?;
+        sender.send(EiMessage::new(EI_HANDSHAKE_ID, 3).string("universal_input"))?;
+        for interface in EI_INTERFACES {
+            sender.send(EiMessage::new(EI_HANDSHAKE_ID, 4).string(interface).uint32(1))?;
+        }
+        sender.send(EiMessage::new(EI_HANDSHAKE_ID, 1))?;
+
+        while !sender.is_ready() {
+            let message = sender.read_message()?;
+            sender.handle(message)?;
+        }
+        exec_or_eyre!(sender.stream.set_read_timeout(None))?;
+        Ok(sender)
+    }
+
+    fn is_ready(&self) -> bool {
+        if self.connection_id.is_none() || self.capabilities.is_empty() {
+            return false;
+        }
+        EI_DEVICE_INTERFACES
+            .iter()
+            .filter(|name| self.capabilities.iter().any(|(interface, _)| interface == *name))
+            .all(|name| self.find_interface(name).is_some())
+    }
+
+    fn send(&mut self, message: EiMessage) -> Result<()> {
+        exec_or_eyre!(self.stream.write_all(&message.encode()))
+    }
+
+    fn take_message(&mut self) -> Result<Option<EiMessage>> {
+        Ok(match EiMessage::decode(&self.buffer)? {
+            Some((message, length)) => {
+                self.buffer.drain(..length);
+                Some(message)
+            }
+            None => None,
+        })
+    }
+
+    fn read_message(&mut self) -> Result<EiMessage> {
+        let mut chunk = [0u8; 4096];
+        loop {
+            if let Some(message) = self.take_message()? {
+                return Ok(message);
+            }
+            let read = match self.stream.read(&mut chunk) {
+                Ok(read) => read,
+                Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    bail!("Timed out waiting for the EIS server")
+                }
+                Err(err) => return exec_or_eyre!(Err(err)),
+            };
+            if read == 0 {
+                bail!("EIS server closed the connection");
+            }
+            self.buffer.extend_from_slice(&chunk[..read]);
+        }
+    }
+
+    // Handles whatever the server sent without blocking: pings, pauses, removed devices
+    pub fn dispatch(&mut self) -> Result<()> {
+        exec_or_eyre!(self.stream.set_nonblocking(true))?;
+        let mut chunk = [0u8; 4096];
+        let result = loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => break Err(eyre!("EIS server closed the connection")),
+                Ok(read) => self.buffer.extend_from_slice(&chunk[..read]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break Ok(()),
+                Err(err) => break exec_or_eyre!(Err(err)),
+            }
+        };
+        exec_or_eyre!(self.stream.set_nonblocking(false))?;
+        result?;
+        while let Some(message) = self.take_message()? {
+            self.handle(message)?;
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, message: EiMessage) -> Result<()> {
+        let mut arguments = message.reader();
+        if message.object_id == EI_HANDSHAKE_ID {
+            // connection(serial, connection, version), interface_version replies are ignored
+            if message.opcode == 2 {
+                self.last_serial = arguments.uint32()?;
+                let connection_id = arguments.uint64()?;
+                self.connection_id = Some(connection_id);
+                self.objects.insert(connection_id, EiObject::Connection);
+            }
+            return Ok(());
+        }
+        // Events can still arrive for objects that were just released
+        let Some(object) = self.objects.get(&message.object_id).cloned() else {
+            return Ok(());
+        };
+        match (object, message.opcode) {
+            (EiObject::Connection, 0) => {
+                let _last_serial = arguments.uint32()?;
+                let _reason = arguments.uint32()?;
+                bail!("Disconnected by the EIS server: {}", arguments.string()?);
+            }
+            (EiObject::Connection, 1) => {
+                self.objects.insert(arguments.uint64()?, EiObject::Seat);
+            }
+            (EiObject::Connection, 2) => {
+                let _last_serial = arguments.uint32()?;
+                bail!("EIS server reports invalid object {}", arguments.uint64()?);
+            }
+            (EiObject::Connection, 3) => {
+                let ping = arguments.uint64()?;
+                self.send(EiMessage::new(ping, 0).uint64(0))?;
+            }
+            (EiObject::Seat, 0) => {
+                self.last_serial = arguments.uint32()?;
+                self.objects.remove(&message.object_id);
+            }
+            (EiObject::Seat, 2) => {
+                let mask = arguments.uint64()?;
+                let interface = arguments.string()?;
+                self.capabilities.push((interface, mask));
+            }
+            (EiObject::Seat, 3) => {
+                let capabilities = self
+                    .capabilities
+                    .iter()
+                    .filter(|(interface, _)| EI_DEVICE_INTERFACES.contains(&interface.as_str()))
+                    .fold(0, |mask, (_, bit)| mask | bit);
+                self.send(EiMessage::new(message.object_id, 1).uint64(capabilities))?;
+            }
+            (EiObject::Seat, 4) => {
+                let device = arguments.uint64()?;
+                self.objects.insert(device, EiObject::Device);
+                self.devices.insert(device, EiDevice::default());
+            }
+            (EiObject::Device, opcode) => self.handle_device(message.object_id, opcode, &mut arguments)?,
+            (EiObject::DeviceInterface { device, name }, 0) => {
+                self.last_serial = arguments.uint32()?;
+                self.objects.remove(&message.object_id);
+                if let Some(device) = self.devices.get_mut(&device) {
+                    device.interfaces.retain(|(interface, _)| *interface != name);
+                }
+            }
+            (EiObject::Callback, 0) => {
+                self.objects.remove(&message.object_id);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn handle_device(&mut self, device_id: u64, opcode: u32, arguments: &mut EiArguments) -> Result<()> {
+        match opcode {
+            // destroyed
+            0 => {
+                self.last_serial = arguments.uint32()?;
+                self.objects.remove(&device_id);
+                self.objects.retain(|_, object| !matches!(object, EiObject::DeviceInterface { device, .. } if *device == device_id));
+                self.devices.remove(&device_id);
+            }
+            // interface
+            5 => {
+                let object_id = arguments.uint64()?;
+                let name = arguments.string()?;
+                self.objects.insert(object_id, EiObject::DeviceInterface { device: device_id, name: name.clone() });
+                if let Some(device) = self.devices.get_mut(&device_id) {
+                    device.interfaces.push((name, object_id));
+                }
+            }
+            // done, resumed, paused
+            6..=8 => {
+                if opcode != 6 {
+                    self.last_serial = arguments.uint32()?;
+                }
+                if let Some(device) = self.devices.get_mut(&device_id) {
+                    match opcode {
+                        6 => device.done = true,
+                        7 => device.resumed = true,
+                        _ => {
+                            device.resumed = false;
+                            device.emulating = false;
+                            device.pending_frame = false;
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // Device and interface object of the first resumed device that has the interface
+    fn find_interface(&self, name: &str) -> Option<(u64, u64)> {
+        self.devices
+            .iter()
+            .filter(|(_, device)| device.done && device.resumed)
+            .find_map(|(device_id, device)| {
+                device
+                    .interfaces
+                    .iter()
+                    .find(|(interface, _)| interface == name)
+                    .map(|(_, object_id)| (*device_id, *object_id))
+            })
+    }
+
+    // Starts emulating on the device if needed and returns the interface object
+    fn prepare(&mut self, name: &str) -> Result<u64> {
+        self.dispatch()?;
+        let Some((device_id, object_id)) = self.find_interface(name) else {
+            bail!("No resumed EIS device with {name}");
+        };
+        let device = self.devices.get_mut(&device_id).unwrap();
+        device.pending_frame = true;
+        if !device.emulating {
+            device.emulating = true;
+            self.sequence = self.sequence.wrapping_add(1);
+            let message = EiMessage::new(device_id, 1).uint32(self.last_serial).uint32(self.sequence);
+            self.send(message)?;
+        }
+        Ok(object_id)
+    }
+
+    #[inline]
+    pub fn key(&mut self, evdev: u16, down: bool) -> Result<()> {
+        let keyboard = self.prepare("ei_keyboard")?;
+        self.send(EiMessage::new(keyboard, 1).uint32(evdev as u32).uint32(down as u32))
+    }
+
+    // Logical pixels, y grows downwards
+    #[inline]
+    pub fn motion(&mut self, dx: f32, dy: f32) -> Result<()> {
+        let pointer = self.prepare("ei_pointer")?;
+        self.send(EiMessage::new(pointer, 1).float(dx).float(dy))
+    }
+
+    #[inline]
+    pub fn button(&mut self, evdev: u16, down: bool) -> Result<()> {
+        let button = self.prepare("ei_button")?;
+        self.send(EiMessage::new(button, 1).uint32(evdev as u32).uint32(down as u32))
+    }
+
+    // Wheel clicks, positive is right and down
+    #[inline]
+    pub fn scroll(&mut self, x: i32, y: i32) -> Result<()> {
+        let scroll = self.prepare("ei_scroll")?;
+        let message = EiMessage::new(scroll, 2)
+            .int32(x.saturating_mul(EI_SCROLL_DISCRETE_STEP))
+            .int32(y.saturating_mul(EI_SCROLL_DISCRETE_STEP));
+        self.send(message)
+    }
+
+    // Ends the frame on every device that got events since the last one
+    pub fn frame(&mut self) -> Result<()> {
+        let mut timespec = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+        unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut timespec) };
+        let timestamp = timespec.tv_sec as u64 * 1_000_000 + timespec.tv_nsec as u64 / 1_000;
+
+        let devices: Vec<u64> = self
+            .devices
+            .iter_mut()
+            .filter_map(|(device_id, device)| std::mem::take(&mut device.pending_frame).then_some(*device_id))
+            .collect();
+        for device_id in devices {
+            self.send(EiMessage::new(device_id, 3).uint32(self.last_serial).uint64(timestamp))?;
+        }
+        Ok(())
+    }
+
+    // Round trip through ei_connection.sync
+    pub fn sync(&mut self) -> Result<()> {
+        let Some(connection_id) = self.connection_id else {
+            bail!("EI connection isn't established");
+        };
+        let callback = self.next_id;
+        self.next_id += 1;
+        self.objects.insert(callback, EiObject::Callback);
+        self.send(EiMessage::new(connection_id, 0).uint64(callback).uint32(1))?;
+        exec_or_eyre!(self.stream.set_read_timeout(Some(CONNECT_TIMEOUT)))?;
+        while self.objects.contains_key(&callback) {
+            let message = self.read_message()?;
+            self.handle(message)?;
+        }
+        exec_or_eyre!(self.stream.set_read_timeout(None))
+    }
+}
+
+#[cfg(feature = "libei")]
+impl Drop for EiSender {
+    fn drop(&mut self) {
+        let emulating: Vec<u64> = self
+            .devices
+            .iter()
+            .filter(|(_, device)| device.emulating)
+            .map(|(device_id, _)| *device_id)
+            .collect();
+        for device_id in emulating {
+            let _ = self.send(EiMessage::new(device_id, 2).uint32(self.last_serial));
+        }
+        if let Some(connection_id) = self.connection_id {
+            let _ = self.send(EiMessage::new(connection_id, 1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_round_trip() {
+        let message = EiMessage::new(EI_SERVER_ID_START + 3, 5)
+            .uint32(7)
+            .int32(-2)
+            .uint64(u64::MAX)
+            .float(1.5)
+            .string("ei_pointer")
+            .string("");
+        let bytes = message.encode();
+        // 4 + 4 + 8 + 4, "ei_pointer\0" padded to 12 plus its length, "\0" padded to 4 plus its length
+        assert_eq!(bytes.len(), EI_HEADER_SIZE + 20 + 16 + 8);
+        assert_eq!(u32::from_ne_bytes(bytes[8..12].try_into().unwrap()) as usize, bytes.len());
+
+        let (decoded, length) = EiMessage::decode(&bytes).unwrap().unwrap();
+        assert_eq!((&decoded, length), (&message, bytes.len()));
+        let mut arguments = decoded.reader();
+        assert_eq!(arguments.uint32().unwrap(), 7);
+        assert_eq!(arguments.int32().unwrap(), -2);
+        assert_eq!(arguments.uint64().unwrap(), u64::MAX);
+        assert_eq!(arguments.float().unwrap(), 1.5);
+        assert_eq!(arguments.string().unwrap(), "ei_pointer");
+        assert_eq!(arguments.string().unwrap(), "");
+        assert!(arguments.uint32().is_err());
+    }
+
+    #[test]
+    fn decode_waits_for_whole_message() {
+        let mut bytes = EiMessage::new(1, 2).uint32(3).encode();
+        bytes.extend(EiMessage::new(4, 5).encode());
+        assert_eq!(EiMessage::decode(&bytes[..EI_HEADER_SIZE - 1]).unwrap(), None);
+        assert_eq!(EiMessage::decode(&bytes[..EI_HEADER_SIZE + 3]).unwrap(), None);
+
+        let (first, length) = EiMessage::decode(&bytes).unwrap().unwrap();
+        assert_eq!((first.object_id, first.opcode, length), (1, 2, EI_HEADER_SIZE + 4));
+        let (second, _) = EiMessage::decode(&bytes[length..]).unwrap().unwrap();
+        assert_eq!((second.object_id, second.opcode), (4, 5));
+    }
+
+    #[test]
+    fn decode_rejects_bad_lengths() {
+        let mut bytes = EiMessage::new(1, 2).uint32(3).encode();
+        for length in [8u32, 18, MAX_MESSAGE_SIZE as u32 + 4] {
+            bytes[8..12].copy_from_slice(&length.to_ne_bytes());
+            assert!(EiMessage::decode(&bytes).is_err());
+        }
+        // String claiming more bytes than the message has
+        let message = EiMessage::new(1, 2).uint32(64);
+        assert!(message.reader().string().is_err());
+    }
+}
+
+#[cfg(all(test, feature = "libei"))]
+mod sender_tests {
+    use std::thread;
+    use super::*;
+
+    const CONNECTION: u64 = EI_SERVER_ID_START;
+    const SEAT: u64 = EI_SERVER_ID_START + 1;
+    const DEVICE: u64 = EI_SERVER_ID_START + 2;
+    const POINTER: u64 = EI_SERVER_ID_START + 3;
+    const KEYBOARD: u64 = EI_SERVER_ID_START + 4;
+
+    // Server end of the socket pair
+    struct FakeEis {
+        stream: UnixStream,
+        buffer: Vec<u8>,
+    }
+
+    impl FakeEis {
+        fn send(&mut self, message: EiMessage) {
+            self.stream.write_all(&message.encode()).unwrap();
+        }
+
+        // None once the client hung up
+        fn receive(&mut self) -> Option<EiMessage> {
+            loop {
+                if let Some((message, length)) = EiMessage::decode(&self.buffer).unwrap() {
+                    self.buffer.drain(..length);
+                    return Some(message);
+                }
+                let mut chunk = [0u8; 4096];
+                let read = self.stream.read(&mut chunk).unwrap();
+                if read == 0 {
+                    return None;
+                }
+                self.buffer.extend_from_slice(&chunk[..read]);
+            }
+        }
+
+        fn receive_until(&mut self, object_id: u64, opcode: u32) -> Vec<EiMessage> {
+            let mut messages = vec![];
+            loop {
+                let message = self.receive().unwrap();
+                let last = (message.object_id, message.opcode) == (object_id, opcode);
+                messages.push(message);
+                if last {
+                    return messages;
+                }
+            }
+        }
+    }
+
+    // Handshake up to a resumed device with a pointer and a keyboard, then everything the client sends
+    fn serve(stream: UnixStream) -> (Vec<EiMessage>, Vec<EiMessage>) {
+        let mut eis = FakeEis { stream, buffer: vec![] };
+        eis.send(EiMessage::new(EI_HANDSHAKE_ID, 0).uint32(1));
+        let handshake = eis.receive_until(EI_HANDSHAKE_ID, 1);
+
+        eis.send(EiMessage::new(EI_HANDSHAKE_ID, 2).uint32(1).uint64(CONNECTION).uint32(1));
+        eis.send(EiMessage::new(CONNECTION, 3).uint64(EI_SERVER_ID_START + 100).uint32(1));
+        let pong = eis.receive().unwrap();
+        assert_eq!(pong, EiMessage::new(EI_SERVER_ID_START + 100, 0).uint64(0));
+
+        eis.send(EiMessage::new(CONNECTION, 1).uint64(SEAT).uint32(1));
+        eis.send(EiMessage::new(SEAT, 2).uint64(1).string("ei_pointer"));
+        eis.send(EiMessage::new(SEAT, 2).uint64(8).string("ei_keyboard"));
+        eis.send(EiMessage::new(SEAT, 2).uint64(16).string("ei_touchscreen"));
+        eis.send(EiMessage::new(SEAT, 3));
+        let bind = eis.receive().unwrap();
+        assert_eq!(bind, EiMessage::new(SEAT, 1).uint64(1 | 8));
+
+        eis.send(EiMessage::new(SEAT, 4).uint64(DEVICE).uint32(1));
+        eis.send(EiMessage::new(DEVICE, 5).uint64(POINTER).string("ei_pointer").uint32(1));
+        eis.send(EiMessage::new(DEVICE, 5).uint64(KEYBOARD).string("ei_keyboard").uint32(1));
+        eis.send(EiMessage::new(DEVICE, 6));
+        eis.send(EiMessage::new(DEVICE, 7).uint32(2));
+
+        let mut messages = vec![];
+        while let Some(message) = eis.receive() {
+            messages.push(message);
+        }
+        (handshake, messages)
+    }
+
+    #[test]
+    fn handshake_and_emulation() {
+        let (client, server) = UnixStream::pair().unwrap();
+        let handle = thread::spawn(move || serve(server));
+
+        let mut sender = EiSender::from_stream(client).unwrap();
+        sender.key(30, true).unwrap();
+        sender.motion(1.0, -2.0).unwrap();
+        sender.frame().unwrap();
+        drop(sender);
+
+        let (handshake, messages) = handle.join().unwrap();
+        let mut expected = vec![
+            EiMessage::new(EI_HANDSHAKE_ID, 0).uint32(1),
+            EiMessage::new(EI_HANDSHAKE_ID, 2).uint32(EI_CONTEXT_SENDER),
+            EiMessage::new(EI_HANDSHAKE_ID, 3).string("universal_input"),
+        ];
+        expected.extend(EI_INTERFACES.iter().map(|interface| EiMessage::new(EI_HANDSHAKE_ID, 4).string(interface).uint32(1)));
+        expected.push(EiMessage::new(EI_HANDSHAKE_ID, 1));
+        assert_eq!(handshake, expected);
+
+        assert_eq!(messages.len(), 6);
+        assert_eq!(messages[..3], [
+            EiMessage::new(DEVICE, 1).uint32(2).uint32(1),
+            EiMessage::new(KEYBOARD, 1).uint32(30).uint32(1),
+            EiMessage::new(POINTER, 1).float(1.0).float(-2.0),
+        ]);
+        // Frame carries the last serial and a timestamp
+        assert_eq!((messages[3].object_id, messages[3].opcode), (DEVICE, 3));
+        assert_eq!(messages[3].reader().uint32().unwrap(), 2);
+        assert_eq!(messages[4..], [EiMessage::new(DEVICE, 2).uint32(2), EiMessage::new(CONNECTION, 1)]);
+    }
+
+    #[test]
+    fn disconnect_during_handshake() {
+        let (client, server) = UnixStream::pair().unwrap();
+        let handle = thread::spawn(move || {
+            let mut eis = FakeEis { stream: server, buffer: vec![] };
+            eis.send(EiMessage::new(EI_HANDSHAKE_ID, 0).uint32(1));
+            eis.receive_until(EI_HANDSHAKE_ID, 1);
+            eis.send(EiMessage::new(EI_HANDSHAKE_ID, 2).uint32(1).uint64(CONNECTION).uint32(1));
+            eis.send(EiMessage::new(CONNECTION, 0).uint32(1).uint32(2).string("not allowed"));
+        });
+
+        let err = EiSender::from_stream(client).err().unwrap();
+        assert_eq!(err.to_string(), "Disconnected by the EIS server: not allowed");
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn missing_device_is_an_error() {
+        let (client, server) = UnixStream::pair().unwrap();
+        let handle = thread::spawn(move || serve(server));
+        let mut sender = EiSender::from_stream(client).unwrap();
+        let err = sender.scroll(0, 1).unwrap_err();
+        assert_eq!(err.to_string(), "No resumed EIS device with ei_scroll");
+        drop(sender);
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,215 @@
+#[cfg(feature = "uinput")]
+use std::io::Write;
+#[cfg(feature = "uinput")]
+use color_eyre::eyre::bail;
+#[cfg(feature = "uinput")]
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+use crate::key_codes::*;
+
+#[cfg(feature = "uinput")]
+use crate::uinput::{AbsInfo, EventParams, UinputBuilder, UinputDevice};
+
+pub const PEN_PRESSURE_MAX: i32 = 4095;
+pub const PEN_TILT_MAX: i32 = 90;
+
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum StylusButton {
+    Primary,
+    Secondary,
+    Tertiary,
+}
+
+impl StylusButton {
+    pub fn evdev_code(&self) -> u16 {
+        match self {
+            StylusButton::Primary => BTN_STYLUS,
+            StylusButton::Secondary => BTN_STYLUS2,
+            StylusButton::Tertiary => BTN_STYLUS3,
+        }
+    }
+}
+
+#[cfg(feature = "uinput")]
+pub struct VirtualPen {
+    device: UinputDevice,
+    width: i32,
+    height: i32,
+    in_range: bool,
+    touching: bool,
+}
+
+#[cfg(feature = "uinput")]
+impl VirtualPen {
+    // Screen tablets map onto the display, external tablets act as a pointer
+    pub fn uinput_builder(width: i32, height: i32, on_screen: bool) -> UinputBuilder {
+        let prop = match on_screen {
+            true => INPUT_PROP_DIRECT,
+            false => INPUT_PROP_POINTER,
+        };
+
+        UinputBuilder::new()
+            .name("universal_input pen")
+            .bus_type(BUS_VIRTUAL)
+            .key_bits(&[BTN_TOOL_PEN, BTN_TOUCH, BTN_STYLUS, BTN_STYLUS2, BTN_STYLUS3])
+            .abs_axis(ABS_X, AbsInfo::new(0, width - 1).resolution(12))
+            .abs_axis(ABS_Y, AbsInfo::new(0, height - 1).resolution(12))
+            .abs_axis(ABS_PRESSURE, AbsInfo::new(0, PEN_PRESSURE_MAX))
+            .abs_axis(ABS_TILT_X, AbsInfo::new(-PEN_TILT_MAX, PEN_TILT_MAX))
+            .abs_axis(ABS_TILT_Y, AbsInfo::new(-PEN_TILT_MAX, PEN_TILT_MAX))
+            .prop_bit(prop)
+    }
+
+    pub fn new(width: i32, height: i32, on_screen: bool) -> Result<Self> {
+        let device = Self::uinput_builder(width, height, on_screen).create()?;
+        Self::from_device(device, width, height)
+    }
+
+    pub fn from_writer<W: Write + Send + 'static>(writer: W, width: i32, height: i32) -> Result<Self> {
+        Self::from_device(UinputDevice::from_writer(writer), width, height)
+    }
+
+    fn from_device(device: UinputDevice, width: i32, height: i32) -> Result<Self> {
+        if width <= 0 || height <= 0 {
+            bail!("Pen surface must have positive size, got {width}x{height}");
+        }
+
+        Ok(Self {
+            device,
+            width,
+            height,
+            in_range: false,
+            touching: false,
+        })
+    }
+
+    #[inline]
+    pub fn is_in_range(&self) -> bool {
+        self.in_range
+    }
+
+    #[inline]
+    pub fn is_touching(&self) -> bool {
+        self.touching
+    }
+
+    #[inline]
+    fn position_events(&self, x: i32, y: i32) -> [EventParams; 2] {
+        [
+            (EV_ABS, ABS_X, x.clamp(0, self.width - 1)),
+            (EV_ABS, ABS_Y, y.clamp(0, self.height - 1)),
+        ]
+    }
+
+    // Pen hovers over the surface without touching it, a tip that is down gets lifted
+    pub fn pen_in_range(&mut self, x: i32, y: i32) -> Result<()> {
+        let mut events = vec![];
+        if !self.in_range {
+            events.push((EV_KEY, BTN_TOOL_PEN, 1));
+        }
+        events.extend(self.position_events(x, y));
+        if self.touching {
+            events.push((EV_ABS, ABS_PRESSURE, 0));
+            events.push((EV_KEY, BTN_TOUCH, 0));
+        }
+
+        self.device.write_batch(&events)?;
+        self.in_range = true;
+        self.touching = false;
+        self.device.synchronize()
+    }
+
+    // Pressure above zero puts the tip down, zero lifts it but stays in range
+    pub fn pen_move(&mut self, x: i32, y: i32, pressure: i32) -> Result<()> {
+        let pressure = pressure.clamp(0, PEN_PRESSURE_MAX);
+        let touching = pressure > 0;
+
+        let mut events = vec![];
+        if !self.in_range {
+            events.push((EV_KEY, BTN_TOOL_PEN, 1));
+        }
+        events.extend(self.position_events(x, y));
+        events.push((EV_ABS, ABS_PRESSURE, pressure));
+        if touching != self.touching {
+            events.push((EV_KEY, BTN_TOUCH, touching as i32));
+        }
+
+        self.device.write_batch(&events)?;
+        self.in_range = true;
+        self.touching = touching;
+        self.device.synchronize()
+    }
+
+    pub fn set_tilt(&mut self, tilt_x: i32, tilt_y: i32) -> Result<()> {
+        self.device.write_batch(&[
+            (EV_ABS, ABS_TILT_X, tilt_x.clamp(-PEN_TILT_MAX, PEN_TILT_MAX)),
+            (EV_ABS, ABS_TILT_Y, tilt_y.clamp(-PEN_TILT_MAX, PEN_TILT_MAX)),
+        ])?;
+        self.device.synchronize()
+    }
+
+    #[inline]
+    pub fn press(&mut self, button: StylusButton) -> Result<()> {
+        self.device.write_event(EV_KEY, button.evdev_code(), 1)?;
+        self.device.synchronize()
+    }
+
+    #[inline]
+    pub fn release(&mut self, button: StylusButton) -> Result<()> {
+        self.device.write_event(EV_KEY, button.evdev_code(), 0)?;
+        self.device.synchronize()
+    }
+
+    pub fn pen_out_of_range(&mut self) -> Result<()> {
+        let mut events = vec![];
+        if self.touching {
+            events.push((EV_ABS, ABS_PRESSURE, 0));
+            events.push((EV_KEY, BTN_TOUCH, 0));
+        }
+        if self.in_range {
+            events.push((EV_KEY, BTN_TOOL_PEN, 0));
+        }
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        self.device.write_batch(&events)?;
+        self.in_range = false;
+        self.touching = false;
+        self.device.synchronize()
+    }
+}
+
+#[cfg(all(test, feature = "uinput"))]
+mod tests {
+    use std::io::Read;
+    use crate::uinput::tests::decode_events;
+    use super::*;
+
+    #[test]
+    fn hovering_lifts_the_tip() {
+        let (mut reader, writer) = std::io::pipe().unwrap();
+        let mut pen = VirtualPen::from_writer(writer, 100, 100).unwrap();
+        pen.pen_move(10, 20, 300).unwrap();
+        pen.pen_in_range(11, 21).unwrap();
+        assert!(pen.is_in_range());
+        assert!(!pen.is_touching());
+        drop(pen);
+
+        let mut written = vec![];
+        reader.read_to_end(&mut written).unwrap();
+        assert_eq!(decode_events(&written), [
+            (EV_KEY, BTN_TOOL_PEN, 1),
+            (EV_ABS, ABS_X, 10),
+            (EV_ABS, ABS_Y, 20),
+            (EV_ABS, ABS_PRESSURE, 300),
+            (EV_KEY, BTN_TOUCH, 1),
+            (EV_SYN, SYN_REPORT, 0),
+            (EV_ABS, ABS_X, 11),
+            (EV_ABS, ABS_Y, 21),
+            (EV_ABS, ABS_PRESSURE, 0),
+            (EV_KEY, BTN_TOUCH, 0),
+            (EV_SYN, SYN_REPORT, 0),
+        ]);
+    }
+}
@@ -0,0 +1,202 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use color_eyre::eyre::bail;
+use color_eyre::Result;
+use serde_json::{json, Value};
+use crate::{exec_or_eyre, OS_Input_Coord};
+
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::path::Path;
+
+// Replies longer than this mean the peer isn't a QMP server
+const MAX_LINE_SIZE: u64 = 1 << 20;
+
+pub trait QmpStream: Read + Write + Send {}
+
+impl<T: Read + Write + Send> QmpStream for T {}
+
+// Commands and replies are JSON objects, one per line. Asynchronous {"event": ...}
+// messages can arrive between them and are skipped.
+pub struct QmpConnection {
+    stream: BufReader<Box<dyn QmpStream>>,
+}
+
+impl QmpConnection {
+    // -qmp unix:/path/to/qmp.sock,server,wait=off
+    #[cfg(unix)]
+    pub fn connect_unix(path: impl AsRef<Path>) -> Result<Self> {
+        Self::handshake(exec_or_eyre!(UnixStream::connect(path))?)
+    }
+
+    pub fn connect_tcp(address: impl ToSocketAddrs) -> Result<Self> {
+        let stream = exec_or_eyre!(TcpStream::connect(address))?;
+        exec_or_eyre!(stream.set_nodelay(true))?;
+        Self::handshake(stream)
+    }
+
+    // Reads the greeting and leaves capabilities negotiation mode
+    pub fn handshake<S: QmpStream + 'static>(stream: S) -> Result<Self> {
+        let mut connection = Self {
+            stream: BufReader::new(Box::new(stream)),
+        };
+        let greeting = connection.read_message()?;
+        if greeting.get("QMP").is_none() {
+            bail!("Expected QMP greeting, got: {greeting}");
+        }
+        connection.execute("qmp_capabilities", None)?;
+        Ok(connection)
+    }
+
+    fn read_message(&mut self) -> Result<Value> {
+        let mut line = String::new();
+        let read = exec_or_eyre!((&mut self.stream).take(MAX_LINE_SIZE).read_line(&mut line))?;
+        if read == 0 {
+            bail!("Connection closed by QEMU");
+        }
+        if !line.ends_with('\n') {
+            bail!("QMP message exceeds the limit of {MAX_LINE_SIZE} bytes");
+        }
+        exec_or_eyre!(serde_json::from_str(&line))
+    }
+
+    pub fn execute(&mut self, command: &str, arguments: Option<Value>) -> Result<Value> {
+        let mut message = json!({ "execute": command });
+        if let Some(arguments) = arguments {
+            message["arguments"] = arguments;
+        }
+        let mut bytes = exec_or_eyre!(serde_json::to_vec(&message))?;
+        bytes.push(b'\n');
+        exec_or_eyre!(self.stream.get_mut().write_all(&bytes))?;
+
+        loop {
+            let reply = self.read_message()?;
+            if let Some(value) = reply.get("return") {
+                return Ok(value.clone());
+            }
+            if let Some(error) = reply.get("error") {
+                let description = error.get("desc").and_then(Value::as_str).unwrap_or("unknown error");
+                bail!("QMP command {command} failed: {description}");
+            }
+            if reply.get("event").is_none() {
+                bail!("Unexpected QMP message: {reply}");
+            }
+        }
+    }
+
+    // Events are applied in order, all of them in the same input frame
+    pub fn input_send_event(&mut self, events: &[Value], device: Option<&str>) -> Result<()> {
+        let mut arguments = json!({ "events": events });
+        if let Some(device) = device {
+            arguments["device"] = json!(device);
+        }
+        self.execute("input-send-event", Some(arguments))?;
+        Ok(())
+    }
+}
+
+pub fn qmp_key_event(qcode: &str, down: bool) -> Value {
+    json!({
+        "type": "key",
+        "data": { "down": down, "key": { "type": "qcode", "data": qcode } },
+    })
+}
+
+// left, middle, right, wheel-up, wheel-down, wheel-left, wheel-right, side, extra
+pub fn qmp_button_event(button: &str, down: bool) -> Value {
+    json!({
+        "type": "btn",
+        "data": { "down": down, "button": button },
+    })
+}
+
+// x or y, y grows downwards
+pub fn qmp_rel_event(axis: &str, value: OS_Input_Coord) -> Value {
+    json!({
+        "type": "rel",
+        "data": { "axis": axis, "value": value },
+    })
+}
+
+#[cfg(all(test, unix))]
+pub(crate) mod tests {
+    use std::os::unix::net::UnixListener;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use super::*;
+
+    pub(crate) const GREETING: &str = r#"{"QMP": {"version": {"qemu": {"micro": 0, "minor": 2, "major": 9}, "package": ""}, "capabilities": []}}"#;
+    const EVENT: &str = r#"{"event": "NIC_RX_FILTER_CHANGED", "timestamp": {"seconds": 1, "microseconds": 2}, "data": {}}"#;
+
+    pub(crate) fn socket_path() -> PathBuf {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let count = COUNT.fetch_add(1, Ordering::Relaxed);
+        std::env::temp_dir().join(format!("universal_input-qmp-{}-{count}.sock", std::process::id()))
+    }
+
+    // Serves one client on a Unix socket: sends the greeting, then an async event before every reply.
+    // Commands named "fail" get an error, the rest an empty return. Returns the commands received.
+    pub(crate) fn fake_server(path: &Path, greeting: &'static str) -> thread::JoinHandle<Vec<Value>> {
+        let _ = std::fs::remove_file(path);
+        let listener = UnixListener::bind(path).unwrap();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut writer = stream.try_clone().unwrap();
+            writeln!(writer, "{greeting}").unwrap();
+
+            let mut commands = vec![];
+            for line in BufReader::new(stream).lines() {
+                let command: Value = serde_json::from_str(&line.unwrap()).unwrap();
+                writeln!(writer, "{EVENT}").unwrap();
+                match command["execute"].as_str() {
+                    Some("fail") => writeln!(writer, r#"{{"error": {{"class": "GenericError", "desc": "no such thing"}}}}"#),
+                    _ => writeln!(writer, r#"{{"return": {{}}}}"#),
+                }
+                .unwrap();
+                commands.push(command);
+            }
+            commands
+        })
+    }
+
+    #[test]
+    fn handshake_skips_events() {
+        let path = socket_path();
+        let handle = fake_server(&path, GREETING);
+        let mut connection = QmpConnection::connect_unix(&path).unwrap();
+        assert_eq!(connection.execute("query-status", None).unwrap(), json!({}));
+        drop(connection);
+
+        let commands = handle.join().unwrap();
+        assert_eq!(commands, [json!({ "execute": "qmp_capabilities" }), json!({ "execute": "query-status" })]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn error_reply() {
+        let path = socket_path();
+        let handle = fake_server(&path, GREETING);
+        let mut connection = QmpConnection::connect_unix(&path).unwrap();
+        let err = connection.execute("fail", Some(json!({ "id": 1 }))).unwrap_err();
+        assert_eq!(err.to_string(), "QMP command fail failed: no such thing");
+        // The connection stays usable
+        connection.execute("query-status", None).unwrap();
+        drop(connection);
+
+        let commands = handle.join().unwrap();
+        assert_eq!(commands[1], json!({ "execute": "fail", "arguments": { "id": 1 } }));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rejects_other_greeting() {
+        let path = socket_path();
+        let handle = fake_server(&path, r#"{"hello": "world"}"#);
+        let err = QmpConnection::connect_unix(&path).err().unwrap();
+        assert!(err.to_string().starts_with("Expected QMP greeting"));
+        assert!(handle.join().unwrap().is_empty());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
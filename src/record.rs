@@ -0,0 +1,510 @@
+use std::ffi::c_long;
+use std::fs::File;
+use std::io::{BufReader, ErrorKind, Read};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use color_eyre::eyre::bail;
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+use crate::input_macro::{cancellable_sleep, HeldKeys};
+use crate::key_codes::*;
+use crate::{exec_or_eyre, InputEmulator, InputEvent, KeyCode, MacroCancel, MacroStatus, OS_Input_Coord};
+
+// struct input_event from linux/input.h: timeval, then type, code and value
+const TIME_FIELD_SIZE: usize = size_of::<c_long>();
+pub const INPUT_EVENT_SIZE: usize = 2 * TIME_FIELD_SIZE + 8;
+
+const BINARY_MAGIC: &[u8; 6] = b"UIREC1";
+
+const TAG_MOVE: u8 = 0;
+const TAG_SCROLL: u8 = 1;
+const TAG_PRESS: u8 = 2;
+const TAG_RELEASE: u8 = 3;
+const TAG_SYNC: u8 = 4;
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct RawInputEvent {
+    // Microseconds, as reported by the kernel
+    pub time_us: u64,
+    pub event_type: u16,
+    pub code: u16,
+    pub value: i32,
+}
+
+impl RawInputEvent {
+    pub fn from_bytes(bytes: &[u8; INPUT_EVENT_SIZE]) -> Self {
+        let time_field = |index: usize| {
+            let start = index * TIME_FIELD_SIZE;
+            let field: [u8; TIME_FIELD_SIZE] = bytes[start..start + TIME_FIELD_SIZE].try_into().unwrap();
+            c_long::from_ne_bytes(field).max(0) as u64
+        };
+        let start = 2 * TIME_FIELD_SIZE;
+
+        Self {
+            time_us: time_field(0) * 1_000_000 + time_field(1),
+            event_type: u16::from_ne_bytes([bytes[start], bytes[start + 1]]),
+            code: u16::from_ne_bytes([bytes[start + 2], bytes[start + 3]]),
+            value: i32::from_ne_bytes(bytes[start + 4..start + 8].try_into().unwrap()),
+        }
+    }
+}
+
+// Reads input_event structs from an evdev node or any other source, e.g. a fixture file
+pub struct EvdevReader<R: Read> {
+    reader: R,
+}
+
+impl<R: Read> EvdevReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader }
+    }
+
+    // None at the end of the stream
+    pub fn read_event(&mut self) -> Result<Option<RawInputEvent>> {
+        let mut bytes = [0u8; INPUT_EVENT_SIZE];
+        match self.reader.read_exact(&mut bytes) {
+            Ok(()) => Ok(Some(RawInputEvent::from_bytes(&bytes))),
+            Err(error) if error.kind() == ErrorKind::UnexpectedEof => Ok(None),
+            Err(error) => bail!(error),
+        }
+    }
+}
+
+impl<R: Read> Iterator for EvdevReader<R> {
+    type Item = Result<RawInputEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_event().transpose()
+    }
+}
+
+// Collects raw events until SYN_REPORT and turns the frame into InputEvents
+#[derive(Clone, Default, Debug)]
+pub(crate) struct FrameDecoder {
+    move_x: OS_Input_Coord,
+    move_y: OS_Input_Coord,
+    scroll_x: OS_Input_Coord,
+    scroll_y: OS_Input_Coord,
+    keys: Vec<InputEvent>,
+    dropped: bool,
+}
+
+impl FrameDecoder {
+    pub(crate) fn feed(&mut self, event: &RawInputEvent) -> Option<Vec<InputEvent>> {
+        match (event.event_type, event.code) {
+            // Positive y goes up here and down in evdev
+            (EV_REL, REL_X) => self.move_x += event.value,
+            (EV_REL, REL_Y) => self.move_y -= event.value,
+            (EV_REL, REL_HWHEEL) => self.scroll_x += event.value,
+            (EV_REL, REL_WHEEL) => self.scroll_y += event.value,
+            // Autorepeat (value 2) is left to the receiving side
+            (EV_KEY, code) if event.value == 0 || event.value == 1 => {
+                if let Some(key_code) = KeyCode::from_evdev(code) {
+                    self.keys.push(match event.value {
+                        1 => InputEvent::Press(key_code),
+                        _ => InputEvent::Release(key_code),
+                    });
+                }
+            }
+            (EV_SYN, SYN_DROPPED) => self.dropped = true,
+            (EV_SYN, SYN_REPORT) => return Some(self.finish_frame()),
+            _ => {}
+        }
+        None
+    }
+
+    fn finish_frame(&mut self) -> Vec<InputEvent> {
+        let frame = std::mem::take(self);
+        // Events up to and including the next SYN_REPORT after SYN_DROPPED are incomplete
+        if frame.dropped {
+            return vec![];
+        }
+
+        let mut events = vec![];
+        if frame.move_x != 0 || frame.move_y != 0 {
+            events.push(InputEvent::Move(frame.move_x, frame.move_y));
+        }
+        if frame.scroll_x != 0 || frame.scroll_y != 0 {
+            events.push(InputEvent::Scroll(frame.scroll_x, frame.scroll_y));
+        }
+        events.extend(frame.keys);
+        events
+    }
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    // Microseconds since the start of the recording
+    pub time_us: u64,
+    pub event: InputEvent,
+}
+
+#[derive(PartialEq, Eq, Clone, Default, Debug, Serialize, Deserialize)]
+pub struct Recording {
+    pub events: Vec<RecordedEvent>,
+}
+
+impl Recording {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.events
+            .last()
+            .map_or(Duration::ZERO, |event| Duration::from_micros(event.time_us))
+    }
+
+    // Records until the end of the stream or until stop_key is pressed, stop_key itself is not recorded
+    pub fn record<R: Read>(reader: R, stop_key: Option<KeyCode>) -> Result<Self> {
+        let mut recording = Self::new();
+        let mut decoder = FrameDecoder::default();
+        let mut start_us = None;
+
+        for raw_event in EvdevReader::new(reader) {
+            let raw_event = raw_event?;
+            let start_us = *start_us.get_or_insert(raw_event.time_us);
+
+            let Some(events) = decoder.feed(&raw_event) else {
+                continue;
+            };
+            let time_us = raw_event.time_us.saturating_sub(start_us);
+
+            for event in events {
+                if stop_key.is_some_and(|stop_key| event == InputEvent::Press(stop_key)) {
+                    return Ok(recording);
+                }
+                recording.events.push(RecordedEvent { time_us, event });
+            }
+        }
+
+        Ok(recording)
+    }
+
+    // e.g. /dev/input/event3, reading it usually requires root or the input group
+    pub fn record_device(path: impl AsRef<Path>, stop_key: KeyCode) -> Result<Self> {
+        let file = exec_or_eyre!(File::open(path))?;
+        Self::record(BufReader::new(file), Some(stop_key))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        exec_or_eyre!(serde_json::from_str(json))
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        exec_or_eyre!(serde_json::to_string(self))
+    }
+
+    // Magic, then per event: u32 time delta in microseconds, tag, payload. Little endian.
+    // Keys are stored as evdev codes so the format survives changes to KeyCode.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes = BINARY_MAGIC.to_vec();
+        let mut last_time_us = 0;
+
+        for recorded in &self.events {
+            let Ok(delta) = u32::try_from(recorded.time_us.saturating_sub(last_time_us)) else {
+                bail!("Gap between events at {}us is too long", recorded.time_us);
+            };
+            last_time_us = recorded.time_us;
+            bytes.extend_from_slice(&delta.to_le_bytes());
+
+            match recorded.event {
+                InputEvent::Move(x, y) | InputEvent::Scroll(x, y) => {
+                    bytes.push(match recorded.event {
+                        InputEvent::Move(..) => TAG_MOVE,
+                        _ => TAG_SCROLL,
+                    });
+                    bytes.extend_from_slice(&x.to_le_bytes());
+                    bytes.extend_from_slice(&y.to_le_bytes());
+                }
+                InputEvent::Press(key_code) | InputEvent::Release(key_code) => {
+                    let Some(code) = key_code.evdev_code() else {
+                        bail!("No such key code: {key_code}");
+                    };
+                    bytes.push(match recorded.event {
+                        InputEvent::Press(_) => TAG_PRESS,
+                        _ => TAG_RELEASE,
+                    });
+                    bytes.extend_from_slice(&code.to_le_bytes());
+                }
+                InputEvent::Sync => bytes.push(TAG_SYNC),
+            }
+        }
+
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let Some(payload) = bytes.strip_prefix(BINARY_MAGIC.as_slice()) else {
+            bail!("Not a binary recording");
+        };
+
+        let mut cursor = ByteCursor {
+            bytes: payload,
+            position: BINARY_MAGIC.len(),
+        };
+        let mut recording = Self::new();
+        let mut time_us = 0;
+
+        while !cursor.is_empty() {
+            time_us += cursor.read_u32()? as u64;
+
+            let event = match cursor.read_u8()? {
+                TAG_MOVE => InputEvent::Move(cursor.read_i32()?, cursor.read_i32()?),
+                TAG_SCROLL => InputEvent::Scroll(cursor.read_i32()?, cursor.read_i32()?),
+                tag @ (TAG_PRESS | TAG_RELEASE) => {
+                    let code = cursor.read_u16()?;
+                    let Some(key_code) = KeyCode::from_evdev(code) else {
+                        bail!("Unknown evdev code in recording: {code}");
+                    };
+                    match tag {
+                        TAG_PRESS => InputEvent::Press(key_code),
+                        _ => InputEvent::Release(key_code),
+                    }
+                }
+                TAG_SYNC => InputEvent::Sync,
+                tag => bail!("Unknown event tag in recording: {tag}"),
+            };
+
+            recording.events.push(RecordedEvent { time_us, event });
+        }
+
+        Ok(recording)
+    }
+
+    pub fn save_json(&self, path: impl AsRef<Path>) -> Result<()> {
+        exec_or_eyre!(std::fs::write(path, self.to_json()?))
+    }
+
+    pub fn save_binary(&self, path: impl AsRef<Path>) -> Result<()> {
+        exec_or_eyre!(std::fs::write(path, self.to_bytes()?))
+    }
+
+    // Detects the format by the binary magic
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let bytes = exec_or_eyre!(std::fs::read(path))?;
+        if bytes.starts_with(BINARY_MAGIC) {
+            return Self::from_bytes(&bytes);
+        }
+        Self::from_json(&exec_or_eyre!(String::from_utf8(bytes))?)
+    }
+}
+
+struct ByteCursor<'a> {
+    bytes: &'a [u8],
+    // Offset in the whole file, for error messages
+    position: usize,
+}
+
+impl ByteCursor<'_> {
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N]> {
+        if self.bytes.len() < N {
+            bail!("Recording is truncated at byte {}", self.position);
+        }
+        let (head, tail) = self.bytes.split_at(N);
+        self.bytes = tail;
+        self.position += N;
+        Ok(head.try_into().unwrap())
+    }
+
+    fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.take()?))
+    }
+
+    fn read_u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+
+    fn read_i32(&mut self) -> Result<i32> {
+        Ok(i32::from_le_bytes(self.take()?))
+    }
+}
+
+impl InputEmulator {
+    // speed 2.0 plays twice as fast, keys still held at the end or on cancel are released
+    pub fn replay(&mut self, recording: &Recording, speed: f64, cancel: &MacroCancel) -> Result<MacroStatus> {
+        if !speed.is_finite() || speed <= 0.0 {
+            bail!("Replay speed must be positive, got {speed}");
+        }
+        // Checked up front, a tiny speed can stretch the recording past what a Duration holds
+        let scaled = |time_us: u64| Duration::try_from_secs_f64(time_us as f64 / 1_000_000.0 / speed);
+        let last_us = recording.events.iter().map(|recorded| recorded.time_us).max().unwrap_or(0);
+        if scaled(last_us).is_err() {
+            bail!("Replay speed {speed} is too slow for a recording of {:?}", Duration::from_micros(last_us));
+        }
+
+        let mut keys = HeldKeys::new(self);
+        let start = Instant::now();
+
+        for recorded in &recording.events {
+            let target = scaled(recorded.time_us).unwrap_or(Duration::MAX);
+            if !cancellable_sleep(target.saturating_sub(start.elapsed()), cancel) {
+                return Ok(MacroStatus::Cancelled);
+            }
+
+            match recorded.event {
+                InputEvent::Move(x, y) => keys.emulator.move_mouse(x, y)?,
+                InputEvent::Scroll(x, y) => {
+                    if x != 0 {
+                        keys.emulator.scroll_x(x)?;
+                    }
+                    if y != 0 {
+                        keys.emulator.scroll_y(y)?;
+                    }
+                }
+                InputEvent::Press(key_code) => keys.press(key_code)?,
+                InputEvent::Release(key_code) => keys.release(key_code)?,
+                // Every call above already synchronizes
+                InputEvent::Sync => {}
+            }
+        }
+
+        Ok(MacroStatus::Completed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Captured layout of a 64-bit little endian kernel: mouse move, tap of A with an autorepeat,
+    // wheel down, a frame after SYN_DROPPED, Esc, then one more move
+    #[cfg(all(target_pointer_width = "64", target_endian = "little"))]
+    const EVDEV_FIXTURE: &[u8] = include_bytes!("../tests/fixtures/evdev_mouse_keyboard.bin");
+
+    fn sample() -> Recording {
+        Recording {
+            events: vec![
+                RecordedEvent { time_us: 0, event: InputEvent::Move(5, -3) },
+                RecordedEvent { time_us: 10_000, event: InputEvent::Press(KeyCode::KEY_A) },
+                RecordedEvent { time_us: 50_000, event: InputEvent::Release(KeyCode::KEY_A) },
+                RecordedEvent { time_us: 100_000, event: InputEvent::Scroll(0, -1) },
+            ],
+        }
+    }
+
+    #[cfg(all(target_pointer_width = "64", target_endian = "little"))]
+    #[test]
+    fn records_fixture_until_stop_key() {
+        let recording = Recording::record(EVDEV_FIXTURE, Some(KeyCode::KEY_ESC)).unwrap();
+        assert_eq!(recording, sample());
+        assert_eq!(recording.duration(), Duration::from_millis(100));
+    }
+
+    #[cfg(all(target_pointer_width = "64", target_endian = "little"))]
+    #[test]
+    fn records_fixture_to_the_end() {
+        let recording = Recording::record(EVDEV_FIXTURE, None).unwrap();
+        let tail: Vec<_> = recording.events[4..].to_vec();
+        assert_eq!(tail, [
+            RecordedEvent { time_us: 300_000, event: InputEvent::Press(KeyCode::KEY_ESC) },
+            RecordedEvent { time_us: 400_000, event: InputEvent::Move(1, 0) },
+        ]);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let recording = sample();
+        assert_eq!(Recording::from_json(&recording.to_json().unwrap()).unwrap(), recording);
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let recording = sample();
+        let bytes = recording.to_bytes().unwrap();
+        assert!(bytes.starts_with(BINARY_MAGIC));
+        // Move: delta, tag, x, y
+        assert_eq!(&bytes[6..15], &[0, 0, 0, 0, TAG_MOVE, 5, 0, 0, 0]);
+        assert_eq!(Recording::from_bytes(&bytes).unwrap(), recording);
+    }
+
+    #[test]
+    fn binary_rejects_bad_input() {
+        let bytes = sample().to_bytes().unwrap();
+        assert!(Recording::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Recording::from_bytes(b"UIREC0").is_err());
+
+        let mut unknown_tag = BINARY_MAGIC.to_vec();
+        unknown_tag.extend_from_slice(&[0, 0, 0, 0, 9]);
+        assert!(Recording::from_bytes(&unknown_tag).is_err());
+    }
+
+    #[cfg(feature = "use_uinput")]
+    #[test]
+    fn rejects_out_of_range_speed() {
+        let mut emulator = InputEmulator::from_writer(std::io::sink());
+        let cancel = MacroCancel::new();
+        for speed in [0.0, -1.0, f64::NAN, f64::INFINITY, 1e-300] {
+            assert!(emulator.replay(&sample(), speed, &cancel).is_err(), "{speed}");
+        }
+        // Nothing to stretch
+        assert_eq!(emulator.replay(&Recording::default(), 1e-300, &cancel).unwrap(), MacroStatus::Completed);
+    }
+
+    #[cfg(feature = "use_uinput")]
+    #[test]
+    fn replays_through_backend() {
+        use std::io::Read;
+        use crate::uinput::tests::decode_events;
+
+        let (mut reader, writer) = std::io::pipe().unwrap();
+        let mut emulator = InputEmulator::from_writer(writer);
+        let status = emulator.replay(&sample(), 100.0, &MacroCancel::new()).unwrap();
+        assert_eq!(status, MacroStatus::Completed);
+        drop(emulator);
+
+        let mut written = vec![];
+        reader.read_to_end(&mut written).unwrap();
+        assert_eq!(decode_events(&written), [
+            (EV_REL, REL_X, 5),
+            (EV_REL, REL_Y, 3),
+            (EV_SYN, SYN_REPORT, 0),
+            (EV_KEY, KEY_A, 1),
+            (EV_SYN, SYN_REPORT, 0),
+            (EV_KEY, KEY_A, 0),
+            (EV_SYN, SYN_REPORT, 0),
+            (EV_REL, REL_WHEEL, -1),
+            (EV_SYN, SYN_REPORT, 0),
+        ]);
+    }
+
+    #[cfg(feature = "use_uinput")]
+    #[test]
+    fn cancelled_replay_releases_keys() {
+        use std::io::Read;
+        use crate::uinput::tests::decode_events;
+
+        let recording = Recording {
+            events: vec![
+                RecordedEvent { time_us: 0, event: InputEvent::Press(KeyCode::KEY_A) },
+                RecordedEvent { time_us: 10_000_000, event: InputEvent::Release(KeyCode::KEY_A) },
+            ],
+        };
+        let (mut reader, writer) = std::io::pipe().unwrap();
+        let mut emulator = InputEmulator::from_writer(writer);
+        let cancel = MacroCancel::new();
+        let canceller = cancel.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            canceller.cancel();
+        });
+        assert_eq!(emulator.replay(&recording, 1.0, &cancel).unwrap(), MacroStatus::Cancelled);
+        drop(emulator);
+
+        let mut written = vec![];
+        reader.read_to_end(&mut written).unwrap();
+        let keys: Vec<_> = decode_events(&written).into_iter()
+            .filter(|(event_type, _, _)| *event_type == EV_KEY)
+            .collect();
+        assert_eq!(keys, [(EV_KEY, KEY_A, 1), (EV_KEY, KEY_A, 0)]);
+    }
+}
@@ -0,0 +1,965 @@
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+use color_eyre::eyre::bail;
+use color_eyre::Result;
+use hmac::{Hmac, Mac};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use crate::input_macro::{run_macro_on, MacroTarget};
+use crate::{err_eyre, exec_or_eyre, InputBatch, InputEmulator, InputEvent, KeyCode, KeyCodes, Macro, MacroCancel, MacroStatus, OS_Input_Coord};
+
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::Path;
+
+// Used by RemoteServer::new and RemoteInputEmulator::new, loopback only
+pub const DEFAULT_REMOTE_ADDRESS: &str = "127.0.0.1:7707";
+
+// Upper bound for one message, anything bigger means a broken or hostile peer
+const MAX_MESSAGE_SIZE: usize = 1 << 20;
+const CHALLENGE_SIZE: usize = 32;
+// Clients that don't finish the handshake in time are dropped so they can't hold up the server
+const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_MAX_BATCH_LEN: usize = 4096;
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+pub enum RemoteCommand {
+    Press(KeyCode),
+    Release(KeyCode),
+    Move(OS_Input_Coord, OS_Input_Coord),
+    Scroll(OS_Input_Coord, OS_Input_Coord),
+    Batch(InputBatch),
+}
+
+impl RemoteCommand {
+    pub fn events(&self) -> Vec<InputEvent> {
+        match self {
+            RemoteCommand::Press(key_code) => vec![InputEvent::Press(*key_code)],
+            RemoteCommand::Release(key_code) => vec![InputEvent::Release(*key_code)],
+            RemoteCommand::Move(x, y) => vec![InputEvent::Move(*x, *y)],
+            RemoteCommand::Scroll(x, y) => vec![InputEvent::Scroll(*x, *y)],
+            RemoteCommand::Batch(batch) => batch.events().to_vec(),
+        }
+    }
+}
+
+// The server opens with Hello. With a challenge the client answers Auth with
+// HMAC-SHA256(key, challenge) and the server confirms with a RemoteResponse.
+#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+pub enum RemoteHandshake {
+    Hello { challenge: Option<Vec<u8>> },
+    Auth { mac: Vec<u8> },
+}
+
+// One response per command, in order
+#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+pub enum RemoteResponse {
+    Ok,
+    Error(String),
+}
+
+// JSON lines: one JSON value per line, easy to drive from a shell or a script.
+// Binary: u32 little-endian length followed by the bincode encoded value.
+#[derive(PartialEq, Eq, Copy, Clone, Default, Debug)]
+pub enum RemoteEncoding {
+    #[default]
+    JsonLines,
+    Binary,
+}
+
+pub fn write_message<T: Serialize>(writer: &mut impl Write, encoding: RemoteEncoding, message: &T) -> Result<()> {
+    let mut bytes = match encoding {
+        RemoteEncoding::JsonLines => exec_or_eyre!(serde_json::to_vec(message))?,
+        RemoteEncoding::Binary => {
+            let payload = exec_or_eyre!(bincode::serialize(message))?;
+            let mut bytes = (payload.len() as u32).to_le_bytes().to_vec();
+            bytes.extend(payload);
+            bytes
+        }
+    };
+    if encoding == RemoteEncoding::JsonLines {
+        bytes.push(b'\n');
+    }
+    exec_or_eyre!(writer.write_all(&bytes))?;
+    exec_or_eyre!(writer.flush())
+}
+
+// None when the peer closed the connection between messages
+pub fn read_message<T: DeserializeOwned>(reader: &mut impl BufRead, encoding: RemoteEncoding) -> Result<Option<T>> {
+    match encoding {
+        RemoteEncoding::JsonLines => {
+            let mut line = String::new();
+            loop {
+                line.clear();
+                let limit = MAX_MESSAGE_SIZE as u64 + 1;
+                if exec_or_eyre!(reader.by_ref().take(limit).read_line(&mut line))? == 0 {
+                    return Ok(None);
+                }
+                if line.len() > MAX_MESSAGE_SIZE {
+                    bail!("Message exceeds the limit of {MAX_MESSAGE_SIZE} bytes");
+                }
+                if !line.trim().is_empty() {
+                    return exec_or_eyre!(serde_json::from_str(&line)).map(Some);
+                }
+            }
+        }
+        RemoteEncoding::Binary => {
+            let mut length = [0u8; 4];
+            match reader.read_exact(&mut length) {
+                Ok(()) => {}
+                Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+                Err(err) => return Err(err_eyre!(err)),
+            }
+            let length = u32::from_le_bytes(length) as usize;
+            if length > MAX_MESSAGE_SIZE {
+                bail!("Message of {length} bytes exceeds the limit of {MAX_MESSAGE_SIZE}");
+            }
+            let mut payload = vec![0u8; length];
+            exec_or_eyre!(reader.read_exact(&mut payload))?;
+            exec_or_eyre!(bincode::deserialize(&payload)).map(Some)
+        }
+    }
+}
+
+pub trait RemoteStream: Read + Write + Send {}
+
+impl<T: Read + Write + Send> RemoteStream for T {}
+
+// Second handle to the socket, the boxed stream can't be reached for timeouts
+enum RemoteSocket {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+pub struct RemoteConnection {
+    stream: BufReader<Box<dyn RemoteStream>>,
+    encoding: RemoteEncoding,
+    socket: Option<RemoteSocket>,
+}
+
+impl RemoteConnection {
+    // Any byte stream works here, e.g. one end of a socket pair. Read timeouts need from_tcp or from_unix.
+    pub fn new<S: RemoteStream + 'static>(stream: S, encoding: RemoteEncoding) -> Self {
+        Self {
+            stream: BufReader::new(Box::new(stream)),
+            encoding,
+            socket: None,
+        }
+    }
+
+    pub fn from_tcp(stream: TcpStream, encoding: RemoteEncoding) -> Result<Self> {
+        let socket = RemoteSocket::Tcp(exec_or_eyre!(stream.try_clone())?);
+        Ok(Self {
+            socket: Some(socket),
+            ..Self::new(stream, encoding)
+        })
+    }
+
+    #[cfg(unix)]
+    pub fn from_unix(stream: UnixStream, encoding: RemoteEncoding) -> Result<Self> {
+        let socket = RemoteSocket::Unix(exec_or_eyre!(stream.try_clone())?);
+        Ok(Self {
+            socket: Some(socket),
+            ..Self::new(stream, encoding)
+        })
+    }
+
+    // No-op for streams passed to new
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        match &self.socket {
+            Some(RemoteSocket::Tcp(stream)) => exec_or_eyre!(stream.set_read_timeout(timeout)),
+            #[cfg(unix)]
+            Some(RemoteSocket::Unix(stream)) => exec_or_eyre!(stream.set_read_timeout(timeout)),
+            None => Ok(()),
+        }
+    }
+
+    #[inline]
+    pub fn encoding(&self) -> RemoteEncoding {
+        self.encoding
+    }
+
+    #[inline]
+    pub fn send<T: Serialize>(&mut self, message: &T) -> Result<()> {
+        write_message(self.stream.get_mut(), self.encoding, message)
+    }
+
+    #[inline]
+    pub fn receive<T: DeserializeOwned>(&mut self) -> Result<Option<T>> {
+        read_message(&mut self.stream, self.encoding)
+    }
+}
+
+pub fn sign_challenge(key: &[u8], challenge: &[u8]) -> Result<Vec<u8>> {
+    let mut mac = exec_or_eyre!(HmacSha256::new_from_slice(key))?;
+    mac.update(challenge);
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+// Constant time comparison
+fn verify_challenge(key: &[u8], challenge: &[u8], signature: &[u8]) -> Result<bool> {
+    let mut mac = exec_or_eyre!(HmacSha256::new_from_slice(key))?;
+    mac.update(challenge);
+    Ok(mac.verify_slice(signature).is_ok())
+}
+
+// Token bucket refilled continuously, allows bursts of up to one second worth of tokens
+#[derive(Clone, Debug)]
+pub struct RateLimiter {
+    per_second: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(per_second: u32) -> Self {
+        Self {
+            per_second: per_second as f64,
+            tokens: per_second as f64,
+            last_refill: Instant::now(),
+        }
+    }
+
+    #[inline]
+    pub fn try_acquire(&mut self, count: usize) -> bool {
+        self.try_acquire_at(count, Instant::now())
+    }
+
+    // Takes count tokens or none, a count above per_second never succeeds
+    pub fn try_acquire_at(&mut self, count: usize, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_second).min(self.per_second);
+        self.last_refill = self.last_refill.max(now);
+
+        let count = count as f64;
+        if self.tokens < count {
+            return false;
+        }
+        self.tokens -= count;
+        true
+    }
+}
+
+// What a client may do. By default there is no key and no rate limit, every key but the power keys is allowed,
+// batches hold up to 4096 events and the handshake must finish within 5 seconds.
+#[derive(Clone, Debug)]
+pub struct RemotePolicy {
+    key: Option<Vec<u8>>,
+    allow_unauthenticated: bool,
+    max_events_per_second: Option<u32>,
+    max_batch_len: usize,
+    handshake_timeout: Duration,
+    allowed_keys: Option<KeyCodes>,
+    denied_keys: KeyCodes,
+}
+
+impl Default for RemotePolicy {
+    fn default() -> Self {
+        Self {
+            key: None,
+            allow_unauthenticated: false,
+            max_events_per_second: None,
+            max_batch_len: DEFAULT_MAX_BATCH_LEN,
+            handshake_timeout: DEFAULT_HANDSHAKE_TIMEOUT,
+            allowed_keys: None,
+            denied_keys: vec![KeyCode::KEY_POWER, KeyCode::KEY_SLEEP, KeyCode::KEY_WAKEUP],
+        }
+    }
+}
+
+impl RemotePolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Pre-shared key, clients must prove they know it before sending commands
+    pub fn key(mut self, key: &[u8]) -> Self {
+        self.key = Some(key.to_vec());
+        self
+    }
+
+    // Without a key anyone who can reach the port controls the input. Loopback and Unix sockets are
+    // always allowed, other TCP addresses are refused unless this is set.
+    pub fn allow_unauthenticated(mut self) -> Self {
+        self.allow_unauthenticated = true;
+        self
+    }
+
+    // Per connection, a batch counts as all of its events and one bigger than the limit is rejected
+    pub fn max_events_per_second(mut self, max_events_per_second: u32) -> Self {
+        self.max_events_per_second = Some(max_events_per_second);
+        self
+    }
+
+    // Longer batches are rejected
+    pub fn max_batch_len(mut self, max_batch_len: usize) -> Self {
+        self.max_batch_len = max_batch_len;
+        self
+    }
+
+    pub fn handshake_timeout(mut self, handshake_timeout: Duration) -> Self {
+        self.handshake_timeout = handshake_timeout;
+        self
+    }
+
+    // Only these keys may be pressed or released
+    pub fn allow_keys(mut self, key_codes: &[KeyCode]) -> Self {
+        self.allowed_keys = Some(key_codes.to_vec());
+        self
+    }
+
+    // Replaces the default list of power keys
+    pub fn deny_keys(mut self, key_codes: &[KeyCode]) -> Self {
+        self.denied_keys = key_codes.to_vec();
+        self
+    }
+
+    pub fn is_key_allowed(&self, key_code: KeyCode) -> bool {
+        let allowed = match &self.allowed_keys {
+            Some(allowed_keys) => allowed_keys.contains(&key_code),
+            None => true,
+        };
+        allowed && !self.denied_keys.contains(&key_code)
+    }
+
+    // The whole command is rejected when one of its keys isn't allowed
+    pub fn check_command(&self, command: &RemoteCommand) -> Result<()> {
+        if let RemoteCommand::Batch(batch) = command
+            && batch.len() > self.max_batch_len
+        {
+            bail!("Batch of {} events exceeds the limit of {}", batch.len(), self.max_batch_len);
+        }
+        if let (RemoteCommand::Batch(batch), Some(max_events_per_second)) = (command, self.max_events_per_second)
+            && batch.len() > max_events_per_second as usize
+        {
+            bail!("Batch of {} events exceeds the rate limit of {max_events_per_second} events per second", batch.len());
+        }
+        for event in command.events() {
+            if let InputEvent::Press(key_code) | InputEvent::Release(key_code) = event
+                && !self.is_key_allowed(key_code)
+            {
+                bail!("Key not allowed: {key_code}");
+            }
+        }
+        Ok(())
+    }
+
+    pub fn rate_limiter(&self) -> Option<RateLimiter> {
+        self.max_events_per_second.map(RateLimiter::new)
+    }
+
+    // The handshake has to finish within handshake_timeout, commands may come at any pace
+    pub fn authenticate(&self, connection: &mut RemoteConnection) -> Result<()> {
+        connection.set_read_timeout(Some(self.handshake_timeout))?;
+        self.handshake(connection)?;
+        connection.set_read_timeout(None)
+    }
+
+    fn handshake(&self, connection: &mut RemoteConnection) -> Result<()> {
+        let challenge = match &self.key {
+            Some(_) => {
+                let mut challenge = vec![0u8; CHALLENGE_SIZE];
+                exec_or_eyre!(getrandom::getrandom(&mut challenge))?;
+                Some(challenge)
+            }
+            None => None,
+        };
+        connection.send(&RemoteHandshake::Hello {
+            challenge: challenge.clone(),
+        })?;
+
+        let (Some(key), Some(challenge)) = (&self.key, challenge) else {
+            return Ok(());
+        };
+        let authenticated = match connection.receive::<RemoteHandshake>()? {
+            Some(RemoteHandshake::Auth { mac }) => verify_challenge(key, &challenge, &mac)?,
+            _ => false,
+        };
+        if !authenticated {
+            let _ = connection.send(&RemoteResponse::Error("Authentication failed".to_string()));
+            bail!("Authentication failed");
+        }
+        connection.send(&RemoteResponse::Ok)
+    }
+}
+
+impl InputEmulator {
+    pub fn run_remote_command(&mut self, command: &RemoteCommand) -> Result<()> {
+        match command {
+            RemoteCommand::Press(key_code) => self.press(*key_code),
+            RemoteCommand::Release(key_code) => self.release(*key_code),
+            RemoteCommand::Move(x, y) => self.move_mouse(*x, *y),
+            RemoteCommand::Scroll(x, y) => {
+                if *x != 0 {
+                    self.scroll_x(*x)?;
+                }
+                if *y != 0 {
+                    self.scroll_y(*y)?;
+                }
+                Ok(())
+            }
+            RemoteCommand::Batch(batch) => self.write_buffer(batch),
+        }
+    }
+
+    // Runs commands until the client disconnects. Failed or rejected commands are reported back
+    // and don't end the connection, malformed messages and failed authentication do.
+    // Keys the client left pressed are released however the connection ends.
+    pub fn serve_remote(&mut self, connection: &mut RemoteConnection, policy: &RemotePolicy) -> Result<()> {
+        policy.authenticate(connection)?;
+
+        let mut held = vec![];
+        let result = self.serve_commands(connection, policy, &mut held);
+        let mut released = Ok(());
+        while let Some(key_code) = held.pop() {
+            released = released.and(self.release(key_code));
+        }
+        result.and(released)
+    }
+
+    fn serve_commands(&mut self, connection: &mut RemoteConnection, policy: &RemotePolicy, held: &mut KeyCodes) -> Result<()> {
+        let mut rate_limiter = policy.rate_limiter();
+
+        while let Some(command) = connection.receive::<RemoteCommand>()? {
+            let events = command.events();
+            // Commands without events still cost a token
+            let cost = events.len().max(1);
+            let result = match policy.check_command(&command) {
+                Err(err) => Err(err),
+                Ok(()) if !rate_limiter.as_mut().is_none_or(|limiter| limiter.try_acquire(cost)) => {
+                    Err(err_eyre!("Rate limit exceeded"))
+                }
+                Ok(()) => {
+                    // Presses are tracked before running so a half-applied batch still gets released
+                    for event in &events {
+                        if let InputEvent::Press(key_code) = event
+                            && !held.contains(key_code)
+                        {
+                            held.push(*key_code);
+                        }
+                    }
+                    let result = self.run_remote_command(&command);
+                    if result.is_ok() {
+                        for event in &events {
+                            if let InputEvent::Release(key_code) = event {
+                                held.retain(|held_key| held_key != key_code);
+                            }
+                        }
+                    }
+                    result
+                }
+            };
+
+            let response = match result {
+                Ok(()) => RemoteResponse::Ok,
+                Err(err) => RemoteResponse::Error(err.to_string()),
+            };
+            connection.send(&response)?;
+        }
+        Ok(())
+    }
+}
+
+enum RemoteListener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+pub struct RemoteServer {
+    listener: RemoteListener,
+    encoding: RemoteEncoding,
+    policy: RemotePolicy,
+}
+
+impl RemoteServer {
+    pub fn new() -> Result<Self> {
+        Self::bind_tcp(DEFAULT_REMOTE_ADDRESS, RemoteEncoding::default())
+    }
+
+    // Port 0 picks a free port, see local_addr
+    pub fn bind_tcp(address: impl ToSocketAddrs, encoding: RemoteEncoding) -> Result<Self> {
+        Ok(Self {
+            listener: RemoteListener::Tcp(exec_or_eyre!(TcpListener::bind(address))?),
+            encoding,
+            policy: RemotePolicy::default(),
+        })
+    }
+
+    #[cfg(unix)]
+    pub fn bind_unix(path: impl AsRef<Path>, encoding: RemoteEncoding) -> Result<Self> {
+        Ok(Self {
+            listener: RemoteListener::Unix(exec_or_eyre!(UnixListener::bind(path))?),
+            encoding,
+            policy: RemotePolicy::default(),
+        })
+    }
+
+    pub fn policy(mut self, policy: RemotePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    // None for Unix sockets
+    pub fn local_addr(&self) -> Result<Option<SocketAddr>> {
+        match &self.listener {
+            RemoteListener::Tcp(listener) => exec_or_eyre!(listener.local_addr()).map(Some),
+            #[cfg(unix)]
+            RemoteListener::Unix(_) => Ok(None),
+        }
+    }
+
+    // Fails right away for a TCP address other than loopback without a key, see allow_unauthenticated
+    pub fn accept(&self) -> Result<RemoteConnection> {
+        match &self.listener {
+            RemoteListener::Tcp(listener) => {
+                let address = exec_or_eyre!(listener.local_addr())?;
+                if self.policy.key.is_none() && !self.policy.allow_unauthenticated && !address.ip().is_loopback() {
+                    bail!("Refusing unauthenticated clients on {address}, set a key or bind to loopback");
+                }
+                let (stream, _) = exec_or_eyre!(listener.accept())?;
+                exec_or_eyre!(stream.set_nodelay(true))?;
+                RemoteConnection::from_tcp(stream, self.encoding)
+            }
+            #[cfg(unix)]
+            RemoteListener::Unix(listener) => {
+                let (stream, _) = exec_or_eyre!(listener.accept())?;
+                RemoteConnection::from_unix(stream, self.encoding)
+            }
+        }
+    }
+
+    // Serves one client at a time, forever. A client that breaks the protocol or stalls the
+    // handshake is dropped, an authenticated client keeps the server until it disconnects.
+    pub fn run(&self, emulator: &mut InputEmulator) -> Result<()> {
+        loop {
+            let mut connection = self.accept()?;
+            let _ = emulator.serve_remote(&mut connection, &self.policy);
+        }
+    }
+}
+
+// Same methods as InputEmulator, each one waits for the server to run it.
+// Raw methods are queued locally and sent as one batch by the next finish_operation or command.
+pub struct RemoteInputEmulator {
+    connection: RemoteConnection,
+    pending: InputBatch,
+}
+
+impl RemoteInputEmulator {
+    pub fn new() -> Result<Self> {
+        Self::connect_tcp(DEFAULT_REMOTE_ADDRESS, RemoteEncoding::default(), None)
+    }
+
+    pub fn connect_tcp(address: impl ToSocketAddrs, encoding: RemoteEncoding, key: Option<&[u8]>) -> Result<Self> {
+        let stream = exec_or_eyre!(TcpStream::connect(address))?;
+        exec_or_eyre!(stream.set_nodelay(true))?;
+        Self::from_connection(RemoteConnection::new(stream, encoding), key)
+    }
+
+    #[cfg(unix)]
+    pub fn connect_unix(path: impl AsRef<Path>, encoding: RemoteEncoding, key: Option<&[u8]>) -> Result<Self> {
+        let stream = exec_or_eyre!(UnixStream::connect(path))?;
+        Self::from_connection(RemoteConnection::new(stream, encoding), key)
+    }
+
+    // Runs the handshake, the key is only needed when the server asks for it
+    pub fn from_connection(mut connection: RemoteConnection, key: Option<&[u8]>) -> Result<Self> {
+        let challenge = match connection.receive::<RemoteHandshake>()? {
+            Some(RemoteHandshake::Hello { challenge }) => challenge,
+            _ => bail!("Expected handshake from the server"),
+        };
+
+        if let Some(challenge) = challenge {
+            let Some(key) = key else {
+                bail!("Server requires a key");
+            };
+            let mac = sign_challenge(key, &challenge)?;
+            connection.send(&RemoteHandshake::Auth { mac })?;
+            match connection.receive::<RemoteResponse>()? {
+                Some(RemoteResponse::Ok) => {}
+                Some(RemoteResponse::Error(message)) => bail!("Remote error: {message}"),
+                None => bail!("Connection closed by the server"),
+            }
+        }
+        Ok(Self {
+            connection,
+            pending: InputBatch::new(),
+        })
+    }
+
+    fn send(&mut self, command: &RemoteCommand) -> Result<()> {
+        self.connection.send(command)?;
+        match self.connection.receive::<RemoteResponse>()? {
+            Some(RemoteResponse::Ok) => Ok(()),
+            Some(RemoteResponse::Error(message)) => bail!("Remote error: {message}"),
+            None => bail!("Connection closed by the server"),
+        }
+    }
+
+    // Queued raw events go first
+    pub fn send_command(&mut self, command: &RemoteCommand) -> Result<()> {
+        self.flush_pending()?;
+        self.send(command)
+    }
+
+    fn flush_pending(&mut self) -> Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let batch = std::mem::take(&mut self.pending);
+        self.send(&RemoteCommand::Batch(batch))
+    }
+
+    // Unique methods
+
+    #[inline]
+    pub fn finish_operation_mouse(&mut self) -> Result<()> {
+        self.flush_pending()
+    }
+
+    #[inline]
+    pub fn finish_operation_keyboard(&mut self) -> Result<()> {
+        self.flush_pending()
+    }
+
+    #[inline]
+    pub fn move_mouse_raw_x(&mut self, x: OS_Input_Coord) -> Result<()> {
+        self.pending.move_mouse_x(x);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn move_mouse_raw_y(&mut self, y: OS_Input_Coord) -> Result<()> {
+        self.pending.move_mouse_y(y);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn move_mouse_raw(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        self.pending.move_mouse(x, y);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn gradual_move_mouse_raw(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        self.pending.gradual_move_mouse(x, y);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn scroll_raw_x(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.pending.scroll_x(value);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn scroll_raw_y(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.pending.scroll_y(value);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn gradual_scroll_raw(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        self.pending.gradual_scroll(x, y);
+        Ok(())
+    }
+
+    // Common methods
+
+    #[inline]
+    pub fn write_buffer(&mut self, batch: &InputBatch) -> Result<()> {
+        self.send_command(&RemoteCommand::Batch(batch.clone()))
+    }
+
+    #[inline]
+    pub fn move_mouse_x(&mut self, x: OS_Input_Coord) -> Result<()> {
+        self.move_mouse(x, 0)
+    }
+
+    #[inline]
+    pub fn move_mouse_y(&mut self, y: OS_Input_Coord) -> Result<()> {
+        self.move_mouse(0, y)
+    }
+
+    #[inline]
+    pub fn move_mouse(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        self.send_command(&RemoteCommand::Move(x, y))
+    }
+
+    #[inline]
+    pub fn gradual_move_mouse(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        let mut batch = InputBatch::new();
+        batch.gradual_move_mouse(x, y);
+        self.write_buffer(&batch)
+    }
+
+    #[inline]
+    pub fn scroll_x(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.send_command(&RemoteCommand::Scroll(value, 0))
+    }
+
+    #[inline]
+    pub fn scroll_y(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.send_command(&RemoteCommand::Scroll(0, value))
+    }
+
+    #[inline]
+    pub fn gradual_scroll(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        let mut batch = InputBatch::new();
+        batch.gradual_scroll(x, y);
+        self.write_buffer(&batch)
+    }
+
+    #[inline]
+    pub fn press(&mut self, key_code: KeyCode) -> Result<()> {
+        self.send_command(&RemoteCommand::Press(key_code))
+    }
+
+    #[inline]
+    pub fn release(&mut self, key_code: KeyCode) -> Result<()> {
+        self.send_command(&RemoteCommand::Release(key_code))
+    }
+
+    // Steps are sent one by one, delays and cancellation are handled on this side
+    #[inline]
+    pub fn run_macro(&mut self, input_macro: &Macro, cancel: &MacroCancel) -> Result<MacroStatus> {
+        run_macro_on(self, input_macro, cancel)
+    }
+}
+
+impl MacroTarget for RemoteInputEmulator {
+    #[inline]
+    fn press(&mut self, key_code: KeyCode) -> Result<()> {
+        RemoteInputEmulator::press(self, key_code)
+    }
+
+    #[inline]
+    fn release(&mut self, key_code: KeyCode) -> Result<()> {
+        RemoteInputEmulator::release(self, key_code)
+    }
+
+    #[inline]
+    fn move_mouse(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        RemoteInputEmulator::move_mouse(self, x, y)
+    }
+
+    #[inline]
+    fn scroll_x(&mut self, value: OS_Input_Coord) -> Result<()> {
+        RemoteInputEmulator::scroll_x(self, value)
+    }
+
+    #[inline]
+    fn scroll_y(&mut self, value: OS_Input_Coord) -> Result<()> {
+        RemoteInputEmulator::scroll_y(self, value)
+    }
+}
+
+#[cfg(all(test, feature = "use_uinput"))]
+mod tests {
+    use std::io::Read;
+    use std::thread;
+    use crate::key_codes::*;
+    use crate::uinput::tests::decode_events;
+    use crate::uinput::EventParams;
+    use super::*;
+
+    type Served = (Result<()>, Vec<EventParams>);
+
+    // Serves one client on a loopback port, returns the evdev events the emulator wrote
+    fn serve_once(server: RemoteServer) -> thread::JoinHandle<Served> {
+        thread::spawn(move || {
+            let (mut reader, writer) = std::io::pipe().unwrap();
+            let mut emulator = InputEmulator::from_writer(writer);
+            let result = server.accept().and_then(|mut connection| emulator.serve_remote(&mut connection, &server.policy));
+            drop(emulator);
+            let mut written = vec![];
+            reader.read_to_end(&mut written).unwrap();
+            let events = decode_events(&written)
+                .into_iter()
+                .filter(|(event_type, _, _)| *event_type != EV_SYN)
+                .collect();
+            (result, events)
+        })
+    }
+
+    fn loopback(encoding: RemoteEncoding, policy: RemotePolicy) -> (RemoteServer, SocketAddr) {
+        let server = RemoteServer::bind_tcp("127.0.0.1:0", encoding).unwrap().policy(policy);
+        let address = server.local_addr().unwrap().unwrap();
+        (server, address)
+    }
+
+    fn round_trip(encoding: RemoteEncoding) {
+        let (server, address) = loopback(encoding, RemotePolicy::new());
+        let handle = serve_once(server);
+
+        let mut client = RemoteInputEmulator::connect_tcp(address, encoding, None).unwrap();
+        client.press(KeyCode::KEY_LEFTCTRL).unwrap();
+        client.move_mouse_raw(3, 4).unwrap();
+        client.scroll_raw_y(-1).unwrap();
+        client.finish_operation_mouse().unwrap();
+        client.move_mouse_raw_x(2).unwrap();
+        // Queued moves go out before the release
+        client.release(KeyCode::KEY_LEFTCTRL).unwrap();
+        let mut input_macro = Macro::new();
+        input_macro.tap(KeyCode::KEY_A).type_text("B");
+        assert_eq!(client.run_macro(&input_macro, &MacroCancel::new()).unwrap(), MacroStatus::Completed);
+        drop(client);
+
+        let (result, events) = handle.join().unwrap();
+        result.unwrap();
+        assert_eq!(events, [
+            (EV_KEY, KEY_LEFTCTRL, 1),
+            (EV_REL, REL_X, 3),
+            (EV_REL, REL_Y, -4),
+            (EV_REL, REL_WHEEL, -1),
+            (EV_REL, REL_X, 2),
+            (EV_KEY, KEY_LEFTCTRL, 0),
+            (EV_KEY, KEY_A, 1),
+            (EV_KEY, KEY_A, 0),
+            (EV_KEY, KEY_LEFTSHIFT, 1),
+            (EV_KEY, KEY_B, 1),
+            (EV_KEY, KEY_B, 0),
+            (EV_KEY, KEY_LEFTSHIFT, 0),
+        ]);
+    }
+
+    #[test]
+    fn json_lines_round_trip() {
+        round_trip(RemoteEncoding::JsonLines);
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        round_trip(RemoteEncoding::Binary);
+    }
+
+    #[test]
+    fn wrong_key_is_rejected() {
+        let (server, address) = loopback(RemoteEncoding::JsonLines, RemotePolicy::new().key(b"secret"));
+        let handle = serve_once(server);
+
+        let err = RemoteInputEmulator::connect_tcp(address, RemoteEncoding::JsonLines, Some(b"wrong")).err().unwrap();
+        assert_eq!(err.to_string(), "Remote error: Authentication failed");
+        let (result, events) = handle.join().unwrap();
+        assert!(result.is_err());
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn right_key_is_accepted() {
+        let (server, address) = loopback(RemoteEncoding::Binary, RemotePolicy::new().key(b"secret"));
+        let handle = serve_once(server);
+
+        let mut client = RemoteInputEmulator::connect_tcp(address, RemoteEncoding::Binary, Some(b"secret")).unwrap();
+        client.press(KeyCode::KEY_A).unwrap();
+        client.release(KeyCode::KEY_A).unwrap();
+        drop(client);
+        let (result, events) = handle.join().unwrap();
+        result.unwrap();
+        assert_eq!(events, [(EV_KEY, KEY_A, 1), (EV_KEY, KEY_A, 0)]);
+    }
+
+    #[test]
+    fn stalled_handshake_times_out() {
+        let policy = RemotePolicy::new().key(b"secret").handshake_timeout(Duration::from_millis(50));
+        let (server, address) = loopback(RemoteEncoding::JsonLines, policy);
+        let handle = serve_once(server);
+
+        // Connected but never answers the challenge
+        let _stream = TcpStream::connect(address).unwrap();
+        let (result, _) = handle.join().unwrap();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn unauthenticated_tcp_needs_loopback() {
+        let server = RemoteServer::bind_tcp("0.0.0.0:0", RemoteEncoding::JsonLines).unwrap();
+        let err = server.accept().err().unwrap();
+        assert!(err.to_string().starts_with("Refusing unauthenticated clients"));
+    }
+
+    #[test]
+    fn denied_keys_are_rejected() {
+        let (server, address) = loopback(RemoteEncoding::JsonLines, RemotePolicy::new());
+        let handle = serve_once(server);
+
+        let mut client = RemoteInputEmulator::connect_tcp(address, RemoteEncoding::JsonLines, None).unwrap();
+        let err = client.press(KeyCode::KEY_POWER).unwrap_err();
+        assert_eq!(err.to_string(), "Remote error: Key not allowed: KEY_POWER");
+        let mut batch = InputBatch::new();
+        batch.press(KeyCode::KEY_A).release(KeyCode::KEY_A).press(KeyCode::KEY_POWER);
+        assert!(client.write_buffer(&batch).is_err());
+        // The connection stays usable
+        client.press(KeyCode::KEY_A).unwrap();
+        client.release(KeyCode::KEY_A).unwrap();
+        drop(client);
+
+        let (result, events) = handle.join().unwrap();
+        result.unwrap();
+        assert_eq!(events, [(EV_KEY, KEY_A, 1), (EV_KEY, KEY_A, 0)]);
+    }
+
+    #[test]
+    fn rate_limit_counts_events() {
+        let (server, address) = loopback(RemoteEncoding::Binary, RemotePolicy::new().max_events_per_second(2));
+        let handle = serve_once(server);
+
+        let mut client = RemoteInputEmulator::connect_tcp(address, RemoteEncoding::Binary, None).unwrap();
+        // A batch can't get around the limit
+        let err = client.gradual_move_mouse(500, 0).unwrap_err();
+        assert_eq!(err.to_string(), "Remote error: Batch of 500 events exceeds the rate limit of 2 events per second");
+        client.move_mouse(1, 0).unwrap();
+        client.move_mouse(1, 0).unwrap();
+        let err = client.move_mouse(1, 0).unwrap_err();
+        assert_eq!(err.to_string(), "Remote error: Rate limit exceeded");
+        drop(client);
+
+        let (result, events) = handle.join().unwrap();
+        result.unwrap();
+        let moves = events.iter().filter(|(event_type, code, _)| (*event_type, *code) == (EV_REL, REL_X)).count();
+        assert_eq!(moves, 2);
+    }
+
+    #[test]
+    fn long_batches_are_rejected() {
+        let (server, address) = loopback(RemoteEncoding::JsonLines, RemotePolicy::new().max_batch_len(100));
+        let handle = serve_once(server);
+
+        let mut client = RemoteInputEmulator::connect_tcp(address, RemoteEncoding::JsonLines, None).unwrap();
+        let err = client.gradual_move_mouse(500, 0).unwrap_err();
+        assert_eq!(err.to_string(), "Remote error: Batch of 500 events exceeds the limit of 100");
+        client.gradual_move_mouse(100, 0).unwrap();
+        drop(client);
+
+        let (result, events) = handle.join().unwrap();
+        result.unwrap();
+        assert_eq!(events.len(), 100);
+    }
+
+    #[test]
+    fn keys_released_on_disconnect() {
+        let (server, address) = loopback(RemoteEncoding::JsonLines, RemotePolicy::new());
+        let handle = serve_once(server);
+
+        let mut client = RemoteInputEmulator::connect_tcp(address, RemoteEncoding::JsonLines, None).unwrap();
+        client.press(KeyCode::KEY_LEFTCTRL).unwrap();
+        client.press(KeyCode::KEY_A).unwrap();
+        drop(client);
+
+        let (result, events) = handle.join().unwrap();
+        result.unwrap();
+        assert_eq!(events, [
+            (EV_KEY, KEY_LEFTCTRL, 1),
+            (EV_KEY, KEY_A, 1),
+            (EV_KEY, KEY_A, 0),
+            (EV_KEY, KEY_LEFTCTRL, 0),
+        ]);
+    }
+}
@@ -0,0 +1,263 @@
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use color_eyre::eyre::bail;
+use color_eyre::Result;
+use crate::exec_or_eyre;
+
+// RFC 6143
+pub const RFB_DEFAULT_PORT: u16 = 5900;
+const PROTOCOL_VERSION_3_3: &[u8; 12] = b"RFB 003.003\n";
+const PROTOCOL_VERSION_3_7: &[u8; 12] = b"RFB 003.007\n";
+const PROTOCOL_VERSION_3_8: &[u8; 12] = b"RFB 003.008\n";
+const SECURITY_INVALID: u32 = 0;
+const SECURITY_NONE: u8 = 1;
+const PIXEL_FORMAT_SIZE: usize = 16;
+// Longer server strings are refused rather than allocated
+const MAX_STRING_SIZE: u32 = 1 << 16;
+
+const MESSAGE_KEY_EVENT: u8 = 4;
+const MESSAGE_POINTER_EVENT: u8 = 5;
+
+// Pointer button mask bits
+pub const RFB_BUTTON_LEFT: u8 = 1 << 0;
+pub const RFB_BUTTON_MIDDLE: u8 = 1 << 1;
+pub const RFB_BUTTON_RIGHT: u8 = 1 << 2;
+pub const RFB_WHEEL_UP: u8 = 1 << 3;
+pub const RFB_WHEEL_DOWN: u8 = 1 << 4;
+pub const RFB_WHEEL_LEFT: u8 = 1 << 5;
+pub const RFB_WHEEL_RIGHT: u8 = 1 << 6;
+
+pub trait RfbStream: Read + Write + Send {}
+
+impl<T: Read + Write + Send> RfbStream for T {}
+
+// Only the client to server direction is used after the handshake. Without
+// FramebufferUpdateRequest messages the server has next to nothing to send back.
+pub struct RfbConnection {
+    stream: Box<dyn RfbStream>,
+    width: u16,
+    height: u16,
+    name: String,
+}
+
+fn read_bytes<const N: usize>(stream: &mut impl Read) -> Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    exec_or_eyre!(stream.read_exact(&mut bytes))?;
+    Ok(bytes)
+}
+
+fn read_u32(stream: &mut impl Read) -> Result<u32> {
+    Ok(u32::from_be_bytes(read_bytes(stream)?))
+}
+
+fn read_string(stream: &mut impl Read) -> Result<String> {
+    let length = read_u32(stream)?;
+    if length > MAX_STRING_SIZE {
+        bail!("Server string of {length} bytes is too long");
+    }
+    let mut bytes = vec![0u8; length as usize];
+    exec_or_eyre!(stream.read_exact(&mut bytes))?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+impl RfbConnection {
+    pub fn connect(address: impl ToSocketAddrs, shared: bool) -> Result<Self> {
+        let stream = exec_or_eyre!(TcpStream::connect(address))?;
+        exec_or_eyre!(stream.set_nodelay(true))?;
+        Self::handshake(stream, shared)
+    }
+
+    // Protocol 3.3, 3.7 and 3.8 with the None security type. Works over any stream, e.g. one end of a socket pair.
+    pub fn handshake<S: RfbStream + 'static>(mut stream: S, shared: bool) -> Result<Self> {
+        let server_version: [u8; 12] = read_bytes(&mut stream)?;
+        if !server_version.starts_with(b"RFB ") {
+            bail!("Not an RFB server");
+        }
+        // Newer versions get 3.8, unknown older ones are treated as 3.3
+        let version = match server_version.as_slice() {
+            version if version >= PROTOCOL_VERSION_3_8.as_slice() => PROTOCOL_VERSION_3_8,
+            version if version == PROTOCOL_VERSION_3_7.as_slice() => PROTOCOL_VERSION_3_7,
+            _ => PROTOCOL_VERSION_3_3,
+        };
+        exec_or_eyre!(stream.write_all(version))?;
+
+        if version == PROTOCOL_VERSION_3_3 {
+            match read_u32(&mut stream)? {
+                SECURITY_INVALID => bail!("Connection refused: {}", read_string(&mut stream)?),
+                security_type if security_type == SECURITY_NONE as u32 => {}
+                security_type => bail!("Unsupported security type {security_type}, only None is supported"),
+            }
+        } else {
+            let [count] = read_bytes(&mut stream)?;
+            if count == 0 {
+                bail!("Connection refused: {}", read_string(&mut stream)?);
+            }
+            let mut security_types = vec![0u8; count as usize];
+            exec_or_eyre!(stream.read_exact(&mut security_types))?;
+            if !security_types.contains(&SECURITY_NONE) {
+                bail!("Server offers security types {security_types:?}, only None is supported");
+            }
+            exec_or_eyre!(stream.write_all(&[SECURITY_NONE]))?;
+
+            // 3.7 skips the result for None
+            if version == PROTOCOL_VERSION_3_8 && read_u32(&mut stream)? != 0 {
+                bail!("Security handshake failed: {}", read_string(&mut stream)?);
+            }
+        }
+
+        exec_or_eyre!(stream.write_all(&[shared as u8]))?;
+
+        let [width_high, width_low, height_high, height_low] = read_bytes(&mut stream)?;
+        let _pixel_format: [u8; PIXEL_FORMAT_SIZE] = read_bytes(&mut stream)?;
+        let name = read_string(&mut stream)?;
+
+        Ok(Self {
+            stream: Box::new(stream),
+            width: u16::from_be_bytes([width_high, width_low]),
+            height: u16::from_be_bytes([height_high, height_low]),
+            name,
+        })
+    }
+
+    #[inline]
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    #[inline]
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn encode_key_event(keysym: u32, down: bool) -> [u8; 8] {
+        let [a, b, c, d] = keysym.to_be_bytes();
+        [MESSAGE_KEY_EVENT, down as u8, 0, 0, a, b, c, d]
+    }
+
+    pub fn encode_pointer_event(button_mask: u8, x: u16, y: u16) -> [u8; 6] {
+        let [x_high, x_low] = x.to_be_bytes();
+        let [y_high, y_low] = y.to_be_bytes();
+        [MESSAGE_POINTER_EVENT, button_mask, x_high, x_low, y_high, y_low]
+    }
+
+    #[inline]
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        exec_or_eyre!(self.stream.write_all(bytes))
+    }
+
+    #[inline]
+    pub fn key_event(&mut self, keysym: u32, down: bool) -> Result<()> {
+        self.write_bytes(&Self::encode_key_event(keysym, down))
+    }
+
+    #[inline]
+    pub fn pointer_event(&mut self, button_mask: u8, x: u16, y: u16) -> Result<()> {
+        self.write_bytes(&Self::encode_pointer_event(button_mask, x, y))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::net::TcpListener;
+    use std::thread;
+    use super::*;
+
+    // What the stub server saw: the client's version, its shared flag and everything sent after ServerInit
+    pub(crate) struct StubSession {
+        pub(crate) version: [u8; 12],
+        pub(crate) shared: u8,
+        pub(crate) messages: Vec<u8>,
+    }
+
+    // Serves one client on a loopback port with the None security type and an 800x600 framebuffer
+    pub(crate) fn stub_server(server_version: &'static [u8; 12]) -> (String, thread::JoinHandle<StubSession>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(server_version).unwrap();
+            let version: [u8; 12] = read_bytes(&mut stream).unwrap();
+            if &version == PROTOCOL_VERSION_3_3 {
+                stream.write_all(&(SECURITY_NONE as u32).to_be_bytes()).unwrap();
+            } else {
+                stream.write_all(&[2, 2, SECURITY_NONE]).unwrap();
+                let [chosen] = read_bytes(&mut stream).unwrap();
+                assert_eq!(chosen, SECURITY_NONE);
+                if &version == PROTOCOL_VERSION_3_8 {
+                    stream.write_all(&0u32.to_be_bytes()).unwrap();
+                }
+            }
+            let [shared] = read_bytes(&mut stream).unwrap();
+
+            let mut server_init = vec![0x03, 0x20, 0x02, 0x58];
+            server_init.extend_from_slice(&[0; PIXEL_FORMAT_SIZE]);
+            server_init.extend_from_slice(&4u32.to_be_bytes());
+            server_init.extend_from_slice(b"stub");
+            stream.write_all(&server_init).unwrap();
+
+            let mut messages = vec![];
+            stream.read_to_end(&mut messages).unwrap();
+            StubSession { version, shared, messages }
+        });
+        (address, handle)
+    }
+
+    #[test]
+    fn handshake_3_3() {
+        let (address, handle) = stub_server(PROTOCOL_VERSION_3_3);
+        let connection = RfbConnection::connect(address.as_str(), false).unwrap();
+        assert_eq!((connection.width(), connection.height(), connection.name()), (800, 600, "stub"));
+        drop(connection);
+
+        let session = handle.join().unwrap();
+        assert_eq!(&session.version, PROTOCOL_VERSION_3_3);
+        assert_eq!(session.shared, 0);
+        assert!(session.messages.is_empty());
+    }
+
+    #[test]
+    fn handshake_3_8_and_messages() {
+        let (address, handle) = stub_server(PROTOCOL_VERSION_3_8);
+        let mut connection = RfbConnection::connect(address.as_str(), true).unwrap();
+        connection.key_event(0xff0d, true).unwrap();
+        connection.pointer_event(RFB_BUTTON_LEFT | RFB_WHEEL_UP, 0x0123, 0x0456).unwrap();
+        drop(connection);
+
+        let session = handle.join().unwrap();
+        assert_eq!(&session.version, PROTOCOL_VERSION_3_8);
+        assert_eq!(session.shared, 1);
+        assert_eq!(session.messages, [
+            4, 1, 0, 0, 0x00, 0x00, 0xff, 0x0d,
+            5, 0b1001, 0x01, 0x23, 0x04, 0x56,
+        ]);
+    }
+
+    #[test]
+    fn newer_servers_get_3_8() {
+        let (address, handle) = stub_server(b"RFB 004.001\n");
+        // The stub answers whatever the client picked
+        RfbConnection::connect(address.as_str(), true).unwrap();
+        assert_eq!(&handle.join().unwrap().version, PROTOCOL_VERSION_3_8);
+    }
+
+    #[test]
+    fn refused_connection_reports_reason() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(PROTOCOL_VERSION_3_8).unwrap();
+            let _version: [u8; 12] = read_bytes(&mut stream).unwrap();
+            stream.write_all(&[0, 0, 0, 0, 4]).unwrap();
+            stream.write_all(b"busy").unwrap();
+        });
+        let err = RfbConnection::connect(address, true).err().unwrap();
+        assert_eq!(err.to_string(), "Connection refused: busy");
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,243 @@
+use std::time::{Duration, Instant};
+use color_eyre::eyre::bail;
+use color_eyre::Result;
+use crate::{InputBatch, InputEmulator, KeyCode, OS_Input_Coord};
+
+// USB full speed polling interval
+pub const DEFAULT_OUTPUT_RATE: u32 = 1000;
+
+// Sums moves and scrolls over a frame and sends them as one report per frame, at a fixed rate.
+// There is no timer: pending motion only goes out from a call made after the frame ends, so the
+// caller's loop must call poll() at least once per period, or sleep for time_until_flush() when
+// it has nothing else to do, otherwise the last move of a burst waits for the next input.
+// Keys are sent right away, after the pending motion, so clicks land where the pointer was moved to.
+// Dropping the scheduler or into_inner() sends what's still pending.
+pub struct OutputScheduler {
+    // Only None after into_inner()
+    emulator: Option<InputEmulator>,
+    period: Duration,
+    next_flush: Instant,
+    pending_move: (OS_Input_Coord, OS_Input_Coord),
+    pending_scroll: (OS_Input_Coord, OS_Input_Coord),
+}
+
+impl OutputScheduler {
+    pub fn new(emulator: InputEmulator, rate_hz: u32) -> Result<Self> {
+        if rate_hz == 0 {
+            bail!("Output rate must be at least 1 Hz");
+        }
+        let period = Duration::from_secs(1) / rate_hz;
+        Ok(Self {
+            emulator: Some(emulator),
+            period,
+            next_flush: Instant::now() + period,
+            pending_move: (0, 0),
+            pending_scroll: (0, 0),
+        })
+    }
+
+    #[inline]
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    #[inline]
+    pub fn emulator(&mut self) -> &mut InputEmulator {
+        self.emulator.as_mut().unwrap()
+    }
+
+    // Pending motion is sent first
+    pub fn into_inner(mut self) -> Result<InputEmulator> {
+        self.flush()?;
+        Ok(self.emulator.take().unwrap())
+    }
+
+    #[inline]
+    pub fn has_pending(&self) -> bool {
+        self.pending_move != (0, 0) || self.pending_scroll != (0, 0)
+    }
+
+    #[inline]
+    pub fn time_until_flush(&self) -> Duration {
+        self.next_flush.saturating_duration_since(Instant::now())
+    }
+
+    // Sends what's pending regardless of the frame
+    pub fn flush(&mut self) -> Result<()> {
+        if !self.has_pending() {
+            return Ok(());
+        }
+        let (x, y) = std::mem::take(&mut self.pending_move);
+        let (scroll_x, scroll_y) = std::mem::take(&mut self.pending_scroll);
+        if (x, y) != (0, 0) {
+            self.emulator().move_mouse_raw(x, y)?;
+        }
+        if scroll_x != 0 {
+            self.emulator().scroll_raw_x(scroll_x)?;
+        }
+        if scroll_y != 0 {
+            self.emulator().scroll_raw_y(scroll_y)?;
+        }
+        self.emulator().finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn poll(&mut self) -> Result<()> {
+        self.poll_at(Instant::now())
+    }
+
+    // Ends the frame if `now` is past it. Deadlines stay on the grid, after a stall the next
+    // frame starts from `now` instead of flushing several times in a row.
+    pub fn poll_at(&mut self, now: Instant) -> Result<()> {
+        if now < self.next_flush {
+            return Ok(());
+        }
+        self.next_flush += self.period;
+        if self.next_flush <= now {
+            self.next_flush = now + self.period;
+        }
+        self.flush()
+    }
+
+    #[inline]
+    pub fn move_mouse(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        self.pending_move.0 = self.pending_move.0.saturating_add(x);
+        self.pending_move.1 = self.pending_move.1.saturating_add(y);
+        self.poll()
+    }
+
+    #[inline]
+    pub fn move_mouse_x(&mut self, x: OS_Input_Coord) -> Result<()> {
+        self.move_mouse(x, 0)
+    }
+
+    #[inline]
+    pub fn move_mouse_y(&mut self, y: OS_Input_Coord) -> Result<()> {
+        self.move_mouse(0, y)
+    }
+
+    #[inline]
+    pub fn scroll(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        self.pending_scroll.0 = self.pending_scroll.0.saturating_add(x);
+        self.pending_scroll.1 = self.pending_scroll.1.saturating_add(y);
+        self.poll()
+    }
+
+    #[inline]
+    pub fn scroll_x(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.scroll(value, 0)
+    }
+
+    #[inline]
+    pub fn scroll_y(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.scroll(0, value)
+    }
+
+    #[inline]
+    pub fn press(&mut self, key_code: KeyCode) -> Result<()> {
+        self.flush()?;
+        self.emulator().press(key_code)
+    }
+
+    #[inline]
+    pub fn release(&mut self, key_code: KeyCode) -> Result<()> {
+        self.flush()?;
+        self.emulator().release(key_code)
+    }
+
+    // Batches are sent as they are, after the pending motion
+    pub fn write_buffer(&mut self, batch: &InputBatch) -> Result<()> {
+        self.flush()?;
+        self.emulator().write_buffer(batch)
+    }
+}
+
+impl Drop for OutputScheduler {
+    fn drop(&mut self) {
+        if self.emulator.is_some() {
+            let _ = self.flush();
+        }
+    }
+}
+
+#[cfg(all(test, feature = "use_uinput"))]
+mod tests {
+    use std::io::Read;
+    use crate::key_codes::{EV_KEY, EV_REL, EV_SYN, KEY_A, REL_WHEEL, REL_X, REL_Y, SYN_REPORT};
+    use crate::uinput::tests::decode_events;
+    use crate::uinput::EventParams;
+    use super::*;
+
+    const SYN: EventParams = (EV_SYN, SYN_REPORT, 0);
+
+    fn piped() -> (std::io::PipeReader, OutputScheduler) {
+        let (reader, writer) = std::io::pipe().unwrap();
+        let scheduler = OutputScheduler::new(InputEmulator::from_writer(writer), 1).unwrap();
+        (reader, scheduler)
+    }
+
+    fn written(mut reader: std::io::PipeReader) -> Vec<EventParams> {
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes).unwrap();
+        decode_events(&bytes)
+    }
+
+    #[test]
+    fn moves_coalesce_until_the_frame_ends() {
+        let (reader, mut scheduler) = piped();
+        let deadline = scheduler.next_flush;
+        // 1 s frames, the moves land inside the first one
+        scheduler.move_mouse(1, 2).unwrap();
+        scheduler.move_mouse(3, -1).unwrap();
+        scheduler.scroll_y(1).unwrap();
+        scheduler.poll_at(deadline - Duration::from_millis(1)).unwrap();
+        assert!(scheduler.has_pending());
+
+        scheduler.poll_at(deadline).unwrap();
+        assert!(!scheduler.has_pending());
+        // Nothing new, nothing sent
+        scheduler.poll_at(deadline + scheduler.period()).unwrap();
+        drop(scheduler);
+
+        assert_eq!(written(reader), [(EV_REL, REL_X, 4), (EV_REL, REL_Y, -1), (EV_REL, REL_WHEEL, 1), SYN]);
+    }
+
+    #[test]
+    fn deadlines_skip_ahead_after_a_stall() {
+        let (_reader, mut scheduler) = piped();
+        let stalled = Instant::now() + scheduler.period() * 5;
+        scheduler.poll_at(stalled).unwrap();
+        assert_eq!(scheduler.next_flush, stalled + scheduler.period());
+    }
+
+    #[test]
+    fn keys_wait_for_pending_motion() {
+        let (reader, mut scheduler) = piped();
+        scheduler.move_mouse_x(5).unwrap();
+        scheduler.press(KeyCode::KEY_A).unwrap();
+        scheduler.move_mouse_x(2).unwrap();
+        scheduler.release(KeyCode::KEY_A).unwrap();
+        drop(scheduler);
+
+        assert_eq!(written(reader), [
+            (EV_REL, REL_X, 5), (EV_REL, REL_Y, 0), SYN,
+            (EV_KEY, KEY_A, 1), SYN,
+            (EV_REL, REL_X, 2), (EV_REL, REL_Y, 0), SYN,
+            (EV_KEY, KEY_A, 0), SYN,
+        ]);
+    }
+
+    #[test]
+    fn drop_sends_the_last_move() {
+        let (reader, mut scheduler) = piped();
+        scheduler.move_mouse_y(3).unwrap();
+        drop(scheduler);
+        // uinput writes both axes of a move
+        assert_eq!(written(reader), [(EV_REL, REL_X, 0), (EV_REL, REL_Y, -3), SYN]);
+
+        let (reader, mut scheduler) = piped();
+        scheduler.move_mouse_x(1).unwrap();
+        drop(scheduler.into_inner().unwrap());
+        assert_eq!(written(reader), [(EV_REL, REL_X, 1), (EV_REL, REL_Y, 0), SYN]);
+    }
+}
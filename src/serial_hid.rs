@@ -0,0 +1,191 @@
+#[cfg(feature = "serial_hid")]
+use std::fs::{File, OpenOptions};
+#[cfg(feature = "serial_hid")]
+use std::os::unix::fs::OpenOptionsExt;
+#[cfg(feature = "serial_hid")]
+use std::os::unix::io::AsRawFd;
+#[cfg(feature = "serial_hid")]
+use std::path::Path;
+
+use color_eyre::Result;
+use crate::hid_report::{KeyboardReport, MouseFrame, MouseReport};
+use crate::{HidUsage, KeyCode, OS_Input_Coord};
+
+#[cfg(feature = "serial_hid")]
+use color_eyre::eyre::bail;
+#[cfg(feature = "serial_hid")]
+use crate::{err_eyre, exec_or_eyre};
+
+// CH9329 frame: 0x57 0xab, address, command, data length, data, checksum (sum of all previous bytes)
+const CH9329_HEADER: [u8; 2] = [0x57, 0xab];
+pub const CH9329_DEFAULT_ADDRESS: u8 = 0x00;
+const CH9329_CMD_SEND_KB_GENERAL_DATA: u8 = 0x02;
+const CH9329_CMD_SEND_MS_REL_DATA: u8 = 0x05;
+// First data byte of a relative mouse packet
+const CH9329_MOUSE_REL: u8 = 0x01;
+
+// Text protocol, one command per line, values in decimal, y grows downwards like in the HID report:
+//   kd <usage>      key down, HID keyboard page usage
+//   ku <usage>      key up
+//   md <mask>       mouse buttons down, 1 left, 2 right, 4 middle, same as Arduino's Mouse.press
+//   mu <mask>       mouse buttons up
+//   mm <x> <y>      relative move, each value within -127..=127
+//   mw <wheel>      vertical scroll, within -127..=127
+//   ra              release all keys and buttons
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum SerialHidProtocol {
+    Ch9329 { address: u8 },
+    Text,
+}
+
+impl Default for SerialHidProtocol {
+    fn default() -> Self {
+        Self::Ch9329 {
+            address: CH9329_DEFAULT_ADDRESS,
+        }
+    }
+}
+
+pub fn ch9329_frame(address: u8, command: u8, data: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(CH9329_HEADER.len() + 3 + data.len() + 1);
+    frame.extend_from_slice(&CH9329_HEADER);
+    frame.extend_from_slice(&[address, command, data.len() as u8]);
+    frame.extend_from_slice(data);
+    let checksum = frame.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+    frame.push(checksum);
+    frame
+}
+
+// Keeps the keyboard and mouse state the bridge reports and turns each operation into bytes for the serial line
+#[derive(Clone, Default, Debug)]
+pub struct SerialHidEncoder {
+    protocol: SerialHidProtocol,
+    keyboard_report: KeyboardReport,
+    mouse_report: MouseReport,
+}
+
+impl SerialHidEncoder {
+    pub fn new(protocol: SerialHidProtocol) -> Self {
+        Self {
+            protocol,
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    pub fn protocol(&self) -> SerialHidProtocol {
+        self.protocol
+    }
+
+    fn keyboard_bytes(&self, text: String) -> Vec<u8> {
+        match self.protocol {
+            SerialHidProtocol::Ch9329 { address } => {
+                ch9329_frame(address, CH9329_CMD_SEND_KB_GENERAL_DATA, &self.keyboard_report.to_bytes())
+            }
+            SerialHidProtocol::Text => text.into_bytes(),
+        }
+    }
+
+    fn mouse_bytes(&self, frame: &MouseFrame, text: String) -> Vec<u8> {
+        match self.protocol {
+            SerialHidProtocol::Ch9329 { address } => ch9329_frame(
+                address,
+                CH9329_CMD_SEND_MS_REL_DATA,
+                &[CH9329_MOUSE_REL, frame.buttons, frame.x as u8, frame.y as u8, frame.wheel as u8],
+            ),
+            SerialHidProtocol::Text => text.into_bytes(),
+        }
+    }
+
+    pub fn press(&mut self, key_code: KeyCode) -> Result<Vec<u8>> {
+        if key_code.is_mouse_button() {
+            let mask = MouseReport::button_bit(key_code)?;
+            let frame = self.mouse_report.press(key_code)?;
+            return Ok(self.mouse_bytes(&frame, format!("md {mask}\n")));
+        }
+        let HidUsage(usage) = HidUsage::try_from(key_code)?;
+        self.keyboard_report.press(key_code)?;
+        Ok(self.keyboard_bytes(format!("kd {usage}\n")))
+    }
+
+    pub fn release(&mut self, key_code: KeyCode) -> Result<Vec<u8>> {
+        if key_code.is_mouse_button() {
+            let mask = MouseReport::button_bit(key_code)?;
+            let frame = self.mouse_report.release(key_code)?;
+            return Ok(self.mouse_bytes(&frame, format!("mu {mask}\n")));
+        }
+        let HidUsage(usage) = HidUsage::try_from(key_code)?;
+        self.keyboard_report.release(key_code)?;
+        Ok(self.keyboard_bytes(format!("ku {usage}\n")))
+    }
+
+    pub fn release_all(&mut self) -> Vec<u8> {
+        self.keyboard_report.release_all();
+        let frame = self.mouse_report.release_all();
+        match self.protocol {
+            SerialHidProtocol::Ch9329 { .. } => {
+                let mut bytes = self.keyboard_bytes(String::new());
+                bytes.extend(self.mouse_bytes(&frame, String::new()));
+                bytes
+            }
+            SerialHidProtocol::Text => b"ra\n".to_vec(),
+        }
+    }
+
+    pub fn move_mouse(&self, x: OS_Input_Coord, y: OS_Input_Coord) -> Vec<u8> {
+        self.mouse_report
+            .move_mouse(x, y)
+            .iter()
+            .flat_map(|frame| self.mouse_bytes(frame, format!("mm {} {}\n", frame.x, frame.y)))
+            .collect()
+    }
+
+    // Neither protocol has a horizontal wheel, so only y is sent
+    pub fn scroll(&self, y: OS_Input_Coord) -> Vec<u8> {
+        self.mouse_report
+            .scroll(0, y)
+            .iter()
+            .flat_map(|frame| self.mouse_bytes(frame, format!("mw {}\n", frame.wheel)))
+            .collect()
+    }
+}
+
+#[cfg(feature = "serial_hid")]
+fn baud_rate_constant(baud_rate: u32) -> Result<libc::speed_t> {
+    Ok(match baud_rate {
+        1200 => libc::B1200,
+        2400 => libc::B2400,
+        4800 => libc::B4800,
+        9600 => libc::B9600,
+        19200 => libc::B19200,
+        38400 => libc::B38400,
+        57600 => libc::B57600,
+        115200 => libc::B115200,
+        230400 => libc::B230400,
+        _ => bail!("Unsupported baud rate: {baud_rate}"),
+    })
+}
+
+// Opens a tty in raw 8N1 mode. The bridge's replies are never read, the driver discards them once its buffer is full.
+#[cfg(feature = "serial_hid")]
+pub fn open_serial_port(path: impl AsRef<Path>, baud_rate: u32) -> Result<File> {
+    let speed = baud_rate_constant(baud_rate)?;
+    let file = exec_or_eyre!(OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY)
+        .open(path))?;
+    let fd = file.as_raw_fd();
+
+    let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(fd, &mut termios) } < 0 {
+        return Err(err_eyre!(std::io::Error::last_os_error()));
+    }
+    unsafe { libc::cfmakeraw(&mut termios) };
+    termios.c_cflag |= libc::CLOCAL | libc::CREAD;
+    termios.c_cflag &= !(libc::CSTOPB | libc::CRTSCTS);
+    if unsafe { libc::cfsetspeed(&mut termios, speed) } < 0 || unsafe { libc::tcsetattr(fd, libc::TCSANOW, &termios) } < 0 {
+        return Err(err_eyre!(std::io::Error::last_os_error()));
+    }
+    Ok(file)
+}
@@ -0,0 +1,182 @@
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use color_eyre::Result;
+use crate::{InputBatch, InputEmulator, KeyCode, Macro, MacroCancel, MacroStatus, OS_Input_Coord};
+
+// These backends only hold files, sockets and protocol connections, so their InputEmulator is
+// Send and SharedInputEmulator is Send + Sync. Checked at compile time below.
+// mki, hidg, enigo and tfc make no such promise: tfc and enigo can hold X11/xdo handles tied to
+// the creating thread. Use AsyncInputEmulator there, it keeps the backend on its own thread.
+#[cfg(any(
+    feature = "use_uinput",
+    feature = "use_uhid",
+    feature = "use_serial_hid",
+    feature = "use_rfb",
+    feature = "use_qmp",
+    feature = "use_wayland",
+    feature = "use_libei",
+    feature = "use_xtest",
+))]
+const _: () = {
+    const fn assert_send<T: Send>() {}
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send::<InputEmulator>();
+    assert_send_sync::<SharedInputEmulator>();
+};
+
+// Cheap to clone, every clone drives the same backend. Each call holds the lock for the whole
+// operation, so calls from one thread are emitted in order and never interleave with another
+// thread's call. Use lock() to keep a sequence of calls together, e.g. a chord.
+#[derive(Clone)]
+pub struct SharedInputEmulator {
+    inner: Arc<Mutex<InputEmulator>>,
+}
+
+impl SharedInputEmulator {
+    pub fn new() -> Result<Self> {
+        Ok(Self::from_emulator(InputEmulator::new()?))
+    }
+
+    pub fn from_emulator(emulator: InputEmulator) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(emulator)),
+        }
+    }
+
+    // A panic in another thread doesn't lock everyone out, the backend is still usable
+    #[inline]
+    pub fn lock(&self) -> MutexGuard<'_, InputEmulator> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    #[inline]
+    pub fn press(&self, key_code: KeyCode) -> Result<()> {
+        self.lock().press(key_code)
+    }
+
+    #[inline]
+    pub fn release(&self, key_code: KeyCode) -> Result<()> {
+        self.lock().release(key_code)
+    }
+
+    #[inline]
+    pub fn move_mouse(&self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        self.lock().move_mouse(x, y)
+    }
+
+    #[inline]
+    pub fn move_mouse_x(&self, x: OS_Input_Coord) -> Result<()> {
+        self.lock().move_mouse_x(x)
+    }
+
+    #[inline]
+    pub fn move_mouse_y(&self, y: OS_Input_Coord) -> Result<()> {
+        self.lock().move_mouse_y(y)
+    }
+
+    #[inline]
+    pub fn gradual_move_mouse(&self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        self.lock().gradual_move_mouse(x, y)
+    }
+
+    #[inline]
+    pub fn scroll_x(&self, value: OS_Input_Coord) -> Result<()> {
+        self.lock().scroll_x(value)
+    }
+
+    #[inline]
+    pub fn scroll_y(&self, value: OS_Input_Coord) -> Result<()> {
+        self.lock().scroll_y(value)
+    }
+
+    #[inline]
+    pub fn gradual_scroll(&self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        self.lock().gradual_scroll(x, y)
+    }
+
+    #[inline]
+    pub fn write_buffer(&self, batch: &InputBatch) -> Result<()> {
+        self.lock().write_buffer(batch)
+    }
+
+    // Other threads block until the macro is done, cancel it through `cancel`
+    pub fn run_macro(&self, input_macro: &Macro, cancel: &MacroCancel) -> Result<MacroStatus> {
+        self.lock().run_macro(input_macro, cancel)
+    }
+}
+
+impl From<InputEmulator> for SharedInputEmulator {
+    fn from(emulator: InputEmulator) -> Self {
+        Self::from_emulator(emulator)
+    }
+}
+
+#[cfg(all(test, feature = "use_uinput"))]
+mod tests {
+    use std::io::Read;
+    use std::thread;
+    use crate::key_codes::{EV_KEY, EV_REL, EV_SYN, KEY_A, REL_X, REL_Y, SYN_REPORT};
+    use crate::uinput::tests::decode_events;
+    use crate::uinput::EventParams;
+    use super::*;
+
+    fn piped() -> (SharedInputEmulator, thread::JoinHandle<Vec<EventParams>>) {
+        let (mut reader, writer) = std::io::pipe().unwrap();
+        let emulator = SharedInputEmulator::from_emulator(InputEmulator::from_writer(writer));
+        // Drained on its own thread so writers never block on a full pipe
+        let written = thread::spawn(move || {
+            let mut written = vec![];
+            reader.read_to_end(&mut written).unwrap();
+            decode_events(&written)
+        });
+        (emulator, written)
+    }
+
+    #[test]
+    fn calls_from_threads_never_interleave() {
+        const CALLS: i32 = 200;
+        let (emulator, written) = piped();
+
+        // Every move is written as the x and y events, then a separate sync. The thread is the y value.
+        let threads: Vec<_> = [1, 2].into_iter().map(|thread_id| {
+            let emulator = emulator.clone();
+            thread::spawn(move || {
+                for call in 1..=CALLS {
+                    emulator.move_mouse(call, thread_id).unwrap();
+                }
+            })
+        }).collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        drop(emulator);
+
+        let events = written.join().unwrap();
+        assert_eq!(events.len(), 2 * CALLS as usize * 3);
+        let mut next_call = [1, 1];
+        for call in events.chunks(3) {
+            let thread_id = -call[1].2;
+            assert_eq!(call, [(EV_REL, REL_X, next_call[thread_id as usize - 1]), (EV_REL, REL_Y, -thread_id), (EV_SYN, SYN_REPORT, 0)]);
+            next_call[thread_id as usize - 1] += 1;
+        }
+        assert_eq!(next_call, [CALLS + 1, CALLS + 1]);
+    }
+
+    #[test]
+    fn lock_recovers_from_poison() {
+        let (emulator, written) = piped();
+
+        let poisoner = emulator.clone();
+        let panicked = thread::spawn(move || {
+            let _emulator = poisoner.lock();
+            panic!("poisoning the lock");
+        }).join();
+        assert!(panicked.is_err());
+
+        emulator.press(KeyCode::KEY_A).unwrap();
+        emulator.lock().release(KeyCode::KEY_A).unwrap();
+        drop(emulator);
+
+        let keys: Vec<_> = written.join().unwrap().into_iter().filter(|(event_type, _, _)| *event_type == EV_KEY).collect();
+        assert_eq!(keys, [(EV_KEY, KEY_A, 1), (EV_KEY, KEY_A, 0)]);
+    }
+}
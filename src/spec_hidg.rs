@@ -138,113 +138,41 @@ impl InputEmulator {
 #[cfg(feature = "use_hidg")]
 impl KeyCode {
     pub fn convert(&self) -> Result<Key> {
-        let result = match self {
-            KeyCode::KEY_ESC => Key::Esc,
-            KeyCode::KEY_1 => Key::Num1,
-            KeyCode::KEY_2 => Key::Num2,
-            KeyCode::KEY_3 => Key::Num3,
-            KeyCode::KEY_4 => Key::Num4,
-            KeyCode::KEY_5 => Key::Num5,
-            KeyCode::KEY_6 => Key::Num6,
-            KeyCode::KEY_7 => Key::Num7,
-            KeyCode::KEY_8 => Key::Num8,
-            KeyCode::KEY_9 => Key::Num9,
-            KeyCode::KEY_10 => Key::Num0,
-            KeyCode::KEY_MINUS => Key::Minus,
-            KeyCode::KEY_EQUAL => Key::Equal,
-            KeyCode::KEY_BACKSPACE => Key::BackSpace,
-            KeyCode::KEY_TAB => Key::Tab,
-            KeyCode::KEY_Q => Key::Q,
-            KeyCode::KEY_W => Key::W,
-            KeyCode::KEY_E => Key::E,
-            KeyCode::KEY_R => Key::R,
-            KeyCode::KEY_T => Key::T,
-            KeyCode::KEY_Y => Key::Y,
-            KeyCode::KEY_U => Key::U,
-            KeyCode::KEY_I => Key::I,
-            KeyCode::KEY_O => Key::O,
-            KeyCode::KEY_P => Key::P,
-            KeyCode::KEY_LEFTBRACE => Key::LeftBrace,
-            KeyCode::KEY_RIGHTBRACE => Key::RightBrace,
-            KeyCode::KEY_ENTER => Key::Enter,
-            KeyCode::KEY_LEFTCTRL => Key::LeftCtrl,
-            KeyCode::KEY_A => Key::A,
-            KeyCode::KEY_S => Key::S,
-            KeyCode::KEY_D => Key::D,
-            KeyCode::KEY_F => Key::F,
-            KeyCode::KEY_G => Key::G,
-            KeyCode::KEY_H => Key::H,
-            KeyCode::KEY_J => Key::J,
-            KeyCode::KEY_K => Key::K,
-            KeyCode::KEY_L => Key::L,
-            KeyCode::KEY_SEMICOLON => Key::Semicolon,
-            KeyCode::KEY_APOSTROPHE => Key::Apostrophe,
-            KeyCode::KEY_GRAVE => Key::Grave,
-            KeyCode::KEY_LEFTSHIFT => Key::LeftShift,
-            KeyCode::KEY_BACKSLASH => Key::BackSlash,
-            KeyCode::KEY_Z => Key::Z,
-            KeyCode::KEY_X => Key::X,
-            KeyCode::KEY_C => Key::C,
-            KeyCode::KEY_V => Key::V,
-            KeyCode::KEY_B => Key::B,
-            KeyCode::KEY_N => Key::N,
-            KeyCode::KEY_M => Key::M,
-            KeyCode::KEY_COMMA => Key::Comma,
-            KeyCode::KEY_DOT => Key::Dot,
-            KeyCode::KEY_SLASH => Key::Slash,
-            KeyCode::KEY_RIGHTSHIFT => Key::RightShift,
-            KeyCode::KEY_LEFTALT => Key::LeftAlt,
-            KeyCode::KEY_SPACE => Key::Space,
-            KeyCode::KEY_CAPSLOCK => Key::CapsLock,
-            KeyCode::KEY_F1 => Key::F1,
-            KeyCode::KEY_F2 => Key::F2,
-            KeyCode::KEY_F3 => Key::F3,
-            KeyCode::KEY_F4 => Key::F4,
-            KeyCode::KEY_F5 => Key::F5,
-            KeyCode::KEY_F6 => Key::F6,
-            KeyCode::KEY_F7 => Key::F7,
-            KeyCode::KEY_F8 => Key::F8,
-            KeyCode::KEY_F9 => Key::F9,
-            KeyCode::KEY_F10 => Key::F10,
-            KeyCode::KEY_KP7 => Key::KeyPad7,
-            KeyCode::KEY_KP8 => Key::KeyPad8,
-            KeyCode::KEY_KP9 => Key::KeyPad9,
-            KeyCode::KEY_KPMINUS => Key::KeyPadMinus,
-            KeyCode::KEY_KP4 => Key::KeyPad4,
-            KeyCode::KEY_KP5 => Key::KeyPad5,
-            KeyCode::KEY_KP6 => Key::KeyPad6,
-            KeyCode::KEY_KPPLUS => Key::KeyPadPlus,
-            KeyCode::KEY_KP1 => Key::KeyPad1,
-            KeyCode::KEY_KP2 => Key::KeyPad2,
-            KeyCode::KEY_KP3 => Key::KeyPad3,
-            KeyCode::KEY_KP0 => Key::KeyPad0,
-            KeyCode::KEY_KPDOT => Key::KeyPadDot,
-            KeyCode::KEY_F11 => Key::F11,
-            KeyCode::KEY_F12 => Key::F12,
-            KeyCode::KEY_KPENTER => Key::KeyPadEnter,
-            KeyCode::KEY_RIGHTCTRL => Key::RightCtrl,
-            KeyCode::KEY_KPSLASH => Key::KeyPadSlash,
-            KeyCode::KEY_RIGHTALT => Key::RightAlt,
-            KeyCode::KEY_HOME => Key::Home,
-            KeyCode::KEY_UP => Key::Up,
-            KeyCode::KEY_PAGEUP => Key::PageUp,
-            KeyCode::KEY_LEFT => Key::Left,
-            KeyCode::KEY_RIGHT => Key::Right,
-            KeyCode::KEY_END => Key::End,
-            KeyCode::KEY_DOWN => Key::Down,
-            KeyCode::KEY_PAGEDOWN => Key::PageDown,
-            KeyCode::KEY_INSERT => Key::Insert,
-            KeyCode::KEY_DELETE => Key::Delete,
-            KeyCode::KEY_MUTE => Key::Mute,
-            KeyCode::KEY_VOLUMEDOWN => Key::VolumeDown,
-            KeyCode::KEY_VOLUMEUP => Key::VolumeUp,
-            KeyCode::KEY_KPEQUAL => Key::KeyPadEqual,
-            KeyCode::KEY_KPPLUSMINUS => Key::KeyPadMinus,
-            KeyCode::KEY_LEFTMETA => Key::LeftMeta,
-            KeyCode::KEY_RIGHTMETA => Key::RightMeta,
-            value => bail!("No such key code: {value}"),
-        };
-        
-        Ok(result)
+        match self.hidg_key() {
+            Some(key) => Ok(key),
+            None => bail!("No such key code: {self}"),
+        }
+    }
+}
+
+#[cfg(feature = "use_hidg")]
+impl TryFrom<Key> for KeyCode {
+    type Error = Report;
+
+    // Reverse of convert, generated from the key table
+    fn try_from(key: Key) -> Result<Self> {
+        match KeyCode::from_hidg_key(&key) {
+            Some(key_code) => Ok(key_code),
+            None => bail!("No key code for key: {key:?}"),
+        }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(all(test, feature = "use_hidg"))]
+mod tests {
+    use strum::IntoEnumIterator;
+    use super::*;
+
+    #[test]
+    fn key_round_trip() {
+        let mut mapped = 0;
+        for key_code in KeyCode::iter() {
+            if let Ok(key) = key_code.convert() {
+                assert_eq!(KeyCode::try_from(key).unwrap(), key_code);
+                mapped += 1;
+            }
+        }
+        let rows = crate::KEY_TABLE.iter().filter(|mapping| mapping.hidg.is_some()).count();
+        assert_eq!(mapped, rows);
+    }
+}
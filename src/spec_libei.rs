@@ -0,0 +1,153 @@
+use color_eyre::eyre::bail;
+use color_eyre::Result;
+use crate::{KeyCode, OS_Input_Coord};
+
+#[cfg(feature = "use_libei")]
+use std::os::unix::net::UnixStream;
+#[cfg(feature = "use_libei")]
+use crate::libei::EiSender;
+
+#[cfg(feature = "use_libei")]
+pub struct InputEmulator {
+    sender: EiSender,
+    pending_move: (OS_Input_Coord, OS_Input_Coord),
+    pending_scroll: (OS_Input_Coord, OS_Input_Coord),
+}
+
+#[cfg(feature = "use_libei")]
+impl InputEmulator {
+    pub fn new() -> Result<Self> {
+        Ok(Self::from_sender(EiSender::connect()?))
+    }
+
+    // Socket from the RemoteDesktop portal's ConnectToEIS, or one end of a pair in tests
+    pub fn from_socket(stream: UnixStream) -> Result<Self> {
+        Ok(Self::from_sender(EiSender::from_stream(stream)?))
+    }
+
+    pub fn from_sender(sender: EiSender) -> Self {
+        Self {
+            sender,
+            pending_move: (0, 0),
+            pending_scroll: (0, 0),
+        }
+    }
+
+    #[inline]
+    fn send_key(&mut self, key_code: KeyCode, down: bool) -> Result<()> {
+        let evdev = key_code.convert()?;
+        if key_code.is_mouse_button() {
+            self.finish_operation_mouse()?;
+            self.sender.button(evdev, down)?;
+        } else {
+            self.sender.key(evdev, down)?;
+        }
+        self.sender.frame()
+    }
+
+    // Unique methods
+
+    #[inline]
+    pub fn finish_operation_mouse(&mut self) -> Result<()> {
+        let (x, y) = std::mem::take(&mut self.pending_move);
+        let (scroll_x, scroll_y) = std::mem::take(&mut self.pending_scroll);
+        if (x, y, scroll_x, scroll_y) == (0, 0, 0, 0) {
+            return Ok(());
+        }
+        if (x, y) != (0, 0) {
+            self.sender.motion(x as f32, -(y as f32))?;
+        }
+        if (scroll_x, scroll_y) != (0, 0) {
+            self.sender.scroll(scroll_x, scroll_y.saturating_neg())?;
+        }
+        self.sender.frame()
+    }
+
+    // Key events are sent right away
+    #[inline]
+    pub fn finish_operation_keyboard(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    #[inline]
+    pub fn move_mouse_raw_x(&mut self, x: OS_Input_Coord) -> Result<()> {
+        self.pending_move.0 = self.pending_move.0.saturating_add(x);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn move_mouse_raw_y(&mut self, y: OS_Input_Coord) -> Result<()> {
+        self.pending_move.1 = self.pending_move.1.saturating_add(y);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn move_mouse_raw(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        self.move_mouse_raw_x(x)?;
+        self.move_mouse_raw_y(y)
+    }
+
+    #[inline]
+    pub fn scroll_raw_x(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.pending_scroll.0 = self.pending_scroll.0.saturating_add(value);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn scroll_raw_y(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.pending_scroll.1 = self.pending_scroll.1.saturating_add(value);
+        Ok(())
+    }
+
+    // Common methods
+
+    #[inline]
+    pub fn move_mouse_x(&mut self, x: OS_Input_Coord) -> Result<()> {
+        self.move_mouse_raw_x(x)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn move_mouse_y(&mut self, y: OS_Input_Coord) -> Result<()> {
+        self.move_mouse_raw_y(y)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn move_mouse(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        self.move_mouse_raw(x, y)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn scroll_x(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.scroll_raw_x(value)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn scroll_y(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.scroll_raw_y(value)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn press(&mut self, key_code: KeyCode) -> Result<()> {
+        self.send_key(key_code, true)
+    }
+
+    #[inline]
+    pub fn release(&mut self, key_code: KeyCode) -> Result<()> {
+        self.send_key(key_code, false)
+    }
+}
+
+#[cfg(feature = "use_libei")]
+impl KeyCode {
+    pub fn convert(&self) -> Result<u16> {
+        match self.evdev_code() {
+            Some(evdev) => Ok(evdev),
+            None => bail!("No such key code: {self}"),
+        }
+    }
+}
@@ -0,0 +1,328 @@
+use color_eyre::eyre::bail;
+use color_eyre::Result;
+use crate::{KeyCode, OS_Input_Coord};
+
+#[cfg(feature = "use_qmp")]
+use serde_json::Value;
+#[cfg(feature = "use_qmp")]
+use crate::qmp::{qmp_button_event, qmp_key_event, qmp_rel_event, QmpConnection};
+#[cfg(feature = "use_qmp")]
+use crate::{InputBatch, InputEvent};
+#[cfg(feature = "use_qmp")]
+use crate::utils::scroll_steps;
+
+// Raw methods queue events, finish_operation_* sends everything queued in one input-send-event
+#[cfg(feature = "use_qmp")]
+pub struct InputEmulator {
+    connection: QmpConnection,
+    device: Option<String>,
+    pending: Vec<Value>,
+}
+
+#[cfg(feature = "use_qmp")]
+#[derive(Clone, Debug)]
+pub enum QmpSocket {
+    Unix(String),
+    Tcp(String),
+}
+
+#[cfg(feature = "use_qmp")]
+#[derive(Clone, Debug)]
+pub struct InputEmulatorBuilder {
+    socket: QmpSocket,
+    device: Option<String>,
+}
+
+#[cfg(feature = "use_qmp")]
+impl Default for InputEmulatorBuilder {
+    fn default() -> Self {
+        Self {
+            socket: QmpSocket::Unix("/tmp/qmp.sock".to_string()),
+            device: None,
+        }
+    }
+}
+
+#[cfg(feature = "use_qmp")]
+impl InputEmulatorBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn unix_socket(mut self, path: &str) -> Self {
+        self.socket = QmpSocket::Unix(path.to_string());
+        self
+    }
+
+    pub fn tcp_socket(mut self, address: &str) -> Self {
+        self.socket = QmpSocket::Tcp(address.to_string());
+        self
+    }
+
+    // QOM id of the input device, by default QEMU picks the first one able to handle each event
+    pub fn device(mut self, device: &str) -> Self {
+        self.device = Some(device.to_string());
+        self
+    }
+
+    pub fn build(self) -> Result<InputEmulator> {
+        let connection = match &self.socket {
+            QmpSocket::Unix(path) => QmpConnection::connect_unix(path)?,
+            QmpSocket::Tcp(address) => QmpConnection::connect_tcp(address.as_str())?,
+        };
+        let mut emulator = InputEmulator::from_connection(connection);
+        emulator.device = self.device;
+        Ok(emulator)
+    }
+}
+
+#[cfg(feature = "use_qmp")]
+impl InputEmulator {
+    pub fn builder() -> InputEmulatorBuilder {
+        InputEmulatorBuilder::default()
+    }
+
+    pub fn new() -> Result<Self> {
+        InputEmulatorBuilder::default().build()
+    }
+
+    pub fn from_connection(connection: QmpConnection) -> Self {
+        Self {
+            connection,
+            device: None,
+            pending: vec![],
+        }
+    }
+
+    fn button(key_code: KeyCode) -> Result<&'static str> {
+        Ok(match key_code {
+            KeyCode::MOUSE_LEFT => "left",
+            KeyCode::MOUSE_RIGHT => "right",
+            KeyCode::MOUSE_MIDDLE => "middle",
+            KeyCode::MOUSE_SIDE => "side",
+            KeyCode::MOUSE_EXTRA => "extra",
+            _ => bail!("No such key code: {key_code}"),
+        })
+    }
+
+    #[inline]
+    fn key_event(key_code: KeyCode, down: bool) -> Result<Value> {
+        Ok(match key_code.is_mouse_button() {
+            true => qmp_button_event(Self::button(key_code)?, down),
+            false => qmp_key_event(key_code.convert()?, down),
+        })
+    }
+
+    #[inline]
+    fn queue_key(&mut self, key_code: KeyCode, down: bool) -> Result<()> {
+        let event = Self::key_event(key_code, down)?;
+        self.pending.push(event);
+        Ok(())
+    }
+
+    // Each wheel step is a press and release of the wheel button, up to MAX_SCROLL_STEPS
+    fn queue_scroll(&mut self, steps: OS_Input_Coord, positive: &str, negative: &str) {
+        let button = if steps > 0 { positive } else { negative };
+        for _ in 0..scroll_steps(steps) {
+            self.pending.push(qmp_button_event(button, true));
+            self.pending.push(qmp_button_event(button, false));
+        }
+    }
+
+    #[inline]
+    fn flush(&mut self) -> Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let events = std::mem::take(&mut self.pending);
+        self.connection.input_send_event(&events, self.device.as_deref())
+    }
+
+    // Unique methods
+
+    #[inline]
+    pub fn finish_operation_mouse(&mut self) -> Result<()> {
+        self.flush()
+    }
+
+    #[inline]
+    pub fn finish_operation_keyboard(&mut self) -> Result<()> {
+        self.flush()
+    }
+
+    pub fn write_buffer(&mut self, batch: &InputBatch) -> Result<()> {
+        // Every key is checked first so a bad one doesn't leave half the batch in pending
+        for event in batch {
+            if let InputEvent::Press(key_code) | InputEvent::Release(key_code) = *event {
+                Self::key_event(key_code, true)?;
+            }
+        }
+        for event in batch {
+            match *event {
+                InputEvent::Move(x, y) => self.move_mouse_raw(x, y)?,
+                InputEvent::Scroll(x, y) => {
+                    self.scroll_raw_x(x)?;
+                    self.scroll_raw_y(y)?;
+                }
+                InputEvent::Press(key_code) => self.queue_key(key_code, true)?,
+                InputEvent::Release(key_code) => self.queue_key(key_code, false)?,
+                InputEvent::Sync => self.flush()?,
+            }
+        }
+        self.flush()
+    }
+
+    #[inline]
+    pub fn move_mouse_raw_x(&mut self, x: OS_Input_Coord) -> Result<()> {
+        if x != 0 {
+            self.pending.push(qmp_rel_event("x", x));
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn move_mouse_raw_y(&mut self, y: OS_Input_Coord) -> Result<()> {
+        if y != 0 {
+            self.pending.push(qmp_rel_event("y", y.saturating_neg()));
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn move_mouse_raw(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        self.move_mouse_raw_x(x)?;
+        self.move_mouse_raw_y(y)
+    }
+
+    #[inline]
+    pub fn scroll_raw_x(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.queue_scroll(value, "wheel-right", "wheel-left");
+        Ok(())
+    }
+
+    #[inline]
+    pub fn scroll_raw_y(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.queue_scroll(value, "wheel-up", "wheel-down");
+        Ok(())
+    }
+
+    // Common methods
+
+    #[inline]
+    pub fn move_mouse_x(&mut self, x: OS_Input_Coord) -> Result<()> {
+        self.move_mouse_raw_x(x)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn move_mouse_y(&mut self, y: OS_Input_Coord) -> Result<()> {
+        self.move_mouse_raw_y(y)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn move_mouse(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        self.move_mouse_raw(x, y)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn scroll_x(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.scroll_raw_x(value)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn scroll_y(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.scroll_raw_y(value)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn press(&mut self, key_code: KeyCode) -> Result<()> {
+        self.queue_key(key_code, true)?;
+        self.finish_operation_keyboard()
+    }
+
+    #[inline]
+    pub fn release(&mut self, key_code: KeyCode) -> Result<()> {
+        self.queue_key(key_code, false)?;
+        self.finish_operation_keyboard()
+    }
+}
+
+#[cfg(feature = "use_qmp")]
+impl KeyCode {
+    pub fn convert(&self) -> Result<&'static str> {
+        match self.qcode() {
+            Some(qcode) => Ok(qcode),
+            None => bail!("No such key code: {self}"),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "use_qmp", unix))]
+mod tests {
+    use serde_json::json;
+    use super::*;
+    use crate::qmp::tests::{fake_server, socket_path, GREETING};
+
+    #[test]
+    fn batch_is_one_input_send_event() {
+        let path = socket_path();
+        let handle = fake_server(&path, GREETING);
+        let mut emulator = InputEmulator::builder().unix_socket(path.to_str().unwrap()).device("kbd0").build().unwrap();
+
+        let mut batch = InputBatch::new();
+        batch
+            .press(KeyCode::KEY_A)
+            .move_mouse(3, 4)
+            .scroll_y(-1)
+            .release(KeyCode::KEY_A)
+            .press(KeyCode::MOUSE_LEFT);
+        emulator.write_buffer(&batch).unwrap();
+        emulator.scroll_y(OS_Input_Coord::MAX).unwrap();
+        drop(emulator);
+
+        let commands = handle.join().unwrap();
+        assert_eq!(commands.len(), 3);
+        assert_eq!(commands[1], json!({
+            "execute": "input-send-event",
+            "arguments": {
+                "device": "kbd0",
+                "events": [
+                    { "type": "key", "data": { "down": true, "key": { "type": "qcode", "data": "a" } } },
+                    { "type": "rel", "data": { "axis": "x", "value": 3 } },
+                    { "type": "rel", "data": { "axis": "y", "value": -4 } },
+                    { "type": "btn", "data": { "down": true, "button": "wheel-down" } },
+                    { "type": "btn", "data": { "down": false, "button": "wheel-down" } },
+                    { "type": "key", "data": { "down": false, "key": { "type": "qcode", "data": "a" } } },
+                    { "type": "btn", "data": { "down": true, "button": "left" } },
+                ],
+            },
+        }));
+        // Scroll steps are clamped
+        assert_eq!(commands[2]["arguments"]["events"].as_array().unwrap().len(), 2 * 256);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn bad_key_rejects_the_whole_batch() {
+        let path = socket_path();
+        let handle = fake_server(&path, GREETING);
+        let mut emulator = InputEmulator::builder().unix_socket(path.to_str().unwrap()).build().unwrap();
+
+        let mut batch = InputBatch::new();
+        batch.move_mouse(3, 4).press(KeyCode::KEY_A).press(KeyCode::None);
+        assert!(emulator.write_buffer(&batch).is_err());
+        emulator.press(KeyCode::KEY_B).unwrap();
+        drop(emulator);
+
+        let commands = handle.join().unwrap();
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[1]["arguments"]["events"], json!([
+            { "type": "key", "data": { "down": true, "key": { "type": "qcode", "data": "b" } } },
+        ]));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
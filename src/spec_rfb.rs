@@ -0,0 +1,296 @@
+use color_eyre::eyre::bail;
+use color_eyre::Result;
+use crate::{KeyCode, OS_Input_Coord};
+
+#[cfg(feature = "use_rfb")]
+use crate::rfb::*;
+#[cfg(feature = "use_rfb")]
+use crate::utils::scroll_steps;
+
+#[cfg(feature = "use_rfb")]
+pub struct InputEmulator {
+    connection: RfbConnection,
+    button_mask: u8,
+    // RFB pointers are absolute, relative moves are applied to the last sent position
+    x: OS_Input_Coord,
+    y: OS_Input_Coord,
+    pending_move: bool,
+    pending_scroll: (OS_Input_Coord, OS_Input_Coord),
+}
+
+#[cfg(feature = "use_rfb")]
+#[derive(Clone, Debug)]
+pub struct InputEmulatorBuilder {
+    address: String,
+    shared: bool,
+}
+
+#[cfg(feature = "use_rfb")]
+impl Default for InputEmulatorBuilder {
+    fn default() -> Self {
+        Self {
+            address: format!("127.0.0.1:{RFB_DEFAULT_PORT}"),
+            shared: true,
+        }
+    }
+}
+
+#[cfg(feature = "use_rfb")]
+impl InputEmulatorBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // host:port, display N listens on port 5900 + N
+    pub fn address(mut self, address: &str) -> Self {
+        self.address = address.to_string();
+        self
+    }
+
+    // Keep other viewers connected
+    pub fn shared(mut self, shared: bool) -> Self {
+        self.shared = shared;
+        self
+    }
+
+    pub fn build(self) -> Result<InputEmulator> {
+        Ok(InputEmulator::from_connection(RfbConnection::connect(self.address.as_str(), self.shared)?))
+    }
+}
+
+#[cfg(feature = "use_rfb")]
+impl InputEmulator {
+    pub fn builder() -> InputEmulatorBuilder {
+        InputEmulatorBuilder::default()
+    }
+
+    pub fn new() -> Result<Self> {
+        InputEmulatorBuilder::default().build()
+    }
+
+    // The pointer starts in the middle of the screen
+    pub fn from_connection(connection: RfbConnection) -> Self {
+        Self {
+            x: connection.width() as OS_Input_Coord / 2,
+            y: connection.height() as OS_Input_Coord / 2,
+            connection,
+            button_mask: 0,
+            pending_move: false,
+            pending_scroll: (0, 0),
+        }
+    }
+
+    #[inline]
+    fn max_position(&self) -> (OS_Input_Coord, OS_Input_Coord) {
+        (
+            self.connection.width().saturating_sub(1) as OS_Input_Coord,
+            self.connection.height().saturating_sub(1) as OS_Input_Coord,
+        )
+    }
+
+    #[inline]
+    fn send_pointer(&mut self, button_mask: u8) -> Result<()> {
+        self.connection.pointer_event(button_mask, self.x as u16, self.y as u16)
+    }
+
+    // Each wheel step is a press and release of the wheel button, up to MAX_SCROLL_STEPS
+    fn send_scroll_steps(&mut self, steps: OS_Input_Coord, positive: u8, negative: u8) -> Result<()> {
+        let button = if steps > 0 { positive } else { negative };
+        for _ in 0..scroll_steps(steps) {
+            self.send_pointer(self.button_mask | button)?;
+            self.send_pointer(self.button_mask)?;
+        }
+        Ok(())
+    }
+
+    fn button(key_code: KeyCode) -> Result<u8> {
+        Ok(match key_code {
+            KeyCode::MOUSE_LEFT => RFB_BUTTON_LEFT,
+            KeyCode::MOUSE_MIDDLE => RFB_BUTTON_MIDDLE,
+            KeyCode::MOUSE_RIGHT => RFB_BUTTON_RIGHT,
+            _ => bail!("No such key code: {key_code}"),
+        })
+    }
+
+    // Unique methods
+
+    #[inline]
+    pub fn pointer_position(&self) -> (OS_Input_Coord, OS_Input_Coord) {
+        (self.x, self.y)
+    }
+
+    // Screen coordinates, y grows downwards like in the framebuffer
+    #[inline]
+    pub fn move_mouse_to(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        let (max_x, max_y) = self.max_position();
+        self.x = x.clamp(0, max_x);
+        self.y = y.clamp(0, max_y);
+        self.pending_move = true;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn finish_operation_mouse(&mut self) -> Result<()> {
+        if std::mem::take(&mut self.pending_move) {
+            self.send_pointer(self.button_mask)?;
+        }
+        let (x, y) = std::mem::take(&mut self.pending_scroll);
+        self.send_scroll_steps(y, RFB_WHEEL_UP, RFB_WHEEL_DOWN)?;
+        self.send_scroll_steps(x, RFB_WHEEL_RIGHT, RFB_WHEEL_LEFT)
+    }
+
+    // Key events are sent right away
+    #[inline]
+    pub fn finish_operation_keyboard(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    #[inline]
+    pub fn move_mouse_raw_x(&mut self, x: OS_Input_Coord) -> Result<()> {
+        let (max_x, _) = self.max_position();
+        self.x = self.x.saturating_add(x).clamp(0, max_x);
+        self.pending_move = true;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn move_mouse_raw_y(&mut self, y: OS_Input_Coord) -> Result<()> {
+        let (_, max_y) = self.max_position();
+        self.y = self.y.saturating_sub(y).clamp(0, max_y);
+        self.pending_move = true;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn move_mouse_raw(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        self.move_mouse_raw_x(x)?;
+        self.move_mouse_raw_y(y)
+    }
+
+    #[inline]
+    pub fn scroll_raw_x(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.pending_scroll.0 = self.pending_scroll.0.saturating_add(value);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn scroll_raw_y(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.pending_scroll.1 = self.pending_scroll.1.saturating_add(value);
+        Ok(())
+    }
+
+    // Common methods
+
+    #[inline]
+    pub fn move_mouse_x(&mut self, x: OS_Input_Coord) -> Result<()> {
+        self.move_mouse_raw_x(x)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn move_mouse_y(&mut self, y: OS_Input_Coord) -> Result<()> {
+        self.move_mouse_raw_y(y)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn move_mouse(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        self.move_mouse_raw(x, y)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn scroll_x(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.scroll_raw_x(value)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn scroll_y(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.scroll_raw_y(value)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn press(&mut self, key_code: KeyCode) -> Result<()> {
+        if key_code.is_mouse_button() {
+            self.finish_operation_mouse()?;
+            self.button_mask |= Self::button(key_code)?;
+            return self.send_pointer(self.button_mask);
+        }
+        let keysym = key_code.convert()?;
+        self.connection.key_event(keysym, true)
+    }
+
+    #[inline]
+    pub fn release(&mut self, key_code: KeyCode) -> Result<()> {
+        if key_code.is_mouse_button() {
+            self.finish_operation_mouse()?;
+            self.button_mask &= !Self::button(key_code)?;
+            return self.send_pointer(self.button_mask);
+        }
+        let keysym = key_code.convert()?;
+        self.connection.key_event(keysym, false)
+    }
+}
+
+#[cfg(feature = "use_rfb")]
+impl KeyCode {
+    pub fn convert(&self) -> Result<u32> {
+        match self.keysym() {
+            Some(keysym) => Ok(keysym),
+            None => bail!("No such key code: {self}"),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "use_rfb"))]
+mod tests {
+    use super::*;
+    use crate::rfb::tests::stub_server;
+
+    fn pointer(button_mask: u8, x: u16, y: u16) -> Vec<u8> {
+        RfbConnection::encode_pointer_event(button_mask, x, y).to_vec()
+    }
+
+    #[test]
+    fn sends_key_and_pointer_events() {
+        let (address, handle) = stub_server(b"RFB 003.008\n");
+        let mut emulator = InputEmulator::builder().address(&address).build().unwrap();
+        assert_eq!(emulator.pointer_position(), (400, 300));
+        emulator.move_mouse(10, 20).unwrap();
+        emulator.press(KeyCode::MOUSE_LEFT).unwrap();
+        emulator.press(KeyCode::KEY_A).unwrap();
+        emulator.release(KeyCode::KEY_A).unwrap();
+        emulator.scroll_y(-1).unwrap();
+        emulator.release(KeyCode::MOUSE_LEFT).unwrap();
+        drop(emulator);
+
+        let expected: Vec<u8> = [
+            pointer(0, 410, 280),
+            pointer(RFB_BUTTON_LEFT, 410, 280),
+            RfbConnection::encode_key_event(0x61, true).to_vec(),
+            RfbConnection::encode_key_event(0x61, false).to_vec(),
+            pointer(RFB_BUTTON_LEFT | RFB_WHEEL_DOWN, 410, 280),
+            pointer(RFB_BUTTON_LEFT, 410, 280),
+            pointer(0, 410, 280),
+        ]
+        .concat();
+        assert_eq!(handle.join().unwrap().messages, expected);
+    }
+
+    #[test]
+    fn scroll_steps_are_clamped() {
+        let (address, handle) = stub_server(b"RFB 003.003\n");
+        let mut emulator = InputEmulator::builder().address(&address).build().unwrap();
+        emulator.scroll_y(OS_Input_Coord::MAX).unwrap();
+        emulator.scroll_x(OS_Input_Coord::MIN).unwrap();
+        drop(emulator);
+
+        let messages = handle.join().unwrap().messages;
+        let wheel = |button: u8| messages.chunks(6).filter(|event| event[1] == button).count();
+        assert_eq!(messages.len(), 2 * 2 * 256 * 6);
+        assert_eq!(wheel(RFB_WHEEL_UP), 256);
+        assert_eq!(wheel(RFB_WHEEL_LEFT), 256);
+    }
+}
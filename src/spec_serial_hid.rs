@@ -0,0 +1,283 @@
+use color_eyre::eyre::bail;
+use color_eyre::Result;
+use crate::{exec_or_eyre, KeyCode, OS_Input_Coord};
+
+#[cfg(feature = "use_serial_hid")]
+use std::io::Write;
+#[cfg(feature = "use_serial_hid")]
+use crate::serial_hid::{open_serial_port, SerialHidEncoder, SerialHidProtocol};
+
+#[cfg(feature = "use_serial_hid")]
+pub struct InputEmulator {
+    writer: Box<dyn Write + Send>,
+    encoder: SerialHidEncoder,
+    pending_move: (OS_Input_Coord, OS_Input_Coord),
+    pending_scroll: OS_Input_Coord,
+}
+
+#[cfg(feature = "use_serial_hid")]
+#[derive(Clone, Debug)]
+pub struct InputEmulatorBuilder {
+    path: String,
+    baud_rate: u32,
+    protocol: SerialHidProtocol,
+}
+
+// CH9329 ships configured for 9600 baud
+#[cfg(feature = "use_serial_hid")]
+impl Default for InputEmulatorBuilder {
+    fn default() -> Self {
+        Self {
+            path: "/dev/ttyUSB0".to_string(),
+            baud_rate: 9600,
+            protocol: SerialHidProtocol::default(),
+        }
+    }
+}
+
+#[cfg(feature = "use_serial_hid")]
+impl InputEmulatorBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn path(mut self, path: &str) -> Self {
+        self.path = path.to_string();
+        self
+    }
+
+    pub fn baud_rate(mut self, baud_rate: u32) -> Self {
+        self.baud_rate = baud_rate;
+        self
+    }
+
+    pub fn protocol(mut self, protocol: SerialHidProtocol) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
+    pub fn build(self) -> Result<InputEmulator> {
+        let port = open_serial_port(&self.path, self.baud_rate)?;
+        Ok(InputEmulator::from_writer(port, self.protocol))
+    }
+}
+
+#[cfg(feature = "use_serial_hid")]
+impl InputEmulator {
+    pub fn builder() -> InputEmulatorBuilder {
+        InputEmulatorBuilder::default()
+    }
+
+    pub fn new() -> Result<Self> {
+        InputEmulatorBuilder::default().build()
+    }
+
+    // Any writer works here, e.g. one end of a pseudo-terminal pair
+    pub fn from_writer<W: Write + Send + 'static>(writer: W, protocol: SerialHidProtocol) -> Self {
+        Self {
+            writer: Box::new(writer),
+            encoder: SerialHidEncoder::new(protocol),
+            pending_move: (0, 0),
+            pending_scroll: 0,
+        }
+    }
+
+    #[inline]
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        exec_or_eyre!(self.writer.write_all(bytes))?;
+        exec_or_eyre!(self.writer.flush())
+    }
+
+    // Unique methods
+
+    #[inline]
+    pub fn finish_operation_mouse(&mut self) -> Result<()> {
+        let (x, y) = std::mem::take(&mut self.pending_move);
+        let mut bytes = self.encoder.move_mouse(x, y);
+        bytes.extend(self.encoder.scroll(std::mem::take(&mut self.pending_scroll)));
+        self.write_bytes(&bytes)
+    }
+
+    // Key changes are sent right away, the bridge only knows full reports or single key commands
+    #[inline]
+    pub fn finish_operation_keyboard(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    #[inline]
+    pub fn release_all(&mut self) -> Result<()> {
+        self.finish_operation_mouse()?;
+        let bytes = self.encoder.release_all();
+        self.write_bytes(&bytes)
+    }
+
+    #[inline]
+    pub fn move_mouse_raw_x(&mut self, x: OS_Input_Coord) -> Result<()> {
+        self.pending_move.0 = self.pending_move.0.saturating_add(x);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn move_mouse_raw_y(&mut self, y: OS_Input_Coord) -> Result<()> {
+        self.pending_move.1 = self.pending_move.1.saturating_add(y);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn move_mouse_raw(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        self.move_mouse_raw_x(x)?;
+        self.move_mouse_raw_y(y)
+    }
+
+    // Neither protocol has a horizontal wheel
+    #[inline]
+    pub fn scroll_raw_x(&mut self, _value: OS_Input_Coord) -> Result<()> {
+        Ok(())
+    }
+
+    #[inline]
+    pub fn scroll_raw_y(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.pending_scroll = self.pending_scroll.saturating_add(value);
+        Ok(())
+    }
+
+    // Common methods
+
+    #[inline]
+    pub fn move_mouse_x(&mut self, x: OS_Input_Coord) -> Result<()> {
+        self.move_mouse_raw_x(x)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn move_mouse_y(&mut self, y: OS_Input_Coord) -> Result<()> {
+        self.move_mouse_raw_y(y)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn move_mouse(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        self.move_mouse_raw(x, y)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn scroll_x(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.scroll_raw_x(value)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn scroll_y(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.scroll_raw_y(value)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn press(&mut self, key_code: KeyCode) -> Result<()> {
+        self.finish_operation_mouse()?;
+        let bytes = self.encoder.press(key_code)?;
+        self.write_bytes(&bytes)
+    }
+
+    #[inline]
+    pub fn release(&mut self, key_code: KeyCode) -> Result<()> {
+        self.finish_operation_mouse()?;
+        let bytes = self.encoder.release(key_code)?;
+        self.write_bytes(&bytes)
+    }
+}
+
+#[cfg(feature = "use_serial_hid")]
+impl KeyCode {
+    pub fn convert(&self) -> Result<u16> {
+        match self.hid_usage() {
+            Some(usage) => Ok(usage),
+            None => bail!("No such key code: {self}"),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "use_serial_hid"))]
+mod tests {
+    use std::ffi::CStr;
+    use std::fs::File;
+    use std::io::Read;
+    use std::os::unix::io::FromRawFd;
+    use super::*;
+    use crate::serial_hid::ch9329_frame;
+
+    // Master end and the path of the slave end, which is opened like a real serial port
+    fn pty_pair() -> (File, String) {
+        unsafe {
+            let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+            assert!(master >= 0);
+            assert_eq!(libc::grantpt(master), 0);
+            assert_eq!(libc::unlockpt(master), 0);
+            let mut name = [0 as libc::c_char; 64];
+            assert_eq!(libc::ptsname_r(master, name.as_mut_ptr(), name.len()), 0);
+            let path = CStr::from_ptr(name.as_ptr()).to_str().unwrap().to_string();
+            (File::from_raw_fd(master), path)
+        }
+    }
+
+    fn run(protocol: SerialHidProtocol) -> (InputEmulator, File) {
+        let (master, path) = pty_pair();
+        let emulator = InputEmulator::builder().path(&path).baud_rate(115200).protocol(protocol).build().unwrap();
+        (emulator, master)
+    }
+
+    fn read_bytes(master: &mut File, len: usize) -> Vec<u8> {
+        let mut bytes = vec![0; len];
+        master.read_exact(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn ch9329_frames() {
+        let (mut emulator, mut master) = run(SerialHidProtocol::Ch9329 { address: 0 });
+        emulator.press(KeyCode::KEY_LEFTSHIFT).unwrap();
+        emulator.press(KeyCode::KEY_A).unwrap();
+        emulator.move_mouse(10, 5).unwrap();
+        emulator.scroll_x(3).unwrap();
+        emulator.scroll_y(-2).unwrap();
+        emulator.press(KeyCode::MOUSE_LEFT).unwrap();
+
+        let expected: Vec<u8> = [
+            &[0x57, 0xab, 0x00, 0x02, 0x08, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0e][..],
+            &[0x57, 0xab, 0x00, 0x02, 0x08, 0x02, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x12],
+            &[0x57, 0xab, 0x00, 0x05, 0x05, 0x01, 0x00, 0x0a, 0xfb, 0x00, 0x12],
+            &[0x57, 0xab, 0x00, 0x05, 0x05, 0x01, 0x00, 0x00, 0x00, 0xfe, 0x0b],
+            &[0x57, 0xab, 0x00, 0x05, 0x05, 0x01, 0x01, 0x00, 0x00, 0x00, 0x0e],
+        ]
+        .concat();
+        assert_eq!(read_bytes(&mut master, expected.len()), expected);
+    }
+
+    #[test]
+    fn ch9329_checksum_and_address() {
+        let frame = ch9329_frame(0x03, 0x02, &[0xff; 8]);
+        let checksum = frame[..frame.len() - 1].iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        assert_eq!(&frame[..5], &[0x57, 0xab, 0x03, 0x02, 0x08]);
+        assert_eq!(*frame.last().unwrap(), checksum);
+        assert_eq!(checksum, 0x07);
+    }
+
+    #[test]
+    fn text_lines() {
+        let (mut emulator, mut master) = run(SerialHidProtocol::Text);
+        emulator.press(KeyCode::KEY_A).unwrap();
+        emulator.move_mouse(200, 5).unwrap();
+        emulator.scroll_x(3).unwrap();
+        emulator.scroll_y(2).unwrap();
+        emulator.press(KeyCode::MOUSE_RIGHT).unwrap();
+        emulator.release(KeyCode::KEY_A).unwrap();
+        emulator.release_all().unwrap();
+
+        let expected = "kd 4\nmm 127 -5\nmm 73 0\nmw 2\nmd 2\nku 4\nra\n";
+        assert_eq!(String::from_utf8(read_bytes(&mut master, expected.len())).unwrap(), expected);
+    }
+}
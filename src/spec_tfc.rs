@@ -80,113 +80,41 @@ impl InputEmulator {
 #[cfg(feature = "use_tfc")]
 impl KeyCode {
     pub fn convert(&self) -> Result<Key> {
-        let result = match self {
-            KeyCode::KEY_ESC => Key::Escape,
-            KeyCode::KEY_1 => Key::N1,
-            KeyCode::KEY_2 => Key::N2,
-            KeyCode::KEY_3 => Key::N3,
-            KeyCode::KEY_4 => Key::N4,
-            KeyCode::KEY_5 => Key::N5,
-            KeyCode::KEY_6 => Key::N6,
-            KeyCode::KEY_7 => Key::N7,
-            KeyCode::KEY_8 => Key::N8,
-            KeyCode::KEY_9 => Key::N9,
-            KeyCode::KEY_10 => Key::N0,
-            KeyCode::KEY_MINUS => Key::Minus,
-            KeyCode::KEY_EQUAL => Key::Equal,
-            KeyCode::KEY_BACKSPACE => Key::DeleteOrBackspace,
-            KeyCode::KEY_TAB => Key::Tab,
-            KeyCode::KEY_Q => Key::Q,
-            KeyCode::KEY_W => Key::W,
-            KeyCode::KEY_E => Key::E,
-            KeyCode::KEY_R => Key::R,
-            KeyCode::KEY_T => Key::T,
-            KeyCode::KEY_Y => Key::Y,
-            KeyCode::KEY_U => Key::U,
-            KeyCode::KEY_I => Key::I,
-            KeyCode::KEY_O => Key::O,
-            KeyCode::KEY_P => Key::P,
-            KeyCode::KEY_LEFTBRACE => Key::LeftBracket,
-            KeyCode::KEY_RIGHTBRACE => Key::RightBracket,
-            KeyCode::KEY_ENTER => Key::ReturnOrEnter,
-            KeyCode::KEY_LEFTCTRL => Key::Control,
-            KeyCode::KEY_A => Key::A,
-            KeyCode::KEY_S => Key::S,
-            KeyCode::KEY_D => Key::D,
-            KeyCode::KEY_F => Key::F,
-            KeyCode::KEY_G => Key::G,
-            KeyCode::KEY_H => Key::H,
-            KeyCode::KEY_J => Key::J,
-            KeyCode::KEY_K => Key::K,
-            KeyCode::KEY_L => Key::L,
-            KeyCode::KEY_SEMICOLON => Key::Semicolon,
-            KeyCode::KEY_APOSTROPHE => Key::Quote,
-            KeyCode::KEY_GRAVE => Key::Grave,
-            KeyCode::KEY_LEFTSHIFT => Key::Shift,
-            KeyCode::KEY_BACKSLASH => Key::Backslash,
-            KeyCode::KEY_Z => Key::Z,
-            KeyCode::KEY_X => Key::X,
-            KeyCode::KEY_C => Key::C,
-            KeyCode::KEY_V => Key::V,
-            KeyCode::KEY_B => Key::B,
-            KeyCode::KEY_N => Key::N,
-            KeyCode::KEY_M => Key::M,
-            KeyCode::KEY_COMMA => Key::Comma,
-            KeyCode::KEY_DOT => Key::Period,
-            KeyCode::KEY_SLASH => Key::Slash,
-            KeyCode::KEY_RIGHTSHIFT => Key::RightShift,
-            KeyCode::KEY_LEFTALT => Key::Alt,
-            KeyCode::KEY_SPACE => Key::Space,
-            KeyCode::KEY_CAPSLOCK => Key::CapsLock,
-            KeyCode::KEY_F1 => Key::F1,
-            KeyCode::KEY_F2 => Key::F2,
-            KeyCode::KEY_F3 => Key::F3,
-            KeyCode::KEY_F4 => Key::F4,
-            KeyCode::KEY_F5 => Key::F5,
-            KeyCode::KEY_F6 => Key::F6,
-            KeyCode::KEY_F7 => Key::F7,
-            KeyCode::KEY_F8 => Key::F8,
-            KeyCode::KEY_F9 => Key::F9,
-            KeyCode::KEY_F10 => Key::F10,
-            KeyCode::KEY_KP7 => Key::Numpad7,
-            KeyCode::KEY_KP8 => Key::Numpad8,
-            KeyCode::KEY_KP9 => Key::Numpad9,
-            KeyCode::KEY_KPMINUS => Key::NumpadMinus,
-            KeyCode::KEY_KP4 => Key::Numpad4,
-            KeyCode::KEY_KP5 => Key::Numpad5,
-            KeyCode::KEY_KP6 => Key::Numpad6,
-            KeyCode::KEY_KPPLUS => Key::NumpadPlus,
-            KeyCode::KEY_KP1 => Key::Numpad1,
-            KeyCode::KEY_KP2 => Key::Numpad2,
-            KeyCode::KEY_KP3 => Key::Numpad3,
-            KeyCode::KEY_KP0 => Key::Numpad0,
-            KeyCode::KEY_KPDOT => Key::NumpadDecimal,
-            KeyCode::KEY_F11 => Key::F11,
-            KeyCode::KEY_F12 => Key::F12,
-            KeyCode::KEY_KPENTER => Key::NumpadEnter,
-            KeyCode::KEY_RIGHTCTRL => Key::RightControl,
-            KeyCode::KEY_KPSLASH => Key::NumpadDivide,
-            KeyCode::KEY_RIGHTALT => Key::RightAlt,
-            KeyCode::KEY_HOME => Key::Home,
-            KeyCode::KEY_UP => Key::UpArrow,
-            KeyCode::KEY_PAGEUP => Key::PageUp,
-            KeyCode::KEY_LEFT => Key::LeftArrow,
-            KeyCode::KEY_RIGHT => Key::RightArrow,
-            KeyCode::KEY_END => Key::End,
-            KeyCode::KEY_DOWN => Key::DownArrow,
-            KeyCode::KEY_PAGEDOWN => Key::PageDown,
-            KeyCode::KEY_INSERT => Key::Insert,
-            KeyCode::KEY_DELETE => Key::ForwardDelete,
-            KeyCode::KEY_MUTE => Key::Mute,
-            KeyCode::KEY_VOLUMEDOWN => Key::VolumeDown,
-            KeyCode::KEY_VOLUMEUP => Key::VolumeUp,
-            KeyCode::KEY_KPEQUAL => Key::NumpadEquals,
-            KeyCode::KEY_KPPLUSMINUS => Key::NumpadMinus,
-            KeyCode::KEY_LEFTMETA => Key::Meta,
-            KeyCode::KEY_RIGHTMETA => Key::RightMeta,
-            value => bail!("No such key code: {value}"),
-        };
+        match self.tfc_key() {
+            Some(key) => Ok(key),
+            None => bail!("No such key code: {self}"),
+        }
+    }
+}
 
-        Ok(result)
+#[cfg(feature = "use_tfc")]
+impl TryFrom<Key> for KeyCode {
+    type Error = Report;
+
+    // Reverse of convert, generated from the key table
+    fn try_from(key: Key) -> Result<Self> {
+        match KeyCode::from_tfc_key(&key) {
+            Some(key_code) => Ok(key_code),
+            None => bail!("No key code for key: {key:?}"),
+        }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(all(test, feature = "use_tfc"))]
+mod tests {
+    use strum::IntoEnumIterator;
+    use super::*;
+
+    #[test]
+    fn key_round_trip() {
+        let mut mapped = 0;
+        for key_code in KeyCode::iter() {
+            if let Ok(key) = key_code.convert() {
+                assert_eq!(KeyCode::try_from(key).unwrap(), key_code);
+                mapped += 1;
+            }
+        }
+        let rows = crate::KEY_TABLE.iter().filter(|mapping| mapping.tfc.is_some()).count();
+        assert_eq!(mapped, rows);
+    }
+}
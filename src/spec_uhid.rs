@@ -0,0 +1,210 @@
+use color_eyre::eyre::bail;
+use color_eyre::Result;
+use crate::{KeyCode, OS_Input_Coord};
+
+#[cfg(feature = "use_uhid")]
+use std::io::Write;
+#[cfg(feature = "use_uhid")]
+use crate::hid_report::{KeyboardReport, MouseFrame, MouseReport, KEYBOARD_REPORT_DESCRIPTOR, MOUSE_REPORT_DESCRIPTOR};
+#[cfg(feature = "use_uhid")]
+use crate::uhid::{UhidBuilder, UhidDevice};
+
+// Keyboard and mouse are separate devices so the descriptors don't need report IDs
+#[cfg(feature = "use_uhid")]
+pub struct InputEmulator {
+    keyboard_device: UhidDevice,
+    mouse_device: UhidDevice,
+    keyboard_report: KeyboardReport,
+    mouse_report: MouseReport,
+    pending_move: (OS_Input_Coord, OS_Input_Coord),
+    pending_scroll: (OS_Input_Coord, OS_Input_Coord),
+}
+
+#[cfg(feature = "use_uhid")]
+impl InputEmulator {
+    fn keyboard_builder() -> UhidBuilder {
+        UhidBuilder::default()
+            .name("universal_input keyboard")
+            .report_descriptor(&KEYBOARD_REPORT_DESCRIPTOR)
+    }
+
+    fn mouse_builder() -> UhidBuilder {
+        UhidBuilder::default()
+            .name("universal_input mouse")
+            .report_descriptor(&MOUSE_REPORT_DESCRIPTOR)
+    }
+
+    pub fn new() -> Result<Self> {
+        Ok(Self::from_devices(
+            Self::keyboard_builder().create()?,
+            Self::mouse_builder().create()?,
+        ))
+    }
+
+    // Capture the raw uhid events instead of creating devices, UHID_CREATE2 is still written first
+    pub fn from_writers<K, M>(keyboard_writer: K, mouse_writer: M) -> Result<Self>
+    where
+        K: Write + Send + 'static,
+        M: Write + Send + 'static,
+    {
+        Ok(Self::from_devices(
+            Self::keyboard_builder().create_on(keyboard_writer)?,
+            Self::mouse_builder().create_on(mouse_writer)?,
+        ))
+    }
+
+    pub fn from_devices(keyboard_device: UhidDevice, mouse_device: UhidDevice) -> Self {
+        Self {
+            keyboard_device,
+            mouse_device,
+            keyboard_report: KeyboardReport::new(),
+            mouse_report: MouseReport::new(),
+            pending_move: (0, 0),
+            pending_scroll: (0, 0),
+        }
+    }
+
+    #[inline]
+    fn write_mouse_frames(&mut self, frames: &[MouseFrame]) -> Result<()> {
+        for frame in frames {
+            self.mouse_device.write_report(&frame.to_bytes())?;
+        }
+        Ok(())
+    }
+
+    // Unique methods
+
+    #[inline]
+    pub fn finish_operation_mouse(&mut self) -> Result<()> {
+        let (x, y) = std::mem::take(&mut self.pending_move);
+        let frames = self.mouse_report.move_mouse(x, y);
+        self.write_mouse_frames(&frames)?;
+
+        let (x, y) = std::mem::take(&mut self.pending_scroll);
+        let frames = self.mouse_report.scroll(x, y);
+        self.write_mouse_frames(&frames)
+    }
+
+    #[inline]
+    pub fn finish_operation_keyboard(&mut self) -> Result<()> {
+        self.keyboard_device.write_report(&self.keyboard_report.to_bytes())
+    }
+
+    #[inline]
+    pub fn move_mouse_raw_x(&mut self, x: OS_Input_Coord) -> Result<()> {
+        self.pending_move.0 = self.pending_move.0.saturating_add(x);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn move_mouse_raw_y(&mut self, y: OS_Input_Coord) -> Result<()> {
+        self.pending_move.1 = self.pending_move.1.saturating_add(y);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn move_mouse_raw(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        self.move_mouse_raw_x(x)?;
+        self.move_mouse_raw_y(y)
+    }
+
+    #[inline]
+    pub fn scroll_raw_x(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.pending_scroll.0 = self.pending_scroll.0.saturating_add(value);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn scroll_raw_y(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.pending_scroll.1 = self.pending_scroll.1.saturating_add(value);
+        Ok(())
+    }
+
+    // Common methods
+
+    #[inline]
+    pub fn move_mouse_x(&mut self, x: OS_Input_Coord) -> Result<()> {
+        self.move_mouse_raw_x(x)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn move_mouse_y(&mut self, y: OS_Input_Coord) -> Result<()> {
+        self.move_mouse_raw_y(y)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn move_mouse(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        self.move_mouse_raw(x, y)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn scroll_x(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.scroll_raw_x(value)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn scroll_y(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.scroll_raw_y(value)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn press(&mut self, key_code: KeyCode) -> Result<()> {
+        if key_code.is_mouse_button() {
+            self.finish_operation_mouse()?;
+            let frame = self.mouse_report.press(key_code)?;
+            return self.write_mouse_frames(&[frame]);
+        }
+        self.keyboard_report.press(key_code)?;
+        self.finish_operation_keyboard()
+    }
+
+    #[inline]
+    pub fn release(&mut self, key_code: KeyCode) -> Result<()> {
+        if key_code.is_mouse_button() {
+            self.finish_operation_mouse()?;
+            let frame = self.mouse_report.release(key_code)?;
+            return self.write_mouse_frames(&[frame]);
+        }
+        self.keyboard_report.release(key_code)?;
+        self.finish_operation_keyboard()
+    }
+}
+
+// HID usage, the same codes the hidg backend ends up writing
+#[cfg(feature = "use_uhid")]
+impl KeyCode {
+    pub fn convert(&self) -> Result<u16> {
+        match self.hid_usage() {
+            Some(usage) => Ok(usage),
+            None => bail!("No such key code: {self}"),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "use_uhid"))]
+mod tests {
+    use super::*;
+    use crate::uhid::tests::Capture;
+
+    fn name(event: &[u8]) -> &[u8] {
+        let field = &event[4..4 + 128];
+        &field[..field.iter().position(|byte| *byte == 0).unwrap()]
+    }
+
+    #[test]
+    fn writers_keep_device_names() {
+        let (keyboard, mouse) = (Capture::default(), Capture::default());
+        let mut emulator = InputEmulator::from_writers(keyboard.clone(), mouse.clone()).unwrap();
+        assert_eq!(name(&keyboard.take()), b"universal_input keyboard");
+        assert_eq!(name(&mouse.take()), b"universal_input mouse");
+
+        emulator.move_mouse(300, 0).unwrap();
+        let reports: Vec<_> = mouse.take().chunks(6 + 5).map(|event| event[6..].to_vec()).collect();
+        assert_eq!(reports, vec![vec![0, 127, 0, 0, 0], vec![0, 127, 0, 0, 0], vec![0, 46, 0, 0, 0]]);
+    }
+}
@@ -0,0 +1,305 @@
+use color_eyre::eyre::bail;
+use color_eyre::{Report, Result};
+use crate::{exec_or_eyre, InputBatch, InputEvent, KeyCode, KeyCodes, OS_Input_Coord};
+
+#[cfg(feature = "use_uinput")]
+use std::io::Write;
+#[cfg(feature = "use_uinput")]
+use strum::IntoEnumIterator;
+#[cfg(feature = "use_uinput")]
+use crate::key_codes::{EV_KEY, EV_REL, REL_HWHEEL, REL_WHEEL, REL_X, REL_Y};
+#[cfg(feature = "use_uinput")]
+use crate::uinput::{EventParams, UinputBuilder, UinputDevice};
+
+#[cfg(feature = "use_uinput")]
+pub struct InputEmulator {
+    device: UinputDevice,
+}
+
+#[cfg(feature = "use_uinput")]
+#[derive(Clone, Debug)]
+pub struct InputEmulatorBuilder {
+    uinput_builder: UinputBuilder,
+    key_bits: KeyCodes,
+}
+
+#[cfg(feature = "use_uinput")]
+impl Default for InputEmulatorBuilder {
+    fn default() -> Self {
+        Self {
+            uinput_builder: UinputBuilder::default()
+                .rel_bits(&[REL_X, REL_Y, REL_WHEEL, REL_HWHEEL]),
+            key_bits: KeyCode::iter().filter(|key_code| key_code.convert().is_ok()).collect(),
+        }
+    }
+}
+
+#[cfg(feature = "use_uinput")]
+impl InputEmulatorBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn name(mut self, name: &str) -> Self {
+        self.uinput_builder = self.uinput_builder.name(name);
+        self
+    }
+
+    pub fn bus_type(mut self, bus_type: u16) -> Self {
+        self.uinput_builder = self.uinput_builder.bus_type(bus_type);
+        self
+    }
+
+    pub fn vendor(mut self, vendor: u16) -> Self {
+        self.uinput_builder = self.uinput_builder.vendor(vendor);
+        self
+    }
+
+    pub fn product(mut self, product: u16) -> Self {
+        self.uinput_builder = self.uinput_builder.product(product);
+        self
+    }
+
+    pub fn version(mut self, version: u16) -> Self {
+        self.uinput_builder = self.uinput_builder.version(version);
+        self
+    }
+
+    pub fn phys(mut self, phys: &str) -> Self {
+        self.uinput_builder = self.uinput_builder.phys(phys);
+        self
+    }
+
+    pub fn key_bits(mut self, key_bits: &[KeyCode]) -> Self {
+        self.key_bits = key_bits.to_vec();
+        self
+    }
+
+    pub fn rel_bits(mut self, rel_bits: &[u16]) -> Self {
+        self.uinput_builder = self.uinput_builder.rel_bits(rel_bits);
+        self
+    }
+
+    pub fn build(self) -> Result<InputEmulator> {
+        let key_bits = self.key_bits.iter()
+            .map(|key_code| key_code.convert())
+            .collect::<Result<Vec<u16>>>()?;
+
+        Ok(InputEmulator {
+            device: self.uinput_builder.key_bits(&key_bits).create()?,
+        })
+    }
+}
+
+#[cfg(feature = "use_uinput")]
+impl InputEmulator {
+    pub fn builder() -> InputEmulatorBuilder {
+        InputEmulatorBuilder::default()
+    }
+
+    pub fn new() -> Result<Self> {
+        InputEmulatorBuilder::default().build()
+    }
+
+    pub fn from_writer<W: Write + Send + 'static>(writer: W) -> Self {
+        Self {
+            device: UinputDevice::from_writer(writer),
+        }
+    }
+
+    // Unique methods
+
+    #[inline]
+    pub fn finish_operation_mouse(&mut self) -> Result<()> {
+        self.device.synchronize()
+    }
+
+    #[inline]
+    pub fn finish_operation_keyboard(&mut self) -> Result<()> {
+        self.device.synchronize()
+    }
+
+    pub fn write_buffer(&mut self, batch: &InputBatch) -> Result<()> {
+        let mut buffer: Vec<EventParams> = vec![];
+
+        for event in batch {
+            match *event {
+                InputEvent::Move(x, y) => {
+                    if x != 0 {
+                        buffer.push((EV_REL, REL_X, x));
+                    }
+                    if y != 0 {
+                        buffer.push((EV_REL, REL_Y, -y));
+                    }
+                }
+                InputEvent::Scroll(x, y) => {
+                    if x != 0 {
+                        buffer.push((EV_REL, REL_HWHEEL, x));
+                    }
+                    if y != 0 {
+                        buffer.push((EV_REL, REL_WHEEL, y));
+                    }
+                }
+                InputEvent::Press(key_code) => {
+                    buffer.push((EV_KEY, key_code.convert()?, 1));
+                }
+                InputEvent::Release(key_code) => {
+                    buffer.push((EV_KEY, key_code.convert()?, 0));
+                }
+                InputEvent::Sync => {
+                    self.device.write_batch(&buffer)?;
+                    self.device.synchronize()?;
+                    buffer.clear();
+                }
+            }
+        }
+
+        if !buffer.is_empty() {
+            self.device.write_batch(&buffer)?;
+            self.device.synchronize()?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn move_mouse_raw_x(&mut self, x: OS_Input_Coord) -> Result<()> {
+        self.device.write_event(EV_REL, REL_X, x)
+    }
+
+    #[inline]
+    pub fn move_mouse_raw_y(&mut self, y: OS_Input_Coord) -> Result<()> {
+        self.device.write_event(EV_REL, REL_Y, -y)
+    }
+
+    #[inline]
+    pub fn move_mouse_raw(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        self.device.write_batch(&[(EV_REL, REL_X, x), (EV_REL, REL_Y, -y)])
+    }
+
+    #[inline]
+    pub fn scroll_raw_x(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.device.write_event(EV_REL, REL_HWHEEL, value)
+    }
+
+    #[inline]
+    pub fn scroll_raw_y(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.device.write_event(EV_REL, REL_WHEEL, value)
+    }
+
+    // Common methods
+
+    #[inline]
+    pub fn move_mouse_x(&mut self, x: OS_Input_Coord) -> Result<()> {
+        self.move_mouse_raw_x(x)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn move_mouse_y(&mut self, y: OS_Input_Coord) -> Result<()> {
+        self.move_mouse_raw_y(y)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn move_mouse(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        self.move_mouse_raw(x, y)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn scroll_x(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.scroll_raw_x(value)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn scroll_y(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.scroll_raw_y(value)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn press(&mut self, key_code: KeyCode) -> Result<()> {
+        let button = key_code.convert()?;
+        self.device.write_event(EV_KEY, button, 1)?;
+        self.finish_operation_keyboard()
+    }
+
+    #[inline]
+    pub fn release(&mut self, key_code: KeyCode) -> Result<()> {
+        let button = key_code.convert()?;
+        self.device.write_event(EV_KEY, button, 0)?;
+        self.finish_operation_keyboard()
+    }
+}
+
+#[cfg(feature = "use_uinput")]
+impl KeyCode {
+    pub fn convert(&self) -> Result<u16> {
+        match self.evdev_code() {
+            Some(code) => Ok(code),
+            None => bail!("No such key code: {self}"),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "use_uinput"))]
+mod tests {
+    use std::io::Read;
+    use crate::key_codes::{BTN_LEFT, EV_SYN, KEY_A, SYN_REPORT};
+    use crate::uinput::tests::event_bytes;
+    use super::*;
+
+    fn capture(f: impl FnOnce(&mut InputEmulator) -> Result<()>) -> Vec<u8> {
+        let (mut reader, writer) = std::io::pipe().unwrap();
+        let mut emulator = InputEmulator::from_writer(writer);
+        f(&mut emulator).unwrap();
+        drop(emulator);
+
+        let mut written = vec![];
+        reader.read_to_end(&mut written).unwrap();
+        written
+    }
+
+    #[test]
+    fn press_and_release() {
+        let written = capture(|emulator| {
+            emulator.press(KeyCode::KEY_A)?;
+            emulator.release(KeyCode::KEY_A)
+        });
+        assert_eq!(written, [
+            event_bytes(EV_KEY, KEY_A, 1),
+            event_bytes(EV_SYN, SYN_REPORT, 0),
+            event_bytes(EV_KEY, KEY_A, 0),
+            event_bytes(EV_SYN, SYN_REPORT, 0),
+        ].concat());
+    }
+
+    #[test]
+    fn move_negates_y() {
+        let written = capture(|emulator| emulator.move_mouse(3, 4));
+        assert_eq!(written, [
+            event_bytes(EV_REL, REL_X, 3),
+            event_bytes(EV_REL, REL_Y, -4),
+            event_bytes(EV_SYN, SYN_REPORT, 0),
+        ].concat());
+    }
+
+    #[test]
+    fn batch_keeps_order_and_syncs() {
+        let mut batch = InputBatch::new();
+        batch.move_mouse(0, -2);
+        batch.press(KeyCode::MOUSE_LEFT);
+        batch.scroll_y(1);
+        batch.release(KeyCode::MOUSE_LEFT);
+
+        let written = capture(|emulator| emulator.write_buffer(&batch));
+        assert_eq!(written, [
+            event_bytes(EV_REL, REL_Y, 2),
+            event_bytes(EV_KEY, BTN_LEFT, 1),
+            event_bytes(EV_REL, REL_WHEEL, 1),
+            event_bytes(EV_KEY, BTN_LEFT, 0),
+            event_bytes(EV_SYN, SYN_REPORT, 0),
+        ].concat());
+    }
+}
@@ -0,0 +1,181 @@
+use color_eyre::eyre::bail;
+use color_eyre::Result;
+use crate::{KeyCode, OS_Input_Coord};
+
+#[cfg(feature = "use_wayland")]
+use std::os::unix::net::UnixStream;
+#[cfg(feature = "use_wayland")]
+use wayland_client::protocol::wl_pointer::Axis;
+#[cfg(feature = "use_wayland")]
+use crate::wayland::{is_xkb_lock, xkb_modifier, WaylandVirtualInput};
+
+#[cfg(feature = "use_wayland")]
+pub struct InputEmulator {
+    input: WaylandVirtualInput,
+    depressed_modifiers: u32,
+    locked_modifiers: u32,
+    pending_move: (OS_Input_Coord, OS_Input_Coord),
+    pending_scroll: (OS_Input_Coord, OS_Input_Coord),
+}
+
+#[cfg(feature = "use_wayland")]
+impl InputEmulator {
+    pub fn new() -> Result<Self> {
+        Ok(Self::from_input(WaylandVirtualInput::connect()?))
+    }
+
+    // Connected socket of a compositor, e.g. a nested or headless one started for tests
+    pub fn from_socket(stream: UnixStream) -> Result<Self> {
+        Ok(Self::from_input(WaylandVirtualInput::from_socket(stream)?))
+    }
+
+    pub fn from_input(input: WaylandVirtualInput) -> Self {
+        Self {
+            input,
+            depressed_modifiers: 0,
+            locked_modifiers: 0,
+            pending_move: (0, 0),
+            pending_scroll: (0, 0),
+        }
+    }
+
+    fn update_modifiers(&mut self, key_code: KeyCode, down: bool) {
+        let Some(modifier) = xkb_modifier(key_code) else {
+            return;
+        };
+        if is_xkb_lock(modifier) {
+            if down {
+                self.locked_modifiers ^= modifier;
+            }
+        } else if down {
+            self.depressed_modifiers |= modifier;
+        } else {
+            self.depressed_modifiers &= !modifier;
+        }
+        self.input.modifiers(self.depressed_modifiers, self.locked_modifiers);
+    }
+
+    #[inline]
+    fn send_key(&mut self, key_code: KeyCode, down: bool) -> Result<()> {
+        let evdev = key_code.convert()?;
+        if key_code.is_mouse_button() {
+            self.finish_operation_mouse()?;
+            self.input.button(evdev, down);
+            self.input.frame();
+        } else {
+            self.input.key(evdev, down);
+            self.update_modifiers(key_code, down);
+        }
+        self.input.flush()
+    }
+
+    // Unique methods
+
+    #[inline]
+    pub fn finish_operation_mouse(&mut self) -> Result<()> {
+        let (x, y) = std::mem::take(&mut self.pending_move);
+        let (scroll_x, scroll_y) = std::mem::take(&mut self.pending_scroll);
+        if (x, y, scroll_x, scroll_y) == (0, 0, 0, 0) {
+            return Ok(());
+        }
+        if (x, y) != (0, 0) {
+            self.input.motion(x as f64, -(y as f64));
+        }
+        if scroll_y != 0 {
+            self.input.axis(Axis::VerticalScroll, scroll_y.saturating_neg());
+        }
+        if scroll_x != 0 {
+            self.input.axis(Axis::HorizontalScroll, scroll_x);
+        }
+        self.input.frame();
+        self.input.flush()
+    }
+
+    // Key events are sent right away
+    #[inline]
+    pub fn finish_operation_keyboard(&mut self) -> Result<()> {
+        self.input.flush()
+    }
+
+    #[inline]
+    pub fn move_mouse_raw_x(&mut self, x: OS_Input_Coord) -> Result<()> {
+        self.pending_move.0 = self.pending_move.0.saturating_add(x);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn move_mouse_raw_y(&mut self, y: OS_Input_Coord) -> Result<()> {
+        self.pending_move.1 = self.pending_move.1.saturating_add(y);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn move_mouse_raw(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        self.move_mouse_raw_x(x)?;
+        self.move_mouse_raw_y(y)
+    }
+
+    #[inline]
+    pub fn scroll_raw_x(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.pending_scroll.0 = self.pending_scroll.0.saturating_add(value);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn scroll_raw_y(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.pending_scroll.1 = self.pending_scroll.1.saturating_add(value);
+        Ok(())
+    }
+
+    // Common methods
+
+    #[inline]
+    pub fn move_mouse_x(&mut self, x: OS_Input_Coord) -> Result<()> {
+        self.move_mouse_raw_x(x)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn move_mouse_y(&mut self, y: OS_Input_Coord) -> Result<()> {
+        self.move_mouse_raw_y(y)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn move_mouse(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        self.move_mouse_raw(x, y)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn scroll_x(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.scroll_raw_x(value)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn scroll_y(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.scroll_raw_y(value)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn press(&mut self, key_code: KeyCode) -> Result<()> {
+        self.send_key(key_code, true)
+    }
+
+    #[inline]
+    pub fn release(&mut self, key_code: KeyCode) -> Result<()> {
+        self.send_key(key_code, false)
+    }
+}
+
+#[cfg(feature = "use_wayland")]
+impl KeyCode {
+    pub fn convert(&self) -> Result<u16> {
+        match self.evdev_code() {
+            Some(evdev) => Ok(evdev),
+            None => bail!("No such key code: {self}"),
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use color_eyre::eyre::bail;
+use color_eyre::Result;
+use crate::{KeyCode, OS_Input_Coord};
+
+#[cfg(feature = "use_xtest")]
+use crate::xtest::*;
+#[cfg(feature = "use_xtest")]
+use crate::utils::scroll_steps;
+
+#[cfg(feature = "use_xtest")]
+pub struct InputEmulator {
+    connection: XTestConnection,
+    pending_move: (OS_Input_Coord, OS_Input_Coord),
+    pending_scroll: (OS_Input_Coord, OS_Input_Coord),
+}
+
+#[cfg(feature = "use_xtest")]
+#[derive(Clone, Debug, Default)]
+pub struct InputEmulatorBuilder {
+    display: Option<String>,
+}
+
+#[cfg(feature = "use_xtest")]
+impl InputEmulatorBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Overrides $DISPLAY, e.g. ":99" for Xvfb
+    pub fn display(mut self, display: &str) -> Self {
+        self.display = Some(display.to_string());
+        self
+    }
+
+    pub fn build(self) -> Result<InputEmulator> {
+        Ok(InputEmulator::from_connection(XTestConnection::connect(self.display.as_deref())?))
+    }
+}
+
+#[cfg(feature = "use_xtest")]
+impl InputEmulator {
+    pub fn builder() -> InputEmulatorBuilder {
+        InputEmulatorBuilder::default()
+    }
+
+    pub fn new() -> Result<Self> {
+        InputEmulatorBuilder::default().build()
+    }
+
+    pub fn from_connection(connection: XTestConnection) -> Self {
+        Self {
+            connection,
+            pending_move: (0, 0),
+            pending_scroll: (0, 0),
+        }
+    }
+
+    fn button(key_code: KeyCode) -> Result<u8> {
+        Ok(match key_code {
+            KeyCode::MOUSE_LEFT => X11_BUTTON_LEFT,
+            KeyCode::MOUSE_MIDDLE => X11_BUTTON_MIDDLE,
+            KeyCode::MOUSE_RIGHT => X11_BUTTON_RIGHT,
+            KeyCode::MOUSE_SIDE | KeyCode::MOUSE_BACK => X11_BUTTON_BACK,
+            KeyCode::MOUSE_EXTRA | KeyCode::MOUSE_FORWARD => X11_BUTTON_FORWARD,
+            _ => bail!("No such key code: {key_code}"),
+        })
+    }
+
+    // Each wheel step is a click of the wheel button, up to MAX_SCROLL_STEPS
+    fn send_scroll_steps(&mut self, steps: OS_Input_Coord, positive: u8, negative: u8) -> Result<()> {
+        let button = if steps > 0 { positive } else { negative };
+        for _ in 0..scroll_steps(steps) {
+            self.connection.button(button, true)?;
+            self.connection.button(button, false)?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn send_key(&mut self, key_code: KeyCode, down: bool) -> Result<()> {
+        if key_code.is_mouse_button() {
+            self.finish_operation_mouse()?;
+            self.connection.button(Self::button(key_code)?, down)?;
+        } else {
+            let keycode = self.connection.keycode(key_code.convert()?)?;
+            self.connection.key(keycode, down)?;
+        }
+        self.connection.flush()
+    }
+
+    // Unique methods
+
+    // Root window coordinates, y grows downwards
+    #[inline]
+    pub fn move_mouse_to(&mut self, x: i16, y: i16) -> Result<()> {
+        self.connection.absolute_motion(x, y)?;
+        self.connection.flush()
+    }
+
+    #[inline]
+    pub fn finish_operation_mouse(&mut self) -> Result<()> {
+        let (x, y) = std::mem::take(&mut self.pending_move);
+        let (scroll_x, scroll_y) = std::mem::take(&mut self.pending_scroll);
+        if (x, y, scroll_x, scroll_y) == (0, 0, 0, 0) {
+            return Ok(());
+        }
+        if (x, y) != (0, 0) {
+            self.connection.relative_motion(x, y.saturating_neg())?;
+        }
+        self.send_scroll_steps(scroll_y, X11_WHEEL_UP, X11_WHEEL_DOWN)?;
+        self.send_scroll_steps(scroll_x, X11_WHEEL_RIGHT, X11_WHEEL_LEFT)?;
+        self.connection.flush()
+    }
+
+    // Key events are sent right away
+    #[inline]
+    pub fn finish_operation_keyboard(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    #[inline]
+    pub fn move_mouse_raw_x(&mut self, x: OS_Input_Coord) -> Result<()> {
+        self.pending_move.0 = self.pending_move.0.saturating_add(x);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn move_mouse_raw_y(&mut self, y: OS_Input_Coord) -> Result<()> {
+        self.pending_move.1 = self.pending_move.1.saturating_add(y);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn move_mouse_raw(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        self.move_mouse_raw_x(x)?;
+        self.move_mouse_raw_y(y)
+    }
+
+    #[inline]
+    pub fn scroll_raw_x(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.pending_scroll.0 = self.pending_scroll.0.saturating_add(value);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn scroll_raw_y(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.pending_scroll.1 = self.pending_scroll.1.saturating_add(value);
+        Ok(())
+    }
+
+    // Common methods
+
+    #[inline]
+    pub fn move_mouse_x(&mut self, x: OS_Input_Coord) -> Result<()> {
+        self.move_mouse_raw_x(x)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn move_mouse_y(&mut self, y: OS_Input_Coord) -> Result<()> {
+        self.move_mouse_raw_y(y)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn move_mouse(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
+        self.move_mouse_raw(x, y)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn scroll_x(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.scroll_raw_x(value)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn scroll_y(&mut self, value: OS_Input_Coord) -> Result<()> {
+        self.scroll_raw_y(value)?;
+        self.finish_operation_mouse()
+    }
+
+    #[inline]
+    pub fn press(&mut self, key_code: KeyCode) -> Result<()> {
+        self.send_key(key_code, true)
+    }
+
+    #[inline]
+    pub fn release(&mut self, key_code: KeyCode) -> Result<()> {
+        self.send_key(key_code, false)
+    }
+}
+
+#[cfg(feature = "use_xtest")]
+impl KeyCode {
+    pub fn convert(&self) -> Result<u32> {
+        match self.keysym() {
+            Some(keysym) => Ok(keysym),
+            None => bail!("No such key code: {self}"),
+        }
+    }
+}
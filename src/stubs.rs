@@ -3,76 +3,98 @@ use color_eyre::{Report, Result};
 use crate::{exec_or_eyre, KeyCode, OS_Input_Coord};
 
 use crate::utils::GradualMove;
-use crate::InputEmulator;
-
-pub type EventParams = (u16, u16, i32);
+use crate::{InputBatch, InputEmulator, InputEvent};
 
 impl InputEmulator {
-    #[cfg(all(not(feature = "use_mki"), not(feature = "use_hidg")))]
+    #[cfg(all(not(feature = "native_backend"), not(feature = "use_hidg")))]
     #[inline]
     pub fn finish_operation_mouse(&mut self) -> Result<()> {
         Ok(())
     }
 
-    #[cfg(all(not(feature = "use_mki"), not(feature = "use_hidg")))]
+    #[cfg(all(not(feature = "native_backend"), not(feature = "use_hidg")))]
     #[inline]
     pub fn finish_operation_keyboard(&mut self) -> Result<()> {
         Ok(())
     }
 
-    #[cfg(not(feature = "use_mki"))]
-    #[inline]
-    pub fn write_buffer(&mut self, buffer: &[EventParams]) -> Result<()> {
+    #[cfg(all(not(feature = "use_mki"), not(feature = "use_uinput"), not(feature = "use_qmp")))]
+    pub fn write_buffer(&mut self, batch: &InputBatch) -> Result<()> {
+        for event in batch {
+            match *event {
+                InputEvent::Move(x, y) => self.move_mouse_raw(x, y)?,
+                InputEvent::Scroll(x, y) => {
+                    if x != 0 {
+                        self.scroll_raw_x(x)?;
+                    }
+                    if y != 0 {
+                        self.scroll_raw_y(y)?;
+                    }
+                }
+                InputEvent::Press(key_code) => self.press(key_code)?,
+                InputEvent::Release(key_code) => self.release(key_code)?,
+                InputEvent::Sync => {
+                    self.finish_operation_mouse()?;
+                    self.finish_operation_keyboard()?;
+                }
+            }
+        }
+
+        if batch.is_pending_sync() {
+            self.finish_operation_mouse()?;
+            self.finish_operation_keyboard()?;
+        }
+
         Ok(())
     }
 
     // #[cfg(all(not(feature = "use-mki"), not(feature = "use-hidg")))]
-    #[cfg(not(feature = "use_mki"))]
+    #[cfg(not(feature = "native_backend"))]
     #[inline]
     pub fn move_mouse_raw_x(&mut self, x: OS_Input_Coord) -> Result<()> {
         self.move_mouse_x(x)
     }
 
     // #[cfg(all(not(feature = "use-mki"), not(feature = "use-hidg")))]
-    #[cfg(not(feature = "use_mki"))]
+    #[cfg(not(feature = "native_backend"))]
     #[inline]
     pub fn move_mouse_raw_y(&mut self, y: OS_Input_Coord) -> Result<()> {
         self.move_mouse_y(y)
     }
 
     // #[cfg(all(not(feature = "use-mki"), not(feature = "use-hidg")))]
-    #[cfg(not(feature = "use_mki"))]
+    #[cfg(not(feature = "native_backend"))]
     #[inline]
     pub fn move_mouse_raw(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Result<()> {
         self.move_mouse(x, y)
     }
 
-    #[cfg(not(feature = "use_mki"))]
     #[inline]
-    pub fn buffered_move_mouse_x(&mut self, x: OS_Input_Coord) -> Vec<EventParams> {
-        self.move_mouse_x(x).unwrap();
-        vec![]
+    pub fn buffered_move_mouse_x(&self, x: OS_Input_Coord) -> InputBatch {
+        let mut batch = InputBatch::new();
+        batch.move_mouse_x(x);
+        batch
     }
 
-    #[cfg(not(feature = "use_mki"))]
     #[inline]
-    pub fn buffered_move_mouse_y(&mut self, y: OS_Input_Coord) -> Vec<EventParams> {
-        self.move_mouse_y(y).unwrap();
-        vec![]
+    pub fn buffered_move_mouse_y(&self, y: OS_Input_Coord) -> InputBatch {
+        let mut batch = InputBatch::new();
+        batch.move_mouse_y(y);
+        batch
     }
 
-    #[cfg(not(feature = "use_mki"))]
     #[inline]
-    pub fn buffered_move_mouse(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Vec<EventParams> {
-        self.move_mouse(x, y).unwrap();
-        vec![]
+    pub fn buffered_move_mouse(&self, x: OS_Input_Coord, y: OS_Input_Coord) -> InputBatch {
+        let mut batch = InputBatch::new();
+        batch.move_mouse(x, y);
+        batch
     }
 
-    #[cfg(not(feature = "use_mki"))]
     #[inline]
-    pub fn buffered_gradual_move_mouse(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Vec<EventParams> {
-        self.gradual_move_mouse_raw(x, y).unwrap();
-        vec![]
+    pub fn buffered_gradual_move_mouse(&self, x: OS_Input_Coord, y: OS_Input_Coord) -> InputBatch {
+        let mut batch = InputBatch::new();
+        batch.gradual_move_mouse(x, y);
+        batch
     }
 
     #[cfg(not(feature = "use_mki"))]
@@ -114,38 +136,38 @@ impl InputEmulator {
     }
 
     // #[cfg(all(not(feature = "use-mki"), not(feature = "use-hidg")))]
-    #[cfg(not(feature = "use_mki"))]
+    #[cfg(not(feature = "native_backend"))]
     #[inline]
     pub fn scroll_raw_x(&mut self, value: OS_Input_Coord) -> Result<()> {
         self.scroll_x(value)
     }
 
     // #[cfg(all(not(feature = "use-mki"), not(feature = "use-hidg")))]
-    #[cfg(not(feature = "use_mki"))]
+    #[cfg(not(feature = "native_backend"))]
     #[inline]
     pub fn scroll_raw_y(&mut self, value: OS_Input_Coord) -> Result<()> {
         self.scroll_y(value)
     }
 
-    #[cfg(not(feature = "use_mki"))]
     #[inline]
-    pub fn buffered_scroll_x(&mut self, x: OS_Input_Coord) -> Vec<EventParams> {
-        self.scroll_x(x).unwrap();
-        vec![]
+    pub fn buffered_scroll_x(&self, x: OS_Input_Coord) -> InputBatch {
+        let mut batch = InputBatch::new();
+        batch.scroll_x(x);
+        batch
     }
 
-    #[cfg(not(feature = "use_mki"))]
     #[inline]
-    pub fn buffered_scroll_y(&mut self, y: OS_Input_Coord) -> Vec<EventParams> {
-        self.scroll_y(y).unwrap();
-        vec![]
+    pub fn buffered_scroll_y(&self, y: OS_Input_Coord) -> InputBatch {
+        let mut batch = InputBatch::new();
+        batch.scroll_y(y);
+        batch
     }
 
-    #[cfg(not(feature = "use_mki"))]
     #[inline]
-    pub fn buffered_gradual_scroll(&mut self, x: OS_Input_Coord, y: OS_Input_Coord) -> Vec<EventParams> {
-        self.gradual_scroll(x, y).unwrap();
-        vec![]
+    pub fn buffered_gradual_scroll(&self, x: OS_Input_Coord, y: OS_Input_Coord) -> InputBatch {
+        let mut batch = InputBatch::new();
+        batch.gradual_scroll(x, y);
+        batch
     }
 
     #[cfg(not(feature = "use_mki"))]
@@ -188,17 +210,76 @@ impl InputEmulator {
         Ok(())
     }
 
-    #[cfg(not(feature = "use_mki"))]
     #[inline]
-    pub fn buffered_press(&mut self, key_code: KeyCode) -> Result<Vec<EventParams>> {
-        self.press(key_code)?;
-        Ok(vec![])
+    pub fn buffered_press(&self, key_code: KeyCode) -> InputBatch {
+        let mut batch = InputBatch::new();
+        batch.press(key_code);
+        batch
     }
 
-    #[cfg(not(feature = "use_mki"))]
     #[inline]
-    pub fn buffered_release(&mut self, key_code: KeyCode) -> Result<Vec<EventParams>> {
-        self.release(key_code)?;
-        Ok(vec![])
+    pub fn buffered_release(&self, key_code: KeyCode) -> InputBatch {
+        let mut batch = InputBatch::new();
+        batch.release(key_code);
+        batch
+    }
+}
+// The generic write_buffer, exercised through the uhid backend's report capture
+#[cfg(all(test, feature = "use_uhid"))]
+mod tests {
+    use crate::uhid::tests::Capture;
+    use super::*;
+
+    const KEY_A_USAGE: u8 = 0x04;
+
+    // Reports after the UHID_CREATE2 event, each behind the 4 byte type and 2 byte size
+    fn reports(capture: &Capture, report_size: usize) -> Vec<Vec<u8>> {
+        capture.take().chunks(6 + report_size).map(|event| event[6..].to_vec()).collect()
+    }
+
+    fn emulator() -> (Capture, Capture, InputEmulator) {
+        let (keyboard, mouse) = (Capture::default(), Capture::default());
+        let emulator = InputEmulator::from_writers(keyboard.clone(), mouse.clone()).unwrap();
+        keyboard.take();
+        mouse.take();
+        (keyboard, mouse, emulator)
+    }
+
+    #[test]
+    fn moves_wait_for_sync() {
+        let (keyboard, mouse, mut emulator) = emulator();
+        let mut batch = InputBatch::new();
+        batch.move_mouse(3, 0).move_mouse(2, 0).sync().press(KeyCode::KEY_A).scroll_y(1).release(KeyCode::KEY_A);
+        emulator.write_buffer(&batch).unwrap();
+
+        // Both moves in one report at the Sync, the scroll at the implicit sync after the last event
+        assert_eq!(reports(&mouse, 5), [[0, 5, 0, 0, 0], [0, 0, 0, 1, 0]]);
+        let key_a = [0, 0, KEY_A_USAGE, 0, 0, 0, 0, 0];
+        let empty = [0; 8];
+        assert_eq!(reports(&keyboard, 8), [empty, key_a, empty, empty]);
+    }
+
+    #[test]
+    fn trailing_sync_is_not_doubled() {
+        let (keyboard, mouse, mut emulator) = emulator();
+        let mut batch = InputBatch::new();
+        batch.move_mouse_x(1).sync();
+        emulator.write_buffer(&batch).unwrap();
+        assert_eq!(reports(&mouse, 5).len(), 1);
+        assert_eq!(reports(&keyboard, 8).len(), 1);
+
+        emulator.write_buffer(&InputBatch::new()).unwrap();
+        assert!(mouse.take().is_empty());
+        assert!(keyboard.take().is_empty());
+    }
+
+    #[test]
+    fn stops_at_the_first_error() {
+        let (keyboard, mouse, mut emulator) = emulator();
+        let mut batch = InputBatch::new();
+        batch.press(KeyCode::KEY_A).press(KeyCode::None).release(KeyCode::KEY_A);
+        assert!(emulator.write_buffer(&batch).is_err());
+        assert_eq!(reports(&keyboard, 8), [[0, 0, KEY_A_USAGE, 0, 0, 0, 0, 0]]);
+        assert!(mouse.take().is_empty());
     }
-}
\ No newline at end of file
+}
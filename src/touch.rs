@@ -0,0 +1,515 @@
+use std::time::Duration;
+
+#[cfg(feature = "uinput")]
+use std::io::Write;
+#[cfg(feature = "uinput")]
+use std::thread::sleep;
+#[cfg(feature = "uinput")]
+use color_eyre::eyre::bail;
+#[cfg(feature = "uinput")]
+use color_eyre::Result;
+#[cfg(feature = "uinput")]
+use crate::key_codes::*;
+#[cfg(feature = "uinput")]
+use crate::uinput::{AbsInfo, EventParams, UinputBuilder, UinputDevice};
+
+pub const MAX_TOUCH_SLOTS: usize = 10;
+pub const GESTURE_FRAME_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum TouchKind {
+    // Direct input, positions map onto the screen
+    Touchscreen,
+    // Indirect input, libinput turns finger motion into pointer motion and gestures
+    Touchpad,
+}
+
+#[cfg(feature = "uinput")]
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+struct TouchSlot {
+    tracking_id: i32,
+    x: i32,
+    y: i32,
+}
+
+#[cfg(feature = "uinput")]
+pub struct VirtualTouch {
+    device: UinputDevice,
+    width: i32,
+    height: i32,
+    slots: [Option<TouchSlot>; MAX_TOUCH_SLOTS],
+    // Slots as of the last frame the device got
+    reported_slots: [Option<TouchSlot>; MAX_TOUCH_SLOTS],
+    current_slot: Option<usize>,
+    next_tracking_id: i32,
+    reported_fingers: usize,
+    pending: Vec<EventParams>,
+}
+
+#[cfg(feature = "uinput")]
+impl VirtualTouch {
+    pub fn uinput_builder(kind: TouchKind, width: i32, height: i32) -> UinputBuilder {
+        let (name, prop) = match kind {
+            TouchKind::Touchscreen => ("universal_input touchscreen", INPUT_PROP_DIRECT),
+            TouchKind::Touchpad => ("universal_input touchpad", INPUT_PROP_POINTER),
+        };
+
+        UinputBuilder::new()
+            .name(name)
+            .bus_type(BUS_VIRTUAL)
+            .key_bits(&[
+                BTN_TOUCH,
+                BTN_TOOL_FINGER,
+                BTN_TOOL_DOUBLETAP,
+                BTN_TOOL_TRIPLETAP,
+                BTN_TOOL_QUADTAP,
+                BTN_TOOL_QUINTTAP,
+            ])
+            .abs_axis(ABS_X, AbsInfo::new(0, width - 1).resolution(12))
+            .abs_axis(ABS_Y, AbsInfo::new(0, height - 1).resolution(12))
+            .abs_axis(ABS_MT_SLOT, AbsInfo::new(0, MAX_TOUCH_SLOTS as i32 - 1))
+            .abs_axis(ABS_MT_TRACKING_ID, AbsInfo::new(0, u16::MAX as i32))
+            .abs_axis(ABS_MT_POSITION_X, AbsInfo::new(0, width - 1).resolution(12))
+            .abs_axis(ABS_MT_POSITION_Y, AbsInfo::new(0, height - 1).resolution(12))
+            .prop_bit(prop)
+    }
+
+    pub fn new(kind: TouchKind, width: i32, height: i32) -> Result<Self> {
+        let device = Self::uinput_builder(kind, width, height).create()?;
+        Self::from_device(device, width, height)
+    }
+
+    pub fn from_writer<W: Write + Send + 'static>(writer: W, width: i32, height: i32) -> Result<Self> {
+        Self::from_device(UinputDevice::from_writer(writer), width, height)
+    }
+
+    fn from_device(device: UinputDevice, width: i32, height: i32) -> Result<Self> {
+        if width <= 0 || height <= 0 {
+            bail!("Touch surface must have positive size, got {width}x{height}");
+        }
+
+        Ok(Self {
+            device,
+            width,
+            height,
+            slots: [None; MAX_TOUCH_SLOTS],
+            reported_slots: [None; MAX_TOUCH_SLOTS],
+            current_slot: None,
+            next_tracking_id: 0,
+            reported_fingers: 0,
+            pending: vec![],
+        })
+    }
+
+    #[inline]
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    #[inline]
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    #[inline]
+    pub fn is_touching(&self, id: usize) -> bool {
+        self.slots.get(id).is_some_and(|slot| slot.is_some())
+    }
+
+    #[inline]
+    pub fn active_touches(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.is_some()).count()
+    }
+
+    fn check_slot(&self, id: usize) -> Result<()> {
+        if id >= MAX_TOUCH_SLOTS {
+            bail!("Touch id {id} is out of range, max is {}", MAX_TOUCH_SLOTS - 1);
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn clamp_position(&self, x: i32, y: i32) -> (i32, i32) {
+        (x.clamp(0, self.width - 1), y.clamp(0, self.height - 1))
+    }
+
+    #[inline]
+    fn select_slot(&mut self, id: usize) {
+        if self.current_slot != Some(id) {
+            self.pending.push((EV_ABS, ABS_MT_SLOT, id as i32));
+            self.current_slot = Some(id);
+        }
+    }
+
+    // Raw methods queue events for the current frame, synchronize() sends it
+
+    pub fn touch_down_raw(&mut self, id: usize, x: i32, y: i32) -> Result<()> {
+        self.check_slot(id)?;
+        if self.is_touching(id) {
+            bail!("Touch {id} is already down");
+        }
+
+        let (x, y) = self.clamp_position(x, y);
+        let tracking_id = self.next_tracking_id;
+        self.next_tracking_id = (self.next_tracking_id + 1) % (u16::MAX as i32 + 1);
+        self.slots[id] = Some(TouchSlot { tracking_id, x, y });
+
+        self.select_slot(id);
+        self.pending.extend([
+            (EV_ABS, ABS_MT_TRACKING_ID, tracking_id),
+            (EV_ABS, ABS_MT_POSITION_X, x),
+            (EV_ABS, ABS_MT_POSITION_Y, y),
+        ]);
+        Ok(())
+    }
+
+    pub fn touch_move_raw(&mut self, id: usize, x: i32, y: i32) -> Result<()> {
+        self.check_slot(id)?;
+        let (x, y) = self.clamp_position(x, y);
+        let Some(slot) = self.slots[id].as_mut() else {
+            bail!("Touch {id} is not down");
+        };
+
+        let moved_x = slot.x != x;
+        let moved_y = slot.y != y;
+        slot.x = x;
+        slot.y = y;
+
+        if moved_x || moved_y {
+            self.select_slot(id);
+        }
+        if moved_x {
+            self.pending.push((EV_ABS, ABS_MT_POSITION_X, x));
+        }
+        if moved_y {
+            self.pending.push((EV_ABS, ABS_MT_POSITION_Y, y));
+        }
+        Ok(())
+    }
+
+    pub fn touch_up_raw(&mut self, id: usize) -> Result<()> {
+        self.check_slot(id)?;
+        if self.slots[id].take().is_none() {
+            bail!("Touch {id} is not down");
+        }
+
+        self.select_slot(id);
+        self.pending.push((EV_ABS, ABS_MT_TRACKING_ID, -1));
+        Ok(())
+    }
+
+    fn tool_code(fingers: usize) -> Option<u16> {
+        match fingers {
+            0 => None,
+            1 => Some(BTN_TOOL_FINGER),
+            2 => Some(BTN_TOOL_DOUBLETAP),
+            3 => Some(BTN_TOOL_TRIPLETAP),
+            4 => Some(BTN_TOOL_QUADTAP),
+            _ => Some(BTN_TOOL_QUINTTAP),
+        }
+    }
+
+    // Closes the frame: adds single-touch emulation and finger count, then EV_SYN
+    pub fn synchronize(&mut self) -> Result<()> {
+        let fingers = self.active_touches();
+
+        if fingers != self.reported_fingers {
+            if self.reported_fingers == 0 {
+                self.pending.push((EV_KEY, BTN_TOUCH, 1));
+            }
+            if fingers == 0 {
+                self.pending.push((EV_KEY, BTN_TOUCH, 0));
+            }
+            if let Some(code) = Self::tool_code(self.reported_fingers) {
+                self.pending.push((EV_KEY, code, 0));
+            }
+            if let Some(code) = Self::tool_code(fingers) {
+                self.pending.push((EV_KEY, code, 1));
+            }
+        }
+
+        if let Some(slot) = self.slots.iter().flatten().next() {
+            self.pending.extend([
+                (EV_ABS, ABS_X, slot.x),
+                (EV_ABS, ABS_Y, slot.y),
+            ]);
+        }
+
+        // A failed frame is rolled back to what the device last got instead of being sent
+        // with the next one, the next frame selects its slot again
+        let written = self.device.write_batch(&self.pending);
+        self.pending.clear();
+        if written.is_err() {
+            self.slots = self.reported_slots;
+            self.current_slot = None;
+            return written;
+        }
+        self.reported_slots = self.slots;
+        self.reported_fingers = fingers;
+        self.device.synchronize()
+    }
+
+    #[inline]
+    pub fn touch_down(&mut self, id: usize, x: i32, y: i32) -> Result<()> {
+        self.touch_down_raw(id, x, y)?;
+        self.synchronize()
+    }
+
+    #[inline]
+    pub fn touch_move(&mut self, id: usize, x: i32, y: i32) -> Result<()> {
+        self.touch_move_raw(id, x, y)?;
+        self.synchronize()
+    }
+
+    #[inline]
+    pub fn touch_up(&mut self, id: usize) -> Result<()> {
+        self.touch_up_raw(id)?;
+        self.synchronize()
+    }
+
+    pub fn release_all(&mut self) -> Result<()> {
+        for id in 0..MAX_TOUCH_SLOTS {
+            if self.is_touching(id) {
+                self.touch_up_raw(id)?;
+            }
+        }
+        self.synchronize()
+    }
+
+    // Gestures use touch ids starting from 0, which must not be in use
+
+    fn check_gesture_fingers(&self, fingers: usize) -> Result<()> {
+        if fingers == 0 || fingers > MAX_TOUCH_SLOTS {
+            bail!("Gesture needs 1 to {MAX_TOUCH_SLOTS} fingers, got {fingers}");
+        }
+        if (0..fingers).any(|id| self.is_touching(id)) {
+            bail!("Gesture touch ids are already in use");
+        }
+        Ok(())
+    }
+
+    fn run_gesture(
+        &mut self,
+        positions: impl Fn(usize, f64) -> (i32, i32),
+        fingers: usize,
+        duration: Duration,
+    ) -> Result<()> {
+        self.check_gesture_fingers(fingers)?;
+        let result = self.move_gesture(&positions, fingers, duration);
+
+        // Fingers are lifted even when the gesture fails halfway, the first error is kept
+        for id in 0..fingers {
+            if self.is_touching(id) {
+                self.touch_up_raw(id)?;
+            }
+        }
+        let lifted = self.synchronize();
+        result?;
+        lifted
+    }
+
+    fn move_gesture(
+        &mut self,
+        positions: &impl Fn(usize, f64) -> (i32, i32),
+        fingers: usize,
+        duration: Duration,
+    ) -> Result<()> {
+        let steps = (duration.as_millis() / GESTURE_FRAME_INTERVAL.as_millis()).max(1) as u32;
+
+        for id in 0..fingers {
+            let (x, y) = positions(id, 0.0);
+            self.touch_down_raw(id, x, y)?;
+        }
+        self.synchronize()?;
+
+        for step in 1..=steps {
+            sleep(GESTURE_FRAME_INTERVAL);
+            let progress = step as f64 / steps as f64;
+            for id in 0..fingers {
+                let (x, y) = positions(id, progress);
+                self.touch_move_raw(id, x, y)?;
+            }
+            self.synchronize()?;
+        }
+        Ok(())
+    }
+
+    pub fn swipe(
+        &mut self,
+        fingers: usize,
+        x: i32,
+        y: i32,
+        dx: i32,
+        dy: i32,
+        duration: Duration,
+    ) -> Result<()> {
+        let spacing = (self.width / 20).max(1);
+        let first = x - spacing * (fingers as i32 - 1) / 2;
+
+        self.run_gesture(
+            |id, progress| {
+                (
+                    first + spacing * id as i32 + (dx as f64 * progress) as i32,
+                    y + (dy as f64 * progress) as i32,
+                )
+            },
+            fingers,
+            duration,
+        )
+    }
+
+    #[inline]
+    pub fn two_finger_scroll(&mut self, x: i32, y: i32, dx: i32, dy: i32, duration: Duration) -> Result<()> {
+        self.swipe(2, x, y, dx, dy, duration)
+    }
+
+    // Fingers move apart (zoom in) when end_distance > start_distance
+    pub fn pinch(
+        &mut self,
+        center_x: i32,
+        center_y: i32,
+        start_distance: i32,
+        end_distance: i32,
+        duration: Duration,
+    ) -> Result<()> {
+        self.run_gesture(
+            |id, progress| {
+                let distance = start_distance as f64 + (end_distance - start_distance) as f64 * progress;
+                let offset = (distance / 2.0) as i32;
+                match id {
+                    0 => (center_x - offset, center_y),
+                    _ => (center_x + offset, center_y),
+                }
+            },
+            2,
+            duration,
+        )
+    }
+}
+
+#[cfg(all(test, feature = "uinput"))]
+mod tests {
+    use std::io;
+    use std::sync::{Arc, Mutex};
+    use crate::uinput::tests::decode_events;
+    use super::*;
+
+    // Fails the `fail_at`-th write once, keeps everything else
+    struct FlakyWriter {
+        written: Arc<Mutex<Vec<u8>>>,
+        writes: usize,
+        fail_at: usize,
+    }
+
+    impl Write for FlakyWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.writes += 1;
+            if self.writes == self.fail_at {
+                return Err(io::Error::other("flaky"));
+            }
+            self.written.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn failed_gesture_lifts_fingers() {
+        let written = Arc::new(Mutex::new(vec![]));
+        // Writes 1 and 2 are the touch down frame, 3 is the first move
+        let writer = FlakyWriter { written: written.clone(), writes: 0, fail_at: 3 };
+        let mut touch = VirtualTouch::from_writer(writer, 1000, 1000).unwrap();
+
+        let result = touch.swipe(2, 500, 500, 100, 0, GESTURE_FRAME_INTERVAL * 2);
+        assert!(result.is_err());
+        assert_eq!(touch.active_touches(), 0);
+
+        let events = decode_events(&written.lock().unwrap());
+        let lifted = events.iter().filter(|event| **event == (EV_ABS, ABS_MT_TRACKING_ID, -1)).count();
+        assert_eq!(lifted, 2);
+        assert!(events.contains(&(EV_KEY, BTN_TOUCH, 0)));
+        assert_eq!(events.last(), Some(&(EV_SYN, SYN_REPORT, 0)));
+    }
+
+    const SYN: EventParams = (EV_SYN, SYN_REPORT, 0);
+
+    #[test]
+    fn multi_touch_protocol_b() {
+        let (mut reader, writer) = io::pipe().unwrap();
+        let mut touch = VirtualTouch::from_writer(writer, 1000, 1000).unwrap();
+        touch.touch_down(0, 10, 20).unwrap();
+        touch.touch_down(1, 30, 2000).unwrap();
+        touch.touch_move(0, 15, 20).unwrap();
+        touch.touch_up(1).unwrap();
+        touch.touch_up(0).unwrap();
+        drop(touch);
+
+        let mut written = vec![];
+        io::Read::read_to_end(&mut reader, &mut written).unwrap();
+        assert_eq!(decode_events(&written), [
+            // One finger
+            (EV_ABS, ABS_MT_SLOT, 0),
+            (EV_ABS, ABS_MT_TRACKING_ID, 0),
+            (EV_ABS, ABS_MT_POSITION_X, 10),
+            (EV_ABS, ABS_MT_POSITION_Y, 20),
+            (EV_KEY, BTN_TOUCH, 1),
+            (EV_KEY, BTN_TOOL_FINGER, 1),
+            (EV_ABS, ABS_X, 10),
+            (EV_ABS, ABS_Y, 20),
+            SYN,
+            // Second finger, clamped to the surface, single-touch stays on the first
+            (EV_ABS, ABS_MT_SLOT, 1),
+            (EV_ABS, ABS_MT_TRACKING_ID, 1),
+            (EV_ABS, ABS_MT_POSITION_X, 30),
+            (EV_ABS, ABS_MT_POSITION_Y, 999),
+            (EV_KEY, BTN_TOOL_FINGER, 0),
+            (EV_KEY, BTN_TOOL_DOUBLETAP, 1),
+            (EV_ABS, ABS_X, 10),
+            (EV_ABS, ABS_Y, 20),
+            SYN,
+            // Only the changed axis
+            (EV_ABS, ABS_MT_SLOT, 0),
+            (EV_ABS, ABS_MT_POSITION_X, 15),
+            (EV_ABS, ABS_X, 15),
+            (EV_ABS, ABS_Y, 20),
+            SYN,
+            (EV_ABS, ABS_MT_SLOT, 1),
+            (EV_ABS, ABS_MT_TRACKING_ID, -1),
+            (EV_KEY, BTN_TOOL_DOUBLETAP, 0),
+            (EV_KEY, BTN_TOOL_FINGER, 1),
+            (EV_ABS, ABS_X, 15),
+            (EV_ABS, ABS_Y, 20),
+            SYN,
+            (EV_ABS, ABS_MT_SLOT, 0),
+            (EV_ABS, ABS_MT_TRACKING_ID, -1),
+            (EV_KEY, BTN_TOUCH, 0),
+            (EV_KEY, BTN_TOOL_FINGER, 0),
+            SYN,
+        ]);
+    }
+
+    #[test]
+    fn failed_frame_is_rolled_back() {
+        let written = Arc::new(Mutex::new(vec![]));
+        let writer = FlakyWriter { written: written.clone(), writes: 0, fail_at: 1 };
+        let mut touch = VirtualTouch::from_writer(writer, 1000, 1000).unwrap();
+
+        assert!(touch.touch_down(0, 10, 20).is_err());
+        assert!(!touch.is_touching(0));
+        touch.touch_down(0, 11, 20).unwrap();
+        assert_eq!(decode_events(&written.lock().unwrap()), [
+            (EV_ABS, ABS_MT_SLOT, 0),
+            (EV_ABS, ABS_MT_TRACKING_ID, 1),
+            (EV_ABS, ABS_MT_POSITION_X, 11),
+            (EV_ABS, ABS_MT_POSITION_Y, 20),
+            (EV_KEY, BTN_TOUCH, 1),
+            (EV_KEY, BTN_TOOL_FINGER, 1),
+            (EV_ABS, ABS_X, 11),
+            (EV_ABS, ABS_Y, 20),
+            SYN,
+        ]);
+    }
+}
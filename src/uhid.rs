@@ -0,0 +1,351 @@
+#[cfg(feature = "uhid")]
+use std::fs::OpenOptions;
+#[cfg(feature = "uhid")]
+use std::os::unix::fs::OpenOptionsExt;
+use std::io::{ErrorKind, Read, Write};
+
+use color_eyre::eyre::bail;
+use color_eyre::Result;
+use crate::exec_or_eyre;
+use crate::key_codes::BUS_VIRTUAL;
+
+#[cfg(feature = "uhid")]
+pub const UHID_PATH: &str = "/dev/uhid";
+
+// Event types and sizes of the packed structs in linux/uhid.h
+const UHID_DESTROY: u32 = 1;
+const UHID_CREATE2: u32 = 11;
+const UHID_INPUT2: u32 = 12;
+
+const UHID_NAME_SIZE: usize = 128;
+const UHID_PHYS_SIZE: usize = 64;
+const UHID_UNIQ_SIZE: usize = 64;
+pub const UHID_DATA_MAX: usize = 4096;
+// Type, name, phys, uniq, rd_size, bus, vendor, product, version, country, rd_data
+const UHID_CREATE2_SIZE: usize = 4 + UHID_NAME_SIZE + UHID_PHYS_SIZE + UHID_UNIQ_SIZE + 2 + 2 + 4 * 4 + UHID_DATA_MAX;
+// CREATE2 is the largest member of struct uhid_event, reads return at most one event
+const UHID_EVENT_SIZE: usize = UHID_CREATE2_SIZE;
+
+#[derive(Clone, Debug)]
+pub struct UhidBuilder {
+    name: String,
+    phys: String,
+    uniq: String,
+    bus_type: u16,
+    vendor: u32,
+    product: u32,
+    version: u32,
+    country: u32,
+    report_descriptor: Vec<u8>,
+}
+
+impl Default for UhidBuilder {
+    fn default() -> Self {
+        Self {
+            name: "universal_input".to_string(),
+            phys: String::new(),
+            uniq: String::new(),
+            bus_type: BUS_VIRTUAL,
+            vendor: 0x1234,
+            product: 0x5678,
+            version: 1,
+            country: 0,
+            report_descriptor: vec![],
+        }
+    }
+}
+
+impl UhidBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn phys(mut self, phys: &str) -> Self {
+        self.phys = phys.to_string();
+        self
+    }
+
+    pub fn uniq(mut self, uniq: &str) -> Self {
+        self.uniq = uniq.to_string();
+        self
+    }
+
+    pub fn bus_type(mut self, bus_type: u16) -> Self {
+        self.bus_type = bus_type;
+        self
+    }
+
+    pub fn vendor(mut self, vendor: u32) -> Self {
+        self.vendor = vendor;
+        self
+    }
+
+    pub fn product(mut self, product: u32) -> Self {
+        self.product = product;
+        self
+    }
+
+    pub fn version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub fn country(mut self, country: u32) -> Self {
+        self.country = country;
+        self
+    }
+
+    pub fn report_descriptor(mut self, report_descriptor: &[u8]) -> Self {
+        self.report_descriptor = report_descriptor.to_vec();
+        self
+    }
+
+    // The UHID_CREATE2 event as written to /dev/uhid
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        if self.report_descriptor.is_empty() || self.report_descriptor.len() > UHID_DATA_MAX {
+            bail!("Report descriptor must be 1 to {UHID_DATA_MAX} bytes long");
+        }
+
+        let mut event = Vec::with_capacity(UHID_CREATE2_SIZE);
+        event.extend_from_slice(&UHID_CREATE2.to_ne_bytes());
+        push_string(&mut event, "name", &self.name, UHID_NAME_SIZE)?;
+        push_string(&mut event, "phys", &self.phys, UHID_PHYS_SIZE)?;
+        push_string(&mut event, "uniq", &self.uniq, UHID_UNIQ_SIZE)?;
+        event.extend_from_slice(&(self.report_descriptor.len() as u16).to_ne_bytes());
+        event.extend_from_slice(&self.bus_type.to_ne_bytes());
+        event.extend_from_slice(&self.vendor.to_ne_bytes());
+        event.extend_from_slice(&self.product.to_ne_bytes());
+        event.extend_from_slice(&self.version.to_ne_bytes());
+        event.extend_from_slice(&self.country.to_ne_bytes());
+        event.extend_from_slice(&self.report_descriptor);
+        event.resize(UHID_CREATE2_SIZE, 0);
+        Ok(event)
+    }
+
+    // Non-blocking so the events the kernel queues can be drained between writes
+    #[cfg(feature = "uhid")]
+    pub fn create(&self) -> Result<UhidDevice> {
+        let file = exec_or_eyre!(
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .custom_flags(libc::O_NONBLOCK)
+                .open(UHID_PATH)
+        )?;
+        let reader = exec_or_eyre!(file.try_clone())?;
+        Ok(self.create_on(file)?.drain_from(reader))
+    }
+
+    // Sends UHID_CREATE2 through any writer, e.g. a pipe or a buffer capturing the events
+    pub fn create_on<W: Write + Send + 'static>(&self, writer: W) -> Result<UhidDevice> {
+        let mut device = UhidDevice::from_writer(writer);
+        device.write_event(&self.encode()?)?;
+        Ok(device)
+    }
+}
+
+// Strings are NUL padded to a fixed size and must leave room for the terminator
+fn push_string(event: &mut Vec<u8>, field: &str, value: &str, size: usize) -> Result<()> {
+    if value.len() >= size {
+        bail!("Device {field} must be shorter than {size} bytes");
+    }
+    event.extend_from_slice(value.as_bytes());
+    event.resize(event.len() + size - value.len(), 0);
+    Ok(())
+}
+
+// The kernel unregisters the device when the fd is closed
+pub struct UhidDevice {
+    writer: Box<dyn Write + Send>,
+    reader: Option<Box<dyn Read + Send>>,
+}
+
+impl UhidDevice {
+    pub fn from_writer<W: Write + Send + 'static>(writer: W) -> Self {
+        Self {
+            writer: Box::new(writer),
+            reader: None,
+        }
+    }
+
+    // The kernel queues UHID_START, UHID_OPEN/CLOSE and UHID_OUTPUT (LED state) for us to read,
+    // only 32 of them fit and the rest are dropped with a warning in the kernel log. They are read
+    // and discarded after every write, the reader must be non-blocking. Nothing here needs them:
+    // the descriptors have no feature reports, so GET_REPORT/SET_REPORT only come from hidraw
+    // users and time out unanswered.
+    pub fn drain_from<R: Read + Send + 'static>(mut self, reader: R) -> Self {
+        self.reader = Some(Box::new(reader));
+        self
+    }
+
+    fn drain(&mut self) -> Result<()> {
+        let Some(reader) = &mut self.reader else {
+            return Ok(());
+        };
+        let mut event = vec![0u8; UHID_EVENT_SIZE];
+        loop {
+            match reader.read(&mut event) {
+                Ok(0) => return Ok(()),
+                Ok(_) => {}
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return exec_or_eyre!(Err(err)),
+            }
+        }
+    }
+
+    // One event per write call, the kernel parses each write as a whole uhid_event
+    #[inline]
+    fn write_event(&mut self, event: &[u8]) -> Result<()> {
+        exec_or_eyre!(self.writer.write_all(event))?;
+        self.drain()
+    }
+
+    pub fn encode_input(report: &[u8]) -> Result<Vec<u8>> {
+        if report.len() > UHID_DATA_MAX {
+            bail!("Report must be at most {UHID_DATA_MAX} bytes long");
+        }
+        let mut event = Vec::with_capacity(size_of::<u32>() + size_of::<u16>() + report.len());
+        event.extend_from_slice(&UHID_INPUT2.to_ne_bytes());
+        event.extend_from_slice(&(report.len() as u16).to_ne_bytes());
+        event.extend_from_slice(report);
+        Ok(event)
+    }
+
+    #[inline]
+    pub fn write_report(&mut self, report: &[u8]) -> Result<()> {
+        let event = Self::encode_input(report)?;
+        self.write_event(&event)
+    }
+
+    pub fn destroy(&mut self) -> Result<()> {
+        self.write_event(&UHID_DESTROY.to_ne_bytes())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::Cursor;
+    use std::sync::{Arc, Mutex};
+    use super::*;
+    use crate::hid_report::{KeyboardReport, KEYBOARD_REPORT_DESCRIPTOR};
+    use crate::KeyCode;
+
+    // Shared buffer so the bytes can be checked while the device still holds the writer
+    #[derive(Clone, Default)]
+    pub(crate) struct Capture(pub(crate) Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Capture {
+        pub(crate) fn take(&self) -> Vec<u8> {
+            std::mem::take(&mut *self.0.lock().unwrap())
+        }
+    }
+
+    fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+        u16::from_ne_bytes(bytes[offset..offset + 2].try_into().unwrap())
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_ne_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn string_at(bytes: &[u8], offset: usize, size: usize) -> String {
+        let field = &bytes[offset..offset + size];
+        let end = field.iter().position(|byte| *byte == 0).unwrap();
+        String::from_utf8(field[..end].to_vec()).unwrap()
+    }
+
+    #[test]
+    fn decodes_create2() {
+        let capture = Capture::default();
+        UhidBuilder::new()
+            .name("keyboard")
+            .phys("usb-1/input0")
+            .uniq("serial")
+            .vendor(0xabcd)
+            .product(0x1234)
+            .version(7)
+            .country(33)
+            .report_descriptor(&KEYBOARD_REPORT_DESCRIPTOR)
+            .create_on(capture.clone())
+            .unwrap();
+        let event = capture.take();
+
+        // Offsets of struct uhid_create2_req after the type
+        assert_eq!(event.len(), 4376);
+        assert_eq!(u32_at(&event, 0), UHID_CREATE2);
+        assert_eq!(string_at(&event, 4, 128), "keyboard");
+        assert_eq!(string_at(&event, 132, 64), "usb-1/input0");
+        assert_eq!(string_at(&event, 196, 64), "serial");
+        assert_eq!(u16_at(&event, 260), KEYBOARD_REPORT_DESCRIPTOR.len() as u16);
+        assert_eq!(u16_at(&event, 262), BUS_VIRTUAL);
+        assert_eq!(u32_at(&event, 264), 0xabcd);
+        assert_eq!(u32_at(&event, 268), 0x1234);
+        assert_eq!(u32_at(&event, 272), 7);
+        assert_eq!(u32_at(&event, 276), 33);
+        assert_eq!(&event[280..280 + KEYBOARD_REPORT_DESCRIPTOR.len()], &KEYBOARD_REPORT_DESCRIPTOR);
+        assert!(event[280 + KEYBOARD_REPORT_DESCRIPTOR.len()..].iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn rejects_bad_create2() {
+        assert!(UhidBuilder::new().encode().is_err());
+        assert!(UhidBuilder::new().name(&"x".repeat(128)).report_descriptor(&[0xc0]).encode().is_err());
+        assert!(UhidBuilder::new().report_descriptor(&[0; UHID_DATA_MAX + 1]).encode().is_err());
+    }
+
+    #[test]
+    fn decodes_input2() {
+        let capture = Capture::default();
+        let mut device = UhidDevice::from_writer(capture.clone());
+        let mut report = KeyboardReport::new();
+        report.press(KeyCode::KEY_LEFTSHIFT).unwrap();
+        report.press(KeyCode::KEY_A).unwrap();
+        device.write_report(&report.to_bytes()).unwrap();
+        device.destroy().unwrap();
+
+        let events = capture.take();
+        assert_eq!(u32_at(&events, 0), UHID_INPUT2);
+        assert_eq!(u16_at(&events, 4), 8);
+        assert_eq!(&events[6..14], &[0x02, 0, 0x04, 0, 0, 0, 0, 0]);
+        assert_eq!(u32_at(&events, 14), UHID_DESTROY);
+        assert_eq!(events.len(), 18);
+        assert!(UhidDevice::encode_input(&[0; UHID_DATA_MAX + 1]).is_err());
+    }
+
+    #[test]
+    fn drains_kernel_events() {
+        // UHID_START and UHID_OPEN as the kernel queues them
+        let mut queued = vec![0u8; 2 * UHID_EVENT_SIZE];
+        queued[..4].copy_from_slice(&2u32.to_ne_bytes());
+        queued[UHID_EVENT_SIZE..UHID_EVENT_SIZE + 4].copy_from_slice(&4u32.to_ne_bytes());
+        let queued = Arc::new(Mutex::new(Cursor::new(queued)));
+
+        struct Shared(Arc<Mutex<Cursor<Vec<u8>>>>);
+        impl Read for Shared {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().read(buf)
+            }
+        }
+
+        let mut device = UhidDevice::from_writer(Capture::default()).drain_from(Shared(queued.clone()));
+        device.write_report(&[0; 8]).unwrap();
+        assert_eq!(queued.lock().unwrap().position(), 2 * UHID_EVENT_SIZE as u64);
+    }
+}
@@ -0,0 +1,361 @@
+#[cfg(feature = "uinput")]
+use std::ffi::CString;
+#[cfg(feature = "uinput")]
+use std::fs::OpenOptions;
+#[cfg(feature = "uinput")]
+use std::io::Write;
+#[cfg(feature = "uinput")]
+use std::os::unix::fs::OpenOptionsExt;
+#[cfg(feature = "uinput")]
+use std::os::unix::io::{AsRawFd, RawFd};
+
+use color_eyre::eyre::bail;
+use color_eyre::Result;
+use crate::{err_eyre, exec_or_eyre};
+use crate::key_codes::{BUS_USB, EV_ABS, EV_KEY, EV_REL, EV_SYN, SYN_REPORT};
+
+pub type EventParams = (u16, u16, i32);
+
+#[cfg(feature = "uinput")]
+pub const UINPUT_PATH: &str = "/dev/uinput";
+
+#[cfg(feature = "uinput")]
+const UINPUT_MAX_NAME_SIZE: usize = 80;
+
+// ioctl request codes, see linux/uinput.h
+#[cfg(feature = "uinput")]
+const fn io(nr: u64) -> u64 {
+    (b'U' as u64) << 8 | nr
+}
+
+#[cfg(feature = "uinput")]
+const fn iow(nr: u64, size: usize) -> u64 {
+    1 << 30 | (size as u64) << 16 | io(nr)
+}
+
+#[cfg(feature = "uinput")]
+const UI_DEV_CREATE: u64 = io(1);
+#[cfg(feature = "uinput")]
+const UI_DEV_SETUP: u64 = iow(3, size_of::<libc::uinput_setup>());
+#[cfg(feature = "uinput")]
+const UI_ABS_SETUP: u64 = iow(4, size_of::<libc::uinput_abs_setup>());
+#[cfg(feature = "uinput")]
+const UI_SET_EVBIT: u64 = iow(100, size_of::<libc::c_int>());
+#[cfg(feature = "uinput")]
+const UI_SET_KEYBIT: u64 = iow(101, size_of::<libc::c_int>());
+#[cfg(feature = "uinput")]
+const UI_SET_RELBIT: u64 = iow(102, size_of::<libc::c_int>());
+#[cfg(feature = "uinput")]
+const UI_SET_ABSBIT: u64 = iow(103, size_of::<libc::c_int>());
+#[cfg(feature = "uinput")]
+const UI_SET_PHYS: u64 = iow(108, size_of::<*const libc::c_char>());
+#[cfg(feature = "uinput")]
+const UI_SET_PROPBIT: u64 = iow(110, size_of::<libc::c_int>());
+
+#[cfg(feature = "uinput")]
+fn ioctl_result(result: libc::c_int) -> Result<()> {
+    if result < 0 {
+        return Err(err_eyre!(std::io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "uinput")]
+fn ioctl_int(fd: RawFd, request: u64, value: u16) -> Result<()> {
+    ioctl_result(unsafe { libc::ioctl(fd, request as _, value as libc::c_int) })
+}
+
+#[cfg(feature = "uinput")]
+fn ioctl_ptr<T>(fd: RawFd, request: u64, value: *const T) -> Result<()> {
+    ioctl_result(unsafe { libc::ioctl(fd, request as _, value) })
+}
+
+#[cfg(feature = "uinput")]
+#[derive(PartialEq, Eq, Copy, Clone, Default, Debug)]
+pub struct AbsInfo {
+    pub minimum: i32,
+    pub maximum: i32,
+    pub fuzz: i32,
+    pub flat: i32,
+    pub resolution: i32,
+}
+
+#[cfg(feature = "uinput")]
+impl AbsInfo {
+    pub fn new(minimum: i32, maximum: i32) -> Self {
+        Self {
+            minimum,
+            maximum,
+            ..Default::default()
+        }
+    }
+
+    pub fn flat(mut self, flat: i32) -> Self {
+        self.flat = flat;
+        self
+    }
+
+    pub fn fuzz(mut self, fuzz: i32) -> Self {
+        self.fuzz = fuzz;
+        self
+    }
+
+    pub fn resolution(mut self, resolution: i32) -> Self {
+        self.resolution = resolution;
+        self
+    }
+}
+
+#[cfg(feature = "uinput")]
+#[derive(Clone, Debug)]
+pub struct UinputBuilder {
+    name: String,
+    bus_type: u16,
+    vendor: u16,
+    product: u16,
+    version: u16,
+    phys: Option<String>,
+    key_bits: Vec<u16>,
+    rel_bits: Vec<u16>,
+    abs_axes: Vec<(u16, AbsInfo)>,
+    prop_bits: Vec<u16>,
+}
+
+#[cfg(feature = "uinput")]
+impl Default for UinputBuilder {
+    fn default() -> Self {
+        Self {
+            name: "universal_input".to_string(),
+            bus_type: BUS_USB,
+            vendor: 0x1234,
+            product: 0x5678,
+            version: 1,
+            phys: None,
+            key_bits: vec![],
+            rel_bits: vec![],
+            abs_axes: vec![],
+            prop_bits: vec![],
+        }
+    }
+}
+
+#[cfg(feature = "uinput")]
+impl UinputBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn bus_type(mut self, bus_type: u16) -> Self {
+        self.bus_type = bus_type;
+        self
+    }
+
+    pub fn vendor(mut self, vendor: u16) -> Self {
+        self.vendor = vendor;
+        self
+    }
+
+    pub fn product(mut self, product: u16) -> Self {
+        self.product = product;
+        self
+    }
+
+    pub fn version(mut self, version: u16) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub fn phys(mut self, phys: &str) -> Self {
+        self.phys = Some(phys.to_string());
+        self
+    }
+
+    pub fn key_bits(mut self, key_bits: &[u16]) -> Self {
+        self.key_bits = key_bits.to_vec();
+        self
+    }
+
+    pub fn rel_bits(mut self, rel_bits: &[u16]) -> Self {
+        self.rel_bits = rel_bits.to_vec();
+        self
+    }
+
+    pub fn abs_axis(mut self, code: u16, abs_info: AbsInfo) -> Self {
+        self.abs_axes.push((code, abs_info));
+        self
+    }
+
+    pub fn prop_bit(mut self, prop: u16) -> Self {
+        self.prop_bits.push(prop);
+        self
+    }
+
+    pub fn create(&self) -> Result<UinputDevice> {
+        if self.name.is_empty() || self.name.len() >= UINPUT_MAX_NAME_SIZE {
+            bail!("Device name must be 1 to {} bytes long", UINPUT_MAX_NAME_SIZE - 1);
+        }
+
+        let file = exec_or_eyre!(OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(UINPUT_PATH))?;
+        let fd = file.as_raw_fd();
+
+        if !self.key_bits.is_empty() {
+            ioctl_int(fd, UI_SET_EVBIT, EV_KEY)?;
+            for key in &self.key_bits {
+                ioctl_int(fd, UI_SET_KEYBIT, *key)?;
+            }
+        }
+
+        if !self.rel_bits.is_empty() {
+            ioctl_int(fd, UI_SET_EVBIT, EV_REL)?;
+            for rel in &self.rel_bits {
+                ioctl_int(fd, UI_SET_RELBIT, *rel)?;
+            }
+        }
+
+        if !self.abs_axes.is_empty() {
+            ioctl_int(fd, UI_SET_EVBIT, EV_ABS)?;
+            for (code, abs_info) in &self.abs_axes {
+                ioctl_int(fd, UI_SET_ABSBIT, *code)?;
+
+                let mut abs_setup: libc::uinput_abs_setup = unsafe { std::mem::zeroed() };
+                abs_setup.code = *code;
+                abs_setup.absinfo.minimum = abs_info.minimum;
+                abs_setup.absinfo.maximum = abs_info.maximum;
+                abs_setup.absinfo.fuzz = abs_info.fuzz;
+                abs_setup.absinfo.flat = abs_info.flat;
+                abs_setup.absinfo.resolution = abs_info.resolution;
+                ioctl_ptr(fd, UI_ABS_SETUP, &abs_setup)?;
+            }
+        }
+
+        for prop in &self.prop_bits {
+            ioctl_int(fd, UI_SET_PROPBIT, *prop)?;
+        }
+
+        if let Some(phys) = &self.phys {
+            let phys = exec_or_eyre!(CString::new(phys.as_str()))?;
+            ioctl_ptr(fd, UI_SET_PHYS, phys.as_ptr())?;
+        }
+
+        let mut setup: libc::uinput_setup = unsafe { std::mem::zeroed() };
+        setup.id.bustype = self.bus_type;
+        setup.id.vendor = self.vendor;
+        setup.id.product = self.product;
+        setup.id.version = self.version;
+        for (dst, src) in setup.name.iter_mut().zip(self.name.bytes()) {
+            *dst = src as libc::c_char;
+        }
+        ioctl_ptr(fd, UI_DEV_SETUP, &setup)?;
+        ioctl_result(unsafe { libc::ioctl(fd, UI_DEV_CREATE as _) })?;
+
+        Ok(UinputDevice::from_writer(file))
+    }
+}
+
+#[cfg(feature = "uinput")]
+pub struct UinputDevice {
+    writer: Box<dyn Write + Send>,
+}
+
+#[cfg(feature = "uinput")]
+impl UinputDevice {
+    // Any writer works here, e.g. a pipe or a temp file capturing input_event structs
+    pub fn from_writer<W: Write + Send + 'static>(writer: W) -> Self {
+        Self {
+            writer: Box::new(writer),
+        }
+    }
+
+    #[inline]
+    fn encode_event(buffer: &mut Vec<u8>, event_type: u16, code: u16, value: i32) {
+        let event = libc::input_event {
+            time: libc::timeval { tv_sec: 0, tv_usec: 0 },
+            type_: event_type,
+            code,
+            value,
+        };
+        let bytes = unsafe {
+            std::slice::from_raw_parts(
+                &event as *const libc::input_event as *const u8,
+                size_of::<libc::input_event>(),
+            )
+        };
+        buffer.extend_from_slice(bytes);
+    }
+
+    #[inline]
+    pub fn write_event(&mut self, event_type: u16, code: u16, value: i32) -> Result<()> {
+        self.write_batch(&[(event_type, code, value)])
+    }
+
+    #[inline]
+    pub fn write_batch(&mut self, batch: &[EventParams]) -> Result<()> {
+        let mut buffer = Vec::with_capacity(batch.len() * size_of::<libc::input_event>());
+        for (event_type, code, value) in batch {
+            Self::encode_event(&mut buffer, *event_type, *code, *value);
+        }
+        exec_or_eyre!(self.writer.write_all(&buffer))?;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn synchronize(&mut self) -> Result<()> {
+        self.write_event(EV_SYN, SYN_REPORT, 0)
+    }
+}
+
+#[cfg(all(test, feature = "uinput"))]
+pub(crate) mod tests {
+    use std::io::Read;
+    use crate::key_codes::{EV_KEY, EV_REL, EV_SYN, KEY_A, REL_X, SYN_REPORT};
+    use super::*;
+
+    // timeval is left zeroed, the kernel stamps events itself
+    pub(crate) fn event_bytes(event_type: u16, code: u16, value: i32) -> Vec<u8> {
+        let mut bytes = vec![0; size_of::<libc::timeval>()];
+        bytes.extend_from_slice(&event_type.to_ne_bytes());
+        bytes.extend_from_slice(&code.to_ne_bytes());
+        bytes.extend_from_slice(&value.to_ne_bytes());
+        bytes
+    }
+
+    pub(crate) fn decode_events(bytes: &[u8]) -> Vec<EventParams> {
+        let time_size = size_of::<libc::timeval>();
+        bytes.chunks(size_of::<libc::input_event>()).map(|event| {
+            let field = &event[time_size..];
+            (
+                u16::from_ne_bytes([field[0], field[1]]),
+                u16::from_ne_bytes([field[2], field[3]]),
+                i32::from_ne_bytes([field[4], field[5], field[6], field[7]]),
+            )
+        }).collect()
+    }
+
+    #[test]
+    fn writes_input_events_to_pipe() {
+        let (mut reader, writer) = std::io::pipe().unwrap();
+        let mut device = UinputDevice::from_writer(writer);
+        device.write_batch(&[(EV_KEY, KEY_A, 1), (EV_REL, REL_X, -5)]).unwrap();
+        device.synchronize().unwrap();
+        drop(device);
+
+        let mut written = vec![];
+        reader.read_to_end(&mut written).unwrap();
+
+        let expected = [
+            event_bytes(EV_KEY, KEY_A, 1),
+            event_bytes(EV_REL, REL_X, -5),
+            event_bytes(EV_SYN, SYN_REPORT, 0),
+        ].concat();
+        assert_eq!(size_of::<libc::input_event>(), expected.len() / 3);
+        assert_eq!(written, expected);
+    }
+}
@@ -1,6 +1,16 @@
 use std::cmp::min;
 use crate::OS_Input_Coord;
 
+// Backends that click the wheel once per step send at most this many clicks per scroll
+#[cfg(any(feature = "use_rfb", feature = "use_qmp", feature = "use_xtest"))]
+pub(crate) const MAX_SCROLL_STEPS: u32 = 256;
+
+#[cfg(any(feature = "use_rfb", feature = "use_qmp", feature = "use_xtest"))]
+#[inline]
+pub(crate) fn scroll_steps(value: OS_Input_Coord) -> u32 {
+    value.unsigned_abs().min(MAX_SCROLL_STEPS)
+}
+
 #[derive(PartialEq, Copy, Clone, Default, Debug)]
 pub struct GradualMove {
     pub x_direction: OS_Input_Coord,
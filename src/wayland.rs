@@ -0,0 +1,393 @@
+use std::fmt::Write as _;
+use crate::{KeyCode, KEY_TABLE};
+
+#[cfg(feature = "wayland")]
+use std::fs::File;
+#[cfg(feature = "wayland")]
+use std::io::{Seek, SeekFrom, Write};
+#[cfg(feature = "wayland")]
+use std::os::fd::{AsFd, FromRawFd};
+#[cfg(feature = "wayland")]
+use std::os::unix::net::UnixStream;
+#[cfg(feature = "wayland")]
+use std::time::Instant;
+#[cfg(feature = "wayland")]
+use color_eyre::eyre::bail;
+#[cfg(feature = "wayland")]
+use color_eyre::Result;
+#[cfg(feature = "wayland")]
+use wayland_client::globals::{registry_queue_init, GlobalListContents};
+#[cfg(feature = "wayland")]
+use wayland_client::protocol::wl_keyboard::{KeyState, KeymapFormat};
+#[cfg(feature = "wayland")]
+use wayland_client::protocol::wl_pointer::{Axis, AxisSource, ButtonState};
+#[cfg(feature = "wayland")]
+use wayland_client::protocol::wl_registry::WlRegistry;
+#[cfg(feature = "wayland")]
+use wayland_client::protocol::wl_seat::WlSeat;
+#[cfg(feature = "wayland")]
+use wayland_client::{delegate_noop, Connection, Dispatch, EventQueue, QueueHandle};
+#[cfg(feature = "wayland")]
+use wayland_protocols_misc::zwp_virtual_keyboard_v1::client::zwp_virtual_keyboard_manager_v1::ZwpVirtualKeyboardManagerV1;
+#[cfg(feature = "wayland")]
+use wayland_protocols_misc::zwp_virtual_keyboard_v1::client::zwp_virtual_keyboard_v1::ZwpVirtualKeyboardV1;
+#[cfg(feature = "wayland")]
+use wayland_protocols_wlr::virtual_pointer::v1::client::zwlr_virtual_pointer_manager_v1::ZwlrVirtualPointerManagerV1;
+#[cfg(feature = "wayland")]
+use wayland_protocols_wlr::virtual_pointer::v1::client::zwlr_virtual_pointer_v1::ZwlrVirtualPointerV1;
+#[cfg(feature = "wayland")]
+use crate::{exec_or_eyre, OS_Input_Coord};
+
+// xkb keycodes are evdev codes shifted by 8
+pub const XKB_KEYCODE_OFFSET: u32 = 8;
+
+// Real modifier bits, their order is fixed by xkb
+pub const XKB_MOD_SHIFT: u32 = 1 << 0;
+pub const XKB_MOD_LOCK: u32 = 1 << 1;
+pub const XKB_MOD_CONTROL: u32 = 1 << 2;
+pub const XKB_MOD_MOD1: u32 = 1 << 3;
+pub const XKB_MOD_MOD2: u32 = 1 << 4;
+pub const XKB_MOD_MOD4: u32 = 1 << 6;
+
+// One wheel detent in wl_pointer axis units, same as libinput reports
+pub const WAYLAND_SCROLL_STEP: f64 = 15.0;
+
+const XKB_MODIFIER_NAMES: &[(u32, &str)] = &[
+    (XKB_MOD_SHIFT, "Shift"),
+    (XKB_MOD_LOCK, "Lock"),
+    (XKB_MOD_CONTROL, "Control"),
+    (XKB_MOD_MOD1, "Mod1"),
+    (XKB_MOD_MOD2, "Mod2"),
+    (XKB_MOD_MOD4, "Mod4"),
+];
+
+// Second level of the US layout for keys that aren't letters
+const US_SHIFTED_KEYSYMS: &[(KeyCode, u32)] = &[
+    (KeyCode::KEY_1, 0x21),
+    (KeyCode::KEY_2, 0x40),
+    (KeyCode::KEY_3, 0x23),
+    (KeyCode::KEY_4, 0x24),
+    (KeyCode::KEY_5, 0x25),
+    (KeyCode::KEY_6, 0x5e),
+    (KeyCode::KEY_7, 0x26),
+    (KeyCode::KEY_8, 0x2a),
+    (KeyCode::KEY_9, 0x28),
+    (KeyCode::KEY_10, 0x29),
+    (KeyCode::KEY_MINUS, 0x5f),
+    (KeyCode::KEY_EQUAL, 0x2b),
+    (KeyCode::KEY_LEFTBRACE, 0x7b),
+    (KeyCode::KEY_RIGHTBRACE, 0x7d),
+    (KeyCode::KEY_BACKSLASH, 0x7c),
+    (KeyCode::KEY_SEMICOLON, 0x3a),
+    (KeyCode::KEY_APOSTROPHE, 0x22),
+    (KeyCode::KEY_GRAVE, 0x7e),
+    (KeyCode::KEY_COMMA, 0x3c),
+    (KeyCode::KEY_DOT, 0x3e),
+    (KeyCode::KEY_SLASH, 0x3f),
+];
+
+// Modifier a key sets while held, caps and num lock are toggles
+pub fn xkb_modifier(key_code: KeyCode) -> Option<u32> {
+    match key_code {
+        KeyCode::KEY_LEFTSHIFT | KeyCode::KEY_RIGHTSHIFT => Some(XKB_MOD_SHIFT),
+        KeyCode::KEY_CAPSLOCK => Some(XKB_MOD_LOCK),
+        KeyCode::KEY_LEFTCTRL | KeyCode::KEY_RIGHTCTRL => Some(XKB_MOD_CONTROL),
+        KeyCode::KEY_LEFTALT | KeyCode::KEY_RIGHTALT => Some(XKB_MOD_MOD1),
+        KeyCode::KEY_NUMLOCK => Some(XKB_MOD_MOD2),
+        KeyCode::KEY_LEFTMETA | KeyCode::KEY_RIGHTMETA => Some(XKB_MOD_MOD4),
+        _ => None,
+    }
+}
+
+#[inline]
+pub fn is_xkb_lock(modifier: u32) -> bool {
+    modifier == XKB_MOD_LOCK || modifier == XKB_MOD_MOD2
+}
+
+// A complete keymap in XKB_V1 text format covering every key with both an evdev code and a keysym.
+// Keysyms are written as numbers so no keysym name table is needed, types and compat come from the system.
+pub fn build_xkb_keymap() -> String {
+    let mappings: Vec<(u32, u32, KeyCode)> = KEY_TABLE
+        .iter()
+        .filter(|mapping| !mapping.key_code.is_mouse_button())
+        .filter_map(|mapping| Some((mapping.evdev? as u32 + XKB_KEYCODE_OFFSET, mapping.keysym?, mapping.key_code)))
+        .collect();
+    let max_keycode = mappings.iter().map(|(keycode, _, _)| *keycode).max().unwrap_or(255).max(255);
+
+    let mut keymap = String::new();
+    keymap.push_str("xkb_keymap {\n");
+
+    let _ = writeln!(keymap, "xkb_keycodes \"universal_input\" {{\n    minimum = {XKB_KEYCODE_OFFSET};\n    maximum = {max_keycode};");
+    for (keycode, _, _) in &mappings {
+        let _ = writeln!(keymap, "    <I{keycode}> = {keycode};");
+    }
+    keymap.push_str("};\n");
+
+    keymap.push_str("xkb_types \"universal_input\" { include \"complete\" };\n");
+    keymap.push_str("xkb_compatibility \"universal_input\" { include \"complete\" };\n");
+
+    keymap.push_str("xkb_symbols \"universal_input\" {\n");
+    for (keycode, keysym, key_code) in &mappings {
+        let shifted = match keysym {
+            0x61..=0x7a => Some(keysym - 0x20),
+            _ => US_SHIFTED_KEYSYMS
+                .iter()
+                .find(|(shifted_key, _)| shifted_key == key_code)
+                .map(|(_, shifted)| *shifted),
+        };
+        match shifted {
+            Some(shifted) => {
+                let _ = writeln!(keymap, "    key <I{keycode}> {{ [ {keysym:#x}, {shifted:#x} ] }};");
+            }
+            None => {
+                let _ = writeln!(keymap, "    key <I{keycode}> {{ [ {keysym:#x} ] }};");
+            }
+        }
+    }
+    for (modifier, name) in XKB_MODIFIER_NAMES {
+        let keys: Vec<String> = mappings
+            .iter()
+            .filter(|(_, _, key_code)| xkb_modifier(*key_code) == Some(*modifier))
+            .map(|(keycode, _, _)| format!("<I{keycode}>"))
+            .collect();
+        if !keys.is_empty() {
+            let _ = writeln!(keymap, "    modifier_map {name} {{ {} }};", keys.join(", "));
+        }
+    }
+    keymap.push_str("};\n");
+
+    keymap.push_str("};\n");
+    keymap
+}
+
+#[cfg(feature = "wayland")]
+struct WaylandState;
+
+#[cfg(feature = "wayland")]
+impl Dispatch<WlRegistry, GlobalListContents> for WaylandState {
+    fn event(
+        _state: &mut Self,
+        _proxy: &WlRegistry,
+        _event: <WlRegistry as wayland_client::Proxy>::Event,
+        _data: &GlobalListContents,
+        _conn: &Connection,
+        _qhandle: &QueueHandle<Self>,
+    ) {}
+}
+
+#[cfg(feature = "wayland")]
+delegate_noop!(WaylandState: ignore WlSeat);
+#[cfg(feature = "wayland")]
+delegate_noop!(WaylandState: ZwpVirtualKeyboardManagerV1);
+#[cfg(feature = "wayland")]
+delegate_noop!(WaylandState: ZwpVirtualKeyboardV1);
+#[cfg(feature = "wayland")]
+delegate_noop!(WaylandState: ZwlrVirtualPointerManagerV1);
+#[cfg(feature = "wayland")]
+delegate_noop!(WaylandState: ZwlrVirtualPointerV1);
+
+// Virtual keyboard and pointer on the first seat. Requests are buffered by the
+// client library until flush().
+#[cfg(feature = "wayland")]
+pub struct WaylandVirtualInput {
+    connection: Connection,
+    queue: EventQueue<WaylandState>,
+    keyboard: ZwpVirtualKeyboardV1,
+    pointer: ZwlrVirtualPointerV1,
+    start: Instant,
+}
+
+#[cfg(feature = "wayland")]
+fn keymap_file(keymap: &str) -> Result<File> {
+    let fd = unsafe { libc::memfd_create(c"universal_input-keymap".as_ptr(), libc::MFD_CLOEXEC) };
+    if fd < 0 {
+        return exec_or_eyre!(Err(std::io::Error::last_os_error()));
+    }
+    let mut file = unsafe { File::from_raw_fd(fd) };
+    exec_or_eyre!(file.write_all(keymap.as_bytes()))?;
+    // The size passed to the compositor includes the terminating NUL
+    exec_or_eyre!(file.write_all(&[0]))?;
+    // Compositors mmap it but some read() it, and the offset is shared with them
+    exec_or_eyre!(file.seek(SeekFrom::Start(0)))?;
+    Ok(file)
+}
+
+#[cfg(feature = "wayland")]
+impl WaylandVirtualInput {
+    // WAYLAND_DISPLAY or WAYLAND_SOCKET
+    pub fn connect() -> Result<Self> {
+        Self::from_connection(exec_or_eyre!(Connection::connect_to_env())?)
+    }
+
+    pub fn from_socket(stream: UnixStream) -> Result<Self> {
+        Self::from_connection(exec_or_eyre!(Connection::from_socket(stream))?)
+    }
+
+    pub fn from_connection(connection: Connection) -> Result<Self> {
+        let (globals, mut queue) = exec_or_eyre!(registry_queue_init::<WaylandState>(&connection))?;
+        let queue_handle = queue.handle();
+
+        let seat: WlSeat = exec_or_eyre!(globals.bind(&queue_handle, 1..=1, ()))?;
+        let keyboard_manager: ZwpVirtualKeyboardManagerV1 = match globals.bind(&queue_handle, 1..=1, ()) {
+            Ok(manager) => manager,
+            Err(_) => bail!("Compositor doesn't support zwp_virtual_keyboard_manager_v1"),
+        };
+        let pointer_manager: ZwlrVirtualPointerManagerV1 = match globals.bind(&queue_handle, 1..=2, ()) {
+            Ok(manager) => manager,
+            Err(_) => bail!("Compositor doesn't support zwlr_virtual_pointer_manager_v1"),
+        };
+
+        let keyboard = keyboard_manager.create_virtual_keyboard(&seat, &queue_handle, ());
+        let pointer = pointer_manager.create_virtual_pointer(Some(&seat), &queue_handle, ());
+
+        let keymap = build_xkb_keymap();
+        let keymap_file = keymap_file(&keymap)?;
+        keyboard.keymap(KeymapFormat::XkbV1.into(), keymap_file.as_fd(), keymap.len() as u32 + 1);
+
+        // Unauthorized clients get a protocol error here rather than on the first event
+        exec_or_eyre!(queue.roundtrip(&mut WaylandState))?;
+
+        Ok(Self {
+            connection,
+            queue,
+            keyboard,
+            pointer,
+            start: Instant::now(),
+        })
+    }
+
+    #[inline]
+    fn time(&self) -> u32 {
+        self.start.elapsed().as_millis() as u32
+    }
+
+    #[inline]
+    pub fn key(&self, evdev: u16, down: bool) {
+        let state = if down { KeyState::Pressed } else { KeyState::Released };
+        self.keyboard.key(self.time(), evdev as u32, state.into());
+    }
+
+    // The virtual keyboard doesn't derive modifiers from its keys, clients track them
+    #[inline]
+    pub fn modifiers(&self, depressed: u32, locked: u32) {
+        self.keyboard.modifiers(depressed, 0, locked, 0);
+    }
+
+    // Surface coordinates, y grows downwards
+    #[inline]
+    pub fn motion(&self, dx: f64, dy: f64) {
+        self.pointer.motion(self.time(), dx, dy);
+    }
+
+    #[inline]
+    pub fn button(&self, evdev: u16, down: bool) {
+        let state = if down { ButtonState::Pressed } else { ButtonState::Released };
+        self.pointer.button(self.time(), evdev as u32, state);
+    }
+
+    // Wheel steps, positive is down or right
+    #[inline]
+    pub fn axis(&self, axis: Axis, steps: OS_Input_Coord) {
+        let time = self.time();
+        self.pointer.axis_source(AxisSource::Wheel);
+        self.pointer.axis_discrete(time, axis, steps as f64 * WAYLAND_SCROLL_STEP, steps);
+    }
+
+    // Groups the pointer events sent since the last frame
+    #[inline]
+    pub fn frame(&self) {
+        self.pointer.frame();
+    }
+
+    #[inline]
+    pub fn flush(&mut self) -> Result<()> {
+        exec_or_eyre!(self.queue.dispatch_pending(&mut WaylandState))?;
+        exec_or_eyre!(self.connection.flush())
+    }
+}
+
+#[cfg(feature = "wayland")]
+impl Drop for WaylandVirtualInput {
+    fn drop(&mut self) {
+        self.keyboard.destroy();
+        self.pointer.destroy();
+        let _ = self.connection.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evdev_keycode(key_code: KeyCode) -> u32 {
+        key_code.evdev_code().unwrap() as u32 + XKB_KEYCODE_OFFSET
+    }
+
+    #[test]
+    fn keymap_declares_each_keycode_once() {
+        let keymap = build_xkb_keymap();
+        assert!(keymap.starts_with("xkb_keymap {\nxkb_keycodes \"universal_input\" {\n    minimum = 8;\n    maximum = 255;\n"));
+        assert_eq!(keymap.matches('{').count(), keymap.matches('}').count());
+
+        let keycodes: Vec<&str> = keymap.lines().filter(|line| line.ends_with(';') && line.contains("> = ")).collect();
+        let mut unique = keycodes.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), keycodes.len());
+        assert_eq!(keymap.matches("    key <I").count(), keycodes.len());
+
+        // Mouse buttons have no keysym and stay out
+        assert!(!keymap.contains(&format!("<I{}>", evdev_keycode(KeyCode::MOUSE_LEFT))));
+    }
+
+    #[test]
+    fn keymap_symbols_and_levels() {
+        let keymap = build_xkb_keymap();
+        let symbols = |key_code: KeyCode| {
+            let keycode = evdev_keycode(key_code);
+            let prefix = format!("    key <I{keycode}> {{ ");
+            let line = keymap.lines().find(|line| line.starts_with(&prefix)).unwrap();
+            line[prefix.len()..].to_string()
+        };
+        assert!(keymap.contains("    <I38> = 38;\n"));
+        assert_eq!(symbols(KeyCode::KEY_A), "[ 0x61, 0x41 ] };");
+        assert_eq!(symbols(KeyCode::KEY_Z), "[ 0x7a, 0x5a ] };");
+        assert_eq!(symbols(KeyCode::KEY_1), "[ 0x31, 0x21 ] };");
+        assert_eq!(symbols(KeyCode::KEY_SLASH), "[ 0x2f, 0x3f ] };");
+        assert_eq!(symbols(KeyCode::KEY_ENTER), "[ 0xff0d ] };");
+        assert_eq!(symbols(KeyCode::KEY_LEFTSHIFT), "[ 0xffe1 ] };");
+    }
+
+    #[test]
+    fn keymap_modifier_map() {
+        let keymap = build_xkb_keymap();
+        assert!(keymap.contains("    modifier_map Shift { <I50>, <I62> };\n"));
+        assert!(keymap.contains("    modifier_map Lock { <I66> };\n"));
+        assert!(keymap.contains("    modifier_map Control { <I37>, <I105> };\n"));
+        assert!(keymap.contains("    modifier_map Mod1 { <I64>, <I108> };\n"));
+        assert!(keymap.contains("    modifier_map Mod2 { <I77> };\n"));
+        assert!(keymap.contains("    modifier_map Mod4 { <I133>, <I134> };\n"));
+    }
+}
+
+// Needs a running compositor with the virtual keyboard and pointer protocols, e.g.
+// `sway --headless` or `weston --backend=headless`:
+//   cargo test --features use_wayland compositor -- --ignored
+#[cfg(all(test, feature = "use_wayland"))]
+mod compositor_tests {
+    use crate::{InputEmulator, KeyCode};
+
+    #[test]
+    #[ignore = "needs a headless compositor"]
+    fn compositor_accepts_input() {
+        let mut emulator = InputEmulator::new().unwrap();
+        emulator.press(KeyCode::KEY_LEFTSHIFT).unwrap();
+        emulator.press(KeyCode::KEY_A).unwrap();
+        emulator.release(KeyCode::KEY_A).unwrap();
+        emulator.release(KeyCode::KEY_LEFTSHIFT).unwrap();
+        emulator.move_mouse(10, -10).unwrap();
+        emulator.scroll_y(1).unwrap();
+        emulator.press(KeyCode::MOUSE_LEFT).unwrap();
+        emulator.release(KeyCode::MOUSE_LEFT).unwrap();
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+
+#[cfg(feature = "xtest")]
+use color_eyre::eyre::bail;
+#[cfg(feature = "xtest")]
+use color_eyre::Result;
+#[cfg(feature = "xtest")]
+use x11rb::connection::{Connection, RequestConnection};
+#[cfg(feature = "xtest")]
+use x11rb::protocol::xproto::{self, ConnectionExt as _};
+#[cfg(feature = "xtest")]
+use x11rb::protocol::xtest::{self, ConnectionExt as _};
+#[cfg(feature = "xtest")]
+use x11rb::protocol::Event;
+#[cfg(feature = "xtest")]
+use x11rb::rust_connection::RustConnection;
+#[cfg(feature = "xtest")]
+use x11rb::wrapper::ConnectionExt as _;
+#[cfg(feature = "xtest")]
+use crate::{exec_or_eyre, OS_Input_Coord};
+
+// Core pointer buttons
+pub const X11_BUTTON_LEFT: u8 = 1;
+pub const X11_BUTTON_MIDDLE: u8 = 2;
+pub const X11_BUTTON_RIGHT: u8 = 3;
+pub const X11_WHEEL_UP: u8 = 4;
+pub const X11_WHEEL_DOWN: u8 = 5;
+pub const X11_WHEEL_LEFT: u8 = 6;
+pub const X11_WHEEL_RIGHT: u8 = 7;
+pub const X11_BUTTON_BACK: u8 = 8;
+pub const X11_BUTTON_FORWARD: u8 = 9;
+
+// Keysym to the first keycode producing it, lower columns (unshifted) win.
+// `keysyms` is the GetKeyboardMapping reply starting at `min_keycode`.
+pub fn keysym_keycodes(min_keycode: u8, keysyms_per_keycode: u8, keysyms: &[u32]) -> HashMap<u32, u8> {
+    let mut keycodes = HashMap::new();
+    if keysyms_per_keycode == 0 {
+        return keycodes;
+    }
+    for column in 0..keysyms_per_keycode as usize {
+        for (index, row) in keysyms.chunks(keysyms_per_keycode as usize).enumerate() {
+            let Some(&keysym) = row.get(column) else {
+                continue;
+            };
+            let Ok(keycode) = u8::try_from(min_keycode as usize + index) else {
+                break;
+            };
+            // NoSymbol
+            if keysym != 0 {
+                keycodes.entry(keysym).or_insert(keycode);
+            }
+        }
+    }
+    keycodes
+}
+
+// Fake input through the XTEST extension. Requests are buffered until flush().
+#[cfg(feature = "xtest")]
+pub struct XTestConnection {
+    connection: RustConnection,
+    root: xproto::Window,
+    keycodes: HashMap<u32, u8>,
+}
+
+#[cfg(feature = "xtest")]
+impl XTestConnection {
+    // None uses $DISPLAY, e.g. ":99" for an Xvfb started for tests
+    pub fn connect(display: Option<&str>) -> Result<Self> {
+        let (connection, screen) = exec_or_eyre!(x11rb::connect(display))?;
+        Self::from_connection(connection, screen)
+    }
+
+    pub fn from_connection(connection: RustConnection, screen: usize) -> Result<Self> {
+        let Some(root) = connection.setup().roots.get(screen).map(|screen| screen.root) else {
+            bail!("X server has no screen {screen}");
+        };
+        let extension = exec_or_eyre!(connection.extension_information(xtest::X11_EXTENSION_NAME))?;
+        if extension.is_none() {
+            bail!("X server doesn't support the XTEST extension");
+        }
+        let mut xtest = Self {
+            connection,
+            root,
+            keycodes: HashMap::new(),
+        };
+        xtest.load_keyboard_mapping()?;
+        Ok(xtest)
+    }
+
+    pub fn load_keyboard_mapping(&mut self) -> Result<()> {
+        let setup = self.connection.setup();
+        let (min_keycode, max_keycode) = (setup.min_keycode, setup.max_keycode);
+        let count = max_keycode - min_keycode + 1;
+        let cookie = exec_or_eyre!(self.connection.get_keyboard_mapping(min_keycode, count))?;
+        let mapping = exec_or_eyre!(cookie.reply())?;
+        self.keycodes = keysym_keycodes(min_keycode, mapping.keysyms_per_keycode, &mapping.keysyms);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn keycode(&self, keysym: u32) -> Result<u8> {
+        match self.keycodes.get(&keysym) {
+            Some(keycode) => Ok(*keycode),
+            None => bail!("No keycode produces keysym {keysym:#x} in the current keyboard mapping"),
+        }
+    }
+
+    #[inline]
+    fn fake_input(&self, event_type: u8, detail: u8, root: xproto::Window, x: i16, y: i16) -> Result<()> {
+        exec_or_eyre!(self.connection.xtest_fake_input(event_type, detail, x11rb::CURRENT_TIME, root, x, y, 0))?;
+        Ok(())
+    }
+
+    // XTestFakeKeyEvent
+    #[inline]
+    pub fn key(&self, keycode: u8, down: bool) -> Result<()> {
+        let event_type = if down { xproto::KEY_PRESS_EVENT } else { xproto::KEY_RELEASE_EVENT };
+        self.fake_input(event_type, keycode, x11rb::NONE, 0, 0)
+    }
+
+    // XTestFakeButtonEvent
+    #[inline]
+    pub fn button(&self, button: u8, down: bool) -> Result<()> {
+        let event_type = if down { xproto::BUTTON_PRESS_EVENT } else { xproto::BUTTON_RELEASE_EVENT };
+        self.fake_input(event_type, button, x11rb::NONE, 0, 0)
+    }
+
+    // XTestFakeRelativeMotionEvent, y grows downwards
+    #[inline]
+    pub fn relative_motion(&self, dx: OS_Input_Coord, dy: OS_Input_Coord) -> Result<()> {
+        let clamp = |value: OS_Input_Coord| value.clamp(i16::MIN as OS_Input_Coord, i16::MAX as OS_Input_Coord) as i16;
+        self.fake_input(xproto::MOTION_NOTIFY_EVENT, 1, x11rb::NONE, clamp(dx), clamp(dy))
+    }
+
+    // XTestFakeMotionEvent on the root window
+    #[inline]
+    pub fn absolute_motion(&self, x: i16, y: i16) -> Result<()> {
+        self.fake_input(xproto::MOTION_NOTIFY_EVENT, 0, self.root, x, y)
+    }
+
+    // Picks up MappingNotify so keycodes follow layout changes
+    pub fn flush(&mut self) -> Result<()> {
+        let mut mapping_changed = false;
+        while let Some(event) = exec_or_eyre!(self.connection.poll_for_event())? {
+            if let Event::MappingNotify(notify) = event
+                && notify.request == xproto::Mapping::KEYBOARD
+            {
+                mapping_changed = true;
+            }
+        }
+        if mapping_changed {
+            self.load_keyboard_mapping()?;
+        }
+        exec_or_eyre!(self.connection.flush())
+    }
+
+    // Round trip, reports errors of the requests sent so far
+    pub fn sync(&mut self) -> Result<()> {
+        exec_or_eyre!(self.connection.sync())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XK_A_LOWER: u32 = 0x61;
+    const XK_A_UPPER: u32 = 0x41;
+    const XK_1: u32 = 0x31;
+    const XK_EXCLAM: u32 = 0x21;
+
+    #[test]
+    fn lower_columns_win() {
+        // 8: 1 !   9: a A   10: ! (only column), 11: a again
+        let keysyms = [XK_1, XK_EXCLAM, XK_A_LOWER, XK_A_UPPER, XK_EXCLAM, 0, XK_A_LOWER, XK_A_UPPER];
+        let keycodes = keysym_keycodes(8, 2, &keysyms);
+        assert_eq!(keycodes.len(), 4);
+        assert_eq!(keycodes[&XK_1], 8);
+        // Unshifted on 10 beats shifted on 8
+        assert_eq!(keycodes[&XK_EXCLAM], 10);
+        // Same column, the lower keycode wins
+        assert_eq!(keycodes[&XK_A_LOWER], 9);
+        assert_eq!(keycodes[&XK_A_UPPER], 9);
+    }
+
+    #[test]
+    fn no_symbol_is_skipped() {
+        let keycodes = keysym_keycodes(8, 3, &[0, 0, 0, 0, XK_A_LOWER, 0]);
+        assert_eq!(keycodes, HashMap::from([(XK_A_LOWER, 9)]));
+        assert!(keysym_keycodes(8, 0, &[XK_A_LOWER]).is_empty());
+    }
+
+    #[test]
+    fn keycodes_stop_at_255() {
+        // Rows 250..=259, only 250..=255 fit in a keycode
+        let keysyms: Vec<u32> = (0..10).flat_map(|row| [0x100 + row, 0x200 + row]).collect();
+        let keycodes = keysym_keycodes(250, 2, &keysyms);
+        assert_eq!(keycodes.len(), 12);
+        assert_eq!(keycodes[&0x100], 250);
+        assert_eq!(keycodes[&0x205], 255);
+        assert!(!keycodes.contains_key(&0x106));
+        assert!(!keycodes.contains_key(&0x209));
+    }
+
+    #[test]
+    fn short_last_row() {
+        let keycodes = keysym_keycodes(8, 2, &[XK_1, XK_EXCLAM, XK_A_LOWER]);
+        assert_eq!(keycodes[&XK_A_LOWER], 9);
+        assert_eq!(keycodes.len(), 3);
+    }
+}
+
+// Needs an X server with XTEST, e.g. `Xvfb :99 & DISPLAY=:99 cargo test --features use_xtest -- --ignored`.
+// Moves the pointer, the old position is restored at the end.
+#[cfg(all(test, feature = "xtest"))]
+mod display_tests {
+    use super::*;
+
+    fn pointer(xtest: &XTestConnection) -> (i16, i16) {
+        let reply = xtest.connection.query_pointer(xtest.root).unwrap().reply().unwrap();
+        (reply.root_x, reply.root_y)
+    }
+
+    #[test]
+    #[ignore = "needs an X server and moves the pointer"]
+    fn fake_input_on_display() {
+        let mut xtest = XTestConnection::connect(None).unwrap();
+        let original = pointer(&xtest);
+
+        xtest.absolute_motion(10, 20).unwrap();
+        xtest.sync().unwrap();
+        assert_eq!(pointer(&xtest), (10, 20));
+        xtest.relative_motion(5, 7).unwrap();
+        xtest.sync().unwrap();
+        assert_eq!(pointer(&xtest), (15, 27));
+
+        // Xvfb's default map has both, Shift_L alone doesn't type anything
+        let shift = xtest.keycode(0xffe1).unwrap();
+        assert!(xtest.keycode(0x61).is_ok());
+        xtest.key(shift, true).unwrap();
+        xtest.key(shift, false).unwrap();
+        xtest.flush().unwrap();
+        xtest.sync().unwrap();
+        assert!(xtest.keycode(0).is_err());
+
+        xtest.absolute_motion(original.0, original.1).unwrap();
+        xtest.sync().unwrap();
+    }
+}